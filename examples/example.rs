@@ -20,4 +20,4 @@ fn main() {
     for (word, count) in word_count.iter() {
         println!("{}: {}", word, count);
     }
-}
\ No newline at end of file
+}
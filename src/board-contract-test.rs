@@ -0,0 +1,310 @@
+#[cfg(test)]
+mod tests {
+    use mockito::{Matcher, Mock, Server};
+    use serde_json::json;
+
+    use crate::{
+        gitea_integration::GiteaIntegration,
+        github_integration::GitHubIntegration,
+        gitlab_integration::GitLabIntegration,
+        issue_repository::{
+            Issue, IssueBoard, IssueId, IssueState, IssueUpdateRequest, Label, User,
+        },
+        jira_integration::{JiraAuth, JiraIntegration},
+        local_board::LocalBoard,
+        todofinder::IssueType,
+    };
+
+    const NUMBER: u32 = 5;
+
+    /// The scenario every board has to pass: create an issue, read it back, rename it, close
+    /// it, and fail cleanly on an issue that doesn't exist.
+    async fn run_scenario(board: &dyn IssueBoard, expected: IssueId, missing: IssueId) {
+        let id = board
+            .add_issue(Issue {
+                number: None,
+                title: "contract issue".to_string(),
+                author: User {
+                    name: "OthelloEngineer".to_string(),
+                },
+                assignee: None,
+                issue_type: vec![Label {
+                    name: "bug".to_string(),
+                }],
+                state: "open".to_string(),
+                body: Some("found in src/main.rs".to_string()),
+                source: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(id, expected);
+
+        let issue = board.get_issue(&id).await.unwrap();
+        assert_eq!(issue.number, Some(expected));
+        assert_eq!(issue.title, "contract issue");
+        assert_eq!(issue.author.name, "OthelloEngineer");
+        assert_eq!(issue.issue_type(), IssueType::Bug);
+        assert_eq!(issue.state().as_str(), "open");
+
+        board
+            .update_issue(&id, &IssueUpdateRequest::Title("renamed".to_string()))
+            .await
+            .unwrap();
+        board
+            .update_issue(&id, &IssueUpdateRequest::State(IssueState::Closed))
+            .await
+            .unwrap();
+
+        assert!(board.get_issue(&missing).await.is_err());
+    }
+
+    async fn assert_all(mocks: Vec<Mock>) {
+        for mock in mocks {
+            mock.assert_async().await;
+        }
+    }
+
+    async fn github_stand_in(server: &mut Server) -> Vec<Mock> {
+        let issue = json!({
+            "number": NUMBER, "title": "contract issue", "body": "found in src/main.rs",
+            "state": "open", "user": {"login": "OthelloEngineer"}, "assignee": null,
+            "labels": [{"name": "bug"}]
+        })
+        .to_string();
+        let path = format!("/repos/OthelloEngineer/tissue/issues/{}", NUMBER);
+        vec![
+            server
+                .mock("POST", "/repos/OthelloEngineer/tissue/issues")
+                .match_header("authorization", "Bearer secret")
+                .match_body(Matcher::PartialJson(
+                    json!({"title": "contract issue", "labels": ["bug"]}),
+                ))
+                .with_status(201)
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("GET", path.as_str())
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PATCH", path.as_str())
+                .match_body(Matcher::Json(json!({"title": "renamed"})))
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PATCH", path.as_str())
+                .match_body(Matcher::Json(json!({"state": "closed"})))
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/repos/OthelloEngineer/tissue/issues/404")
+                .with_status(404)
+                .create_async()
+                .await,
+        ]
+    }
+
+    async fn gitlab_stand_in(server: &mut Server) -> Vec<Mock> {
+        let issue = json!({
+            "id": 90211, "iid": NUMBER, "title": "contract issue",
+            "description": "found in src/main.rs", "state": "opened",
+            "author": {"id": 1, "username": "OthelloEngineer"}, "assignees": [],
+            "labels": ["bug"]
+        })
+        .to_string();
+        let path = format!("/api/v4/projects/ops%2Ftissue/issues/{}", NUMBER);
+        vec![
+            server
+                .mock("POST", "/api/v4/projects/ops%2Ftissue/issues")
+                .match_header("private-token", "secret")
+                .match_body(Matcher::PartialJson(
+                    json!({"title": "contract issue", "labels": "bug"}),
+                ))
+                .with_status(201)
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("GET", path.as_str())
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PUT", path.as_str())
+                .match_body(Matcher::Json(json!({"title": "renamed"})))
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PUT", path.as_str())
+                .match_body(Matcher::Json(json!({"state_event": "close"})))
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/api/v4/projects/ops%2Ftissue/issues/404")
+                .with_status(404)
+                .create_async()
+                .await,
+        ]
+    }
+
+    async fn gitea_stand_in(server: &mut Server) -> Vec<Mock> {
+        let issue = json!({
+            "number": NUMBER, "title": "contract issue", "body": "found in src/main.rs",
+            "state": "open", "user": {"login": "OthelloEngineer"}, "assignee": null,
+            "labels": [{"id": 12, "name": "bug"}]
+        })
+        .to_string();
+        let path = format!("/api/v1/repos/tools/tissue/issues/{}", NUMBER);
+        vec![
+            server
+                .mock("GET", "/api/v1/repos/tools/tissue/labels")
+                .match_query(Matcher::Any)
+                .with_body(r#"[{"id": 12, "name": "bug"}]"#)
+                .create_async()
+                .await,
+            server
+                .mock("POST", "/api/v1/repos/tools/tissue/issues")
+                .match_header("authorization", "token secret")
+                .match_body(Matcher::PartialJson(
+                    json!({"title": "contract issue", "labels": [12]}),
+                ))
+                .with_status(201)
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("GET", path.as_str())
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PATCH", path.as_str())
+                .match_body(Matcher::Json(json!({"title": "renamed"})))
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PATCH", path.as_str())
+                .match_body(Matcher::Json(json!({"state": "closed"})))
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/api/v1/repos/tools/tissue/issues/404")
+                .with_status(404)
+                .create_async()
+                .await,
+        ]
+    }
+
+    async fn jira_stand_in(server: &mut Server) -> Vec<Mock> {
+        let issue = json!({
+            "key": "PROJ-5",
+            "fields": {
+                "summary": "contract issue", "description": "found in src/main.rs",
+                "status": {"name": "To Do", "statusCategory": {"key": "new"}},
+                "reporter": {"name": "OthelloEngineer"}, "assignee": null,
+                "labels": [], "issuetype": {"name": "Bug"}
+            }
+        })
+        .to_string();
+        vec![
+            server
+                .mock("POST", "/rest/api/2/issue")
+                .match_header("authorization", "Bearer secret")
+                .match_body(Matcher::PartialJson(json!({"fields": {
+                    "project": {"key": "PROJ"}, "summary": "contract issue",
+                    "issuetype": {"name": "Bug"}
+                }})))
+                .with_status(201)
+                .with_body(r#"{"id": "10005", "key": "PROJ-5"}"#)
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/rest/api/2/issue/PROJ-5")
+                .with_body(&issue)
+                .create_async()
+                .await,
+            server
+                .mock("PUT", "/rest/api/2/issue/PROJ-5")
+                .match_body(Matcher::Json(json!({"fields": {"summary": "renamed"}})))
+                .with_status(204)
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/rest/api/2/issue/PROJ-5/transitions")
+                .with_body(
+                    r#"{"transitions": [{"id": "31", "name": "Done", "to": {"name": "Done"}}]}"#,
+                )
+                .create_async()
+                .await,
+            server
+                .mock("POST", "/rest/api/2/issue/PROJ-5/transitions")
+                .match_body(Matcher::Json(json!({"transition": {"id": "31"}})))
+                .with_status(204)
+                .create_async()
+                .await,
+            server
+                .mock("GET", "/rest/api/2/issue/PROJ-404")
+                .with_status(404)
+                .create_async()
+                .await,
+        ]
+    }
+
+    #[tokio::test]
+    async fn github_fulfils_board_contract() {
+        let mut server = Server::new_async().await;
+        let mocks = github_stand_in(&mut server).await;
+        let board = GitHubIntegration::new("OthelloEngineer", "tissue")
+            .await
+            .with_base_url(&server.url())
+            .with_token("secret");
+        run_scenario(&board, IssueId::from(NUMBER), IssueId::from(404)).await;
+        assert_all(mocks).await;
+    }
+
+    #[tokio::test]
+    async fn gitlab_fulfils_board_contract() {
+        let mut server = Server::new_async().await;
+        let mocks = gitlab_stand_in(&mut server).await;
+        let board = GitLabIntegration::new(&server.url(), "ops/tissue", "secret");
+        run_scenario(&board, IssueId::from(NUMBER), IssueId::from(404)).await;
+        assert_all(mocks).await;
+    }
+
+    #[tokio::test]
+    async fn gitea_fulfils_board_contract() {
+        let mut server = Server::new_async().await;
+        let mocks = gitea_stand_in(&mut server).await;
+        let board = GiteaIntegration::new(&server.url(), "tools", "tissue", "secret");
+        run_scenario(&board, IssueId::from(NUMBER), IssueId::from(404)).await;
+        assert_all(mocks).await;
+    }
+
+    #[tokio::test]
+    async fn jira_fulfils_board_contract() {
+        let mut server = Server::new_async().await;
+        let mocks = jira_stand_in(&mut server).await;
+        let board = JiraIntegration::new(
+            &server.url(),
+            "PROJ",
+            JiraAuth::Bearer("secret".to_string()),
+        );
+        run_scenario(&board, IssueId::from("PROJ-5"), IssueId::from("PROJ-404")).await;
+        assert_all(mocks).await;
+    }
+
+    #[tokio::test]
+    async fn local_board_fulfils_board_contract() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = LocalBoard::new(directory.path());
+        run_scenario(&board, IssueId::from(1), IssueId::from(404)).await;
+    }
+}
@@ -0,0 +1,740 @@
+//! Project configuration: `tissue.toml` at the repository root, layered over the defaults and a
+//! user-level `~/.config/tissue/tissue.toml`, and overridden by `TISSUE_<SECTION>__<KEY>`
+//! environment variables. Every value remembers which layer it came from.
+use std::{
+    collections::BTreeMap,
+    env, fmt, fs, io,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use toml::{Table, Value};
+
+use crate::{
+    gitea_integration::GiteaIntegration,
+    github_integration::GitHubIntegration,
+    gitlab_integration::GitLabIntegration,
+    issue_repository::IssueState,
+    issue_repository::{IssueBoard, IssueTemplate},
+    jira_integration::{JiraAuth, JiraFieldMapping, JiraIntegration},
+    local_board::LocalBoard,
+    policy::Policy,
+    routing::{Router, RoutingRule},
+    stale::StaleRule,
+    tissuebox_file::TissueBoxFile,
+    todofinder::{IssueType, Syntax},
+};
+
+/// Name of the project configuration file, at the repository root.
+pub const FILE_NAME: &str = "tissue.toml";
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub scan: ScanConfig,
+    pub parse: ParseConfig,
+    pub tissue_box: TissueBoxConfig,
+    pub boards: BoardsConfig,
+    pub routing: RoutingConfig,
+    pub labels: LabelsConfig,
+    pub templates: TemplatesConfig,
+    pub policy: PolicyConfig,
+    pub stale: StaleConfig,
+}
+
+/// Which lines are ToDo comments.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ScanConfig {
+    pub comment_markers: Vec<String>,
+    pub keywords: Vec<String>,
+}
+
+/// The words accepted as each issue type, e.g. `bug = ["bug", "fix"]`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ParseConfig {
+    pub bug: Vec<String>,
+    pub feature: Vec<String>,
+    pub improvement: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TissueBoxConfig {
+    /// Directory holding tissue_box.json, relative to the repository root.
+    pub path: String,
+}
+
+/// Where the boards live and which environment variables hold their credentials.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoardsConfig {
+    pub github_url: String,
+    pub github_token_env: String,
+    pub gitlab_url: String,
+    pub gitlab_token_env: String,
+    pub gitea_url: String,
+    pub gitea_token_env: String,
+    pub jira_url: String,
+    /// Set for Jira Cloud's e-mail and API token; without it the token is a personal access token.
+    pub jira_email_env: String,
+    pub jira_token_env: String,
+    pub jira: JiraConfig,
+}
+
+/// How a Jira project names things, under `[boards.jira]`. Unset issue types and transitions
+/// keep Jira's built-in names; the `*_field` keys are custom field IDs such as
+/// `customfield_10042` that receive the ToDo's location and author.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct JiraConfig {
+    pub bug_type: Option<String>,
+    pub feature_type: Option<String>,
+    pub improvement_type: Option<String>,
+    pub other_type: Option<String>,
+    pub open_transition: Option<String>,
+    pub closed_transition: Option<String>,
+    pub reopened_transition: Option<String>,
+    pub file_field: Option<String>,
+    pub line_field: Option<String>,
+    pub author_field: Option<String>,
+}
+
+/// Routing rules such as `services/billing/** -> jira:BILL`. When set, they replace the rules
+/// stored in the Tissue Box.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RoutingConfig {
+    pub rules: Vec<String>,
+}
+
+/// Labels added to new issues of each type, besides the type itself.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LabelsConfig {
+    pub bug: Vec<String>,
+    pub feature: Vec<String>,
+    pub improvement: Vec<String>,
+    pub other: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TemplatesConfig {
+    /// Body of new issues; see `IssueTemplate` for the placeholders.
+    pub issue_body: String,
+}
+
+/// Defaults for `tissue check`; its flags add to these.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PolicyConfig {
+    pub require_issue_type: bool,
+    pub require_link: bool,
+    pub protected_paths: Vec<String>,
+    pub max_todos: Option<usize>,
+}
+
+/// Rules for `tissue stale`, e.g. a `[[stale.rules]]` with `older_than_days = 180` and
+/// `escalations = [{ add_label = "priority:high" }, "fail_ci"]`. When set, they replace the
+/// default rules.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct StaleConfig {
+    pub rules: Vec<StaleRule>,
+}
+
+impl Default for ScanConfig {
+    fn default() -> ScanConfig {
+        let syntax = Syntax::default();
+        ScanConfig {
+            comment_markers: syntax.comment_markers,
+            keywords: syntax.keywords,
+        }
+    }
+}
+
+impl Default for ParseConfig {
+    fn default() -> ParseConfig {
+        let name = |issue_type: IssueType| vec![issue_type.as_str().to_string()];
+        ParseConfig {
+            bug: name(IssueType::Bug),
+            feature: name(IssueType::Feature),
+            improvement: name(IssueType::Improvement),
+        }
+    }
+}
+
+impl Default for TissueBoxConfig {
+    fn default() -> TissueBoxConfig {
+        TissueBoxConfig {
+            path: String::from("."),
+        }
+    }
+}
+
+impl Default for BoardsConfig {
+    fn default() -> BoardsConfig {
+        BoardsConfig {
+            github_url: String::from("https://api.github.com"),
+            github_token_env: String::from("TISSUE_GITHUB_TOKEN"),
+            gitlab_url: String::from("https://gitlab.com"),
+            gitlab_token_env: String::from("TISSUE_GITLAB_TOKEN"),
+            gitea_url: String::new(),
+            gitea_token_env: String::from("TISSUE_GITEA_TOKEN"),
+            jira_url: String::new(),
+            jira_email_env: String::from("TISSUE_JIRA_EMAIL"),
+            jira_token_env: String::from("TISSUE_JIRA_TOKEN"),
+            jira: JiraConfig::default(),
+        }
+    }
+}
+
+impl Default for TemplatesConfig {
+    fn default() -> TemplatesConfig {
+        TemplatesConfig {
+            issue_body: IssueTemplate::default().body,
+        }
+    }
+}
+
+impl Config {
+    pub fn syntax(&self) -> Syntax {
+        Syntax {
+            comment_markers: self.scan.comment_markers.clone(),
+            keywords: self.scan.keywords.clone(),
+            issue_types: vec![
+                (IssueType::Bug, self.parse.bug.clone()),
+                (IssueType::Feature, self.parse.feature.clone()),
+                (IssueType::Improvement, self.parse.improvement.clone()),
+            ],
+        }
+    }
+
+    pub fn issue_template(&self) -> IssueTemplate {
+        IssueTemplate {
+            body: self.templates.issue_body.clone(),
+            labels: vec![
+                (IssueType::Bug, self.labels.bug.clone()),
+                (IssueType::Feature, self.labels.feature.clone()),
+                (IssueType::Improvement, self.labels.improvement.clone()),
+                (IssueType::Other, self.labels.other.clone()),
+            ],
+        }
+    }
+
+    pub fn policy(&self) -> Policy {
+        Policy {
+            require_issue_type: self.policy.require_issue_type,
+            require_link: self.policy.require_link,
+            protected_paths: self.policy.protected_paths.clone(),
+            max_todos: self.policy.max_todos,
+        }
+    }
+
+    /// The configured stale rules, or else ToDos older than `older_than_days` and past due ones.
+    pub fn stale_rules(&self, older_than_days: i64) -> Vec<StaleRule> {
+        if !self.stale.rules.is_empty() {
+            return self.stale.rules.clone();
+        }
+        vec![
+            StaleRule::older_than(
+                &format!("older than {} days", older_than_days),
+                older_than_days,
+            ),
+            StaleRule::past_due("past due"),
+        ]
+    }
+
+    /// The directory of the Tissue Box of the repository at `root`.
+    pub fn box_path(&self, root: &Path) -> String {
+        root.join(&self.tissue_box.path).display().to_string()
+    }
+
+    /// The configured routing rules, or the Tissue Box's own if there are none.
+    pub fn router(&self, tissue_box: &TissueBoxFile) -> Result<Router> {
+        if self.routing.rules.is_empty() {
+            return tissue_box.router();
+        }
+        let rules = self
+            .routing
+            .rules
+            .iter()
+            .map(|rule| rule.parse())
+            .collect::<Result<Vec<RoutingRule>>>()?;
+        Router::new(&rules)
+    }
+}
+
+impl BoardsConfig {
+    /// Connects to the board a name such as `github:org/repo` or `jira:KEY` refers to.
+    pub async fn connect(&self, name: &str) -> Result<Box<dyn IssueBoard>> {
+        let (kind, target) = name.split_once(':').unwrap_or((name, ""));
+        match (kind, target.split_once('/')) {
+            ("github", Some((org, project))) => {
+                let mut github = GitHubIntegration::new(org, project)
+                    .await
+                    .with_base_url(&self.github_url);
+                if let Ok(token) = env::var(&self.github_token_env) {
+                    github = github.with_token(&token);
+                }
+                Ok(Box::new(github))
+            }
+            ("gitlab", _) => {
+                let token = required_env(&self.gitlab_token_env, name)?;
+                Ok(Box::new(GitLabIntegration::new(
+                    &self.gitlab_url,
+                    target,
+                    &token,
+                )))
+            }
+            ("gitea", Some((owner, repo))) => {
+                let url = required_url(&self.gitea_url, "boards.gitea_url", name)?;
+                let token = required_env(&self.gitea_token_env, name)?;
+                Ok(Box::new(GiteaIntegration::new(url, owner, repo, &token)))
+            }
+            ("jira", _) => {
+                let url = required_url(&self.jira_url, "boards.jira_url", name)?;
+                let api_token = required_env(&self.jira_token_env, name)?;
+                let auth = match env::var(&self.jira_email_env) {
+                    Ok(email) => JiraAuth::Basic { email, api_token },
+                    Err(_) => JiraAuth::Bearer(api_token),
+                };
+                Ok(Box::new(
+                    self.jira.apply(JiraIntegration::new(url, target, auth)),
+                ))
+            }
+            ("local", _) => Ok(Box::new(LocalBoard::new(target))),
+            _ => Err(color_eyre::Report::msg(format!(
+                "Can't connect to board '{}'; expected github:org/repo, gitlab:project, \
+                 gitea:owner/repo, jira:KEY or local:dir",
+                name
+            ))),
+        }
+    }
+}
+
+impl JiraConfig {
+    fn apply(&self, mut jira: JiraIntegration) -> JiraIntegration {
+        let issue_types = [
+            (IssueType::Bug, &self.bug_type),
+            (IssueType::Feature, &self.feature_type),
+            (IssueType::Improvement, &self.improvement_type),
+            (IssueType::Other, &self.other_type),
+        ];
+        for (issue_type, jira_type) in issue_types {
+            if let Some(jira_type) = jira_type {
+                jira = jira.with_issue_type(issue_type, jira_type);
+            }
+        }
+        let transitions = [
+            (IssueState::Open, &self.open_transition),
+            (IssueState::Closed, &self.closed_transition),
+            (IssueState::Reopened, &self.reopened_transition),
+        ];
+        for (state, transition) in transitions {
+            if let Some(transition) = transition {
+                jira = jira.with_transition(state, transition);
+            }
+        }
+        jira.with_custom_fields(JiraFieldMapping {
+            file: self.file_field.clone(),
+            line: self.line_field.clone(),
+            author: self.author_field.clone(),
+        })
+    }
+}
+
+fn required_env(variable: &str, board: &str) -> Result<String> {
+    env::var(variable)
+        .map_err(|_| color_eyre::Report::msg(format!("Set {} to connect to {}", variable, board)))
+}
+
+fn required_url<'a>(url: &'a str, key: &str, board: &str) -> Result<&'a str> {
+    if url.is_empty() {
+        return Err(color_eyre::Report::msg(format!(
+            "Set {} in {} to connect to {}",
+            key, FILE_NAME, board
+        )));
+    }
+    Ok(url)
+}
+
+/// Where a configuration value came from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Source {
+    Default,
+    File(PathBuf),
+    Env(String),
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Default => write!(f, "default"),
+            Source::File(path) => write!(f, "{}", path.display()),
+            Source::Env(variable) => write!(f, "env {}", variable),
+        }
+    }
+}
+
+/// Configuration values by dotted key, e.g. `scan.keywords`, each from the last layer that set
+/// it.
+#[derive(Clone, Debug)]
+pub struct LayeredConfig {
+    values: BTreeMap<String, (Value, Source)>,
+}
+
+impl Default for LayeredConfig {
+    /// Just the defaults.
+    fn default() -> LayeredConfig {
+        let Ok(Value::Table(defaults)) = Value::try_from(Config::default()) else {
+            unreachable!("the default configuration serializes to a table");
+        };
+        let mut values = BTreeMap::new();
+        flatten("", defaults, &Source::Default, &mut values);
+        LayeredConfig { values }
+    }
+}
+
+impl LayeredConfig {
+    /// Layers the TOML file at `path` over the values so far, if it exists.
+    pub fn with_file(mut self, path: &Path) -> Result<LayeredConfig> {
+        let text = match fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => {
+                return Err(color_eyre::Report::msg(format!(
+                    "Can't read the configuration in {}: {}",
+                    path.display(),
+                    e
+                )))
+            }
+        };
+        let table: Table = toml::from_str(&text).map_err(|e| {
+            color_eyre::Report::msg(format!(
+                "Invalid configuration in {}: {}",
+                path.display(),
+                e
+            ))
+        })?;
+        Value::Table(table.clone())
+            .try_into::<Config>()
+            .map_err(|e| {
+                color_eyre::Report::msg(format!(
+                    "Invalid configuration in {}: {}",
+                    path.display(),
+                    e
+                ))
+            })?;
+        flatten(
+            "",
+            table,
+            &Source::File(path.to_path_buf()),
+            &mut self.values,
+        );
+        Ok(self)
+    }
+
+    /// Layers the `TISSUE_<SECTION>__<KEY>` variables among `variables` over the values so far,
+    /// e.g. `TISSUE_POLICY__MAX_TODOS=20`. Values are read as TOML, falling back to a string.
+    pub fn with_env(
+        mut self,
+        variables: impl IntoIterator<Item = (String, String)>,
+    ) -> Result<LayeredConfig> {
+        for (variable, raw) in variables {
+            let Some(name) = variable.strip_prefix("TISSUE_") else {
+                continue;
+            };
+            let Some((section, key)) = name.split_once("__") else {
+                continue;
+            };
+            let key = format!("{}.{}", section.to_lowercase(), key.to_lowercase());
+            let value = toml::from_str::<Table>(&format!("value = {}", raw))
+                .ok()
+                .and_then(|mut table| table.remove("value"))
+                .unwrap_or_else(|| Value::String(raw.clone()));
+            self.values.insert(key, (value, Source::Env(variable)));
+        }
+        self.config()?;
+        Ok(self)
+    }
+
+    pub fn config(&self) -> Result<Config> {
+        let mut table = Table::new();
+        for (key, (value, _)) in &self.values {
+            let mut section = &mut table;
+            let mut parts: Vec<&str> = key.split('.').collect();
+            let last = parts.pop().unwrap_or_default();
+            for part in parts {
+                section = match section
+                    .entry(part)
+                    .or_insert_with(|| Value::Table(Table::new()))
+                {
+                    Value::Table(table) => table,
+                    _ => unreachable!("sections are tables"),
+                };
+            }
+            section.insert(last.to_string(), value.clone());
+        }
+        Value::Table(table)
+            .try_into()
+            .map_err(|e| color_eyre::Report::msg(format!("Invalid configuration: {}", e)))
+    }
+
+    pub fn source(&self, key: &str) -> Option<&Source> {
+        self.values.get(key).map(|(_, source)| source)
+    }
+
+    /// Every value as `key = value  # source`, in key order.
+    pub fn show(&self) -> String {
+        self.values
+            .iter()
+            .map(|(key, (value, source))| {
+                let value = match value {
+                    // Keeps multi-line strings such as templates on one line.
+                    Value::String(string) => format!("{:?}", string),
+                    value => value.to_string(),
+                };
+                format!("{} = {}  # {}\n", key, value, source)
+            })
+            .collect()
+    }
+}
+
+fn flatten(
+    prefix: &str,
+    table: Table,
+    source: &Source,
+    values: &mut BTreeMap<String, (Value, Source)>,
+) {
+    for (key, value) in table {
+        let key = match prefix {
+            "" => key,
+            prefix => format!("{}.{}", prefix, key),
+        };
+        match value {
+            Value::Table(table) => flatten(&key, table, source, values),
+            value => {
+                values.insert(key, (value, source.clone()));
+            }
+        }
+    }
+}
+
+/// The user-level configuration file, `$XDG_CONFIG_HOME/tissue/tissue.toml` or
+/// `~/.config/tissue/tissue.toml`.
+pub fn user_file() -> Option<PathBuf> {
+    let directory = match env::var_os("XDG_CONFIG_HOME") {
+        Some(directory) if !directory.is_empty() => PathBuf::from(directory),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".config"),
+    };
+    Some(directory.join("tissue").join(FILE_NAME))
+}
+
+/// The configuration of the repository at `root`: defaults, then the user's file, then the
+/// project's `tissue.toml`, then the environment.
+pub fn load(root: &Path) -> Result<LayeredConfig> {
+    let mut layered = LayeredConfig::default();
+    if let Some(user_file) = user_file() {
+        layered = layered.with_file(&user_file)?;
+    }
+    layered
+        .with_file(&root.join(FILE_NAME))?
+        .with_env(env::vars())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{stale::Escalation, todofinder::Submission};
+
+    #[test]
+    fn layers_files_and_environment_with_their_sources() {
+        let directory = tempfile::tempdir().unwrap();
+        let user = directory.path().join("user.toml");
+        let project = directory.path().join(FILE_NAME);
+        fs::write(
+            &user,
+            "[scan]\nkeywords = [\"todo\", \"fixme\"]\n[policy]\nrequire_link = true\n",
+        )
+        .unwrap();
+        fs::write(
+            &project,
+            "[scan]\nkeywords = [\"todo\", \"hack\"]\n[labels]\nbug = [\"defect\"]\n",
+        )
+        .unwrap();
+
+        let layered = LayeredConfig::default()
+            .with_file(&user)
+            .unwrap()
+            .with_file(&project)
+            .unwrap()
+            .with_file(&directory.path().join("missing.toml"))
+            .unwrap();
+        assert_eq!(layered.config().unwrap().scan.keywords, ["todo", "hack"]);
+        let unknown = [(String::from("TISSUE_BOX__PATH"), String::from("meta"))];
+        assert!(layered.clone().with_env(unknown).is_err());
+
+        let layered = LayeredConfig::default()
+            .with_file(&user)
+            .unwrap()
+            .with_file(&project)
+            .unwrap()
+            .with_env([
+                (String::from("TISSUE_POLICY__MAX_TODOS"), String::from("20")),
+                (String::from("TISSUE_GITHUB_TOKEN"), String::from("secret")),
+                (
+                    String::from("TISSUE_TISSUE_BOX__PATH"),
+                    String::from("meta"),
+                ),
+            ])
+            .unwrap();
+        let config = layered.config().unwrap();
+
+        assert_eq!(config.scan.keywords, ["todo", "hack"]);
+        assert!(config.policy.require_link);
+        assert_eq!(config.policy.max_todos, Some(20));
+        assert_eq!(config.tissue_box.path, "meta");
+        assert_eq!(
+            layered.source("scan.keywords"),
+            Some(&Source::File(project))
+        );
+        assert_eq!(
+            layered.source("policy.require_link"),
+            Some(&Source::File(user))
+        );
+        assert_eq!(
+            layered.source("policy.max_todos"),
+            Some(&Source::Env(String::from("TISSUE_POLICY__MAX_TODOS")))
+        );
+        assert_eq!(
+            layered.source("scan.comment_markers"),
+            Some(&Source::Default)
+        );
+        let shown = layered.show();
+        assert!(shown.contains("policy.max_todos = 20  # env TISSUE_POLICY__MAX_TODOS\n"));
+        assert!(shown.contains("tissue_box.path = \"meta\"  # env TISSUE_TISSUE_BOX__PATH\n"));
+        assert!(shown.contains("boards.github_token_env = \"TISSUE_GITHUB_TOKEN\"  # default\n"));
+    }
+
+    #[test]
+    fn reads_stale_rules_with_escalations() {
+        let directory = tempfile::tempdir().unwrap();
+        let project = directory.path().join(FILE_NAME);
+        fs::write(
+            &project,
+            "[[stale.rules]]\nname = \"ancient\"\nolder_than_days = 365\n\
+             escalations = [{ add_label = \"priority:high\" }, \"fail_ci\"]\n",
+        )
+        .unwrap();
+
+        let config = LayeredConfig::default()
+            .with_file(&project)
+            .unwrap()
+            .config()
+            .unwrap();
+
+        assert_eq!(
+            config.stale_rules(90),
+            [StaleRule::older_than("ancient", 365)
+                .with_escalation(Escalation::AddLabel("priority:high".to_string()))
+                .with_escalation(Escalation::FailCi)]
+        );
+        assert_eq!(
+            Config::default().stale_rules(30)[0].older_than_days,
+            Some(30)
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_keys_naming_the_file() {
+        let directory = tempfile::tempdir().unwrap();
+        let project = directory.path().join(FILE_NAME);
+        fs::write(&project, "[scan]\nkeyword = [\"todo\"]\n").unwrap();
+
+        let error = LayeredConfig::default().with_file(&project).unwrap_err();
+
+        assert!(error.to_string().contains(&project.display().to_string()));
+        assert!(error.to_string().contains("keyword"));
+    }
+
+    #[test]
+    fn fails_on_unreadable_files_naming_them() {
+        let directory = tempfile::tempdir().unwrap();
+        let project = directory.path().join(FILE_NAME);
+        assert!(LayeredConfig::default().with_file(&project).is_ok());
+
+        fs::write(&project, b"[scan]\nkeywords = [\"\xff\"]\n").unwrap();
+        let error = LayeredConfig::default().with_file(&project).unwrap_err();
+        assert!(error.to_string().contains(&project.display().to_string()));
+    }
+
+    #[tokio::test]
+    async fn connects_to_jira_with_its_issue_types_and_fields() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/rest/api/2/issue")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "fields": {
+                    "issuetype": { "name": "Defect" },
+                    "customfield_10042": "src/main.rs",
+                    "customfield_10043": 4
+                }
+            })))
+            .with_body(r#"{"key": "BILL-1"}"#)
+            .create_async()
+            .await;
+        let mut config: Config = toml::from_str(
+            "[boards]\njira_token_env = \"TISSUE_TEST_JIRA_CONFIG_TOKEN\"\n\
+             [boards.jira]\nbug_type = \"Defect\"\nclosed_transition = \"Resolve\"\n\
+             file_field = \"customfield_10042\"\nline_field = \"customfield_10043\"\n",
+        )
+        .unwrap();
+        config.boards.jira_url = server.url();
+        env::set_var("TISSUE_TEST_JIRA_CONFIG_TOKEN", "secret");
+
+        let board = config.boards.connect("jira:BILL").await.unwrap();
+        let todo = crate::test_fixtures::todo("src/main.rs", 4, "flaky retries");
+        let id = board
+            .add_issue(config.issue_template().issue(&todo))
+            .await
+            .unwrap();
+
+        assert_eq!(id, crate::issue_repository::IssueId::from("BILL-1"));
+        mock.assert_async().await;
+    }
+
+    #[test]
+    fn configures_parsing_and_new_issues() {
+        let config: Config = toml::from_str(
+            "[scan]\nkeywords = [\"fixme\"]\n\
+             [parse]\nbug = [\"bug\", \"defect\"]\n\
+             [labels]\nbug = [\"tissue\"]\n\
+             [templates]\nissue_body = \"{type} in {file}:{line}\"\n",
+        )
+        .unwrap();
+        let syntax = config.syntax();
+        assert!(syntax.is_to_do("// FIXME defect: flaky"));
+        assert!(!syntax.is_to_do("// TODO defect: flaky"));
+
+        let todo = syntax
+            .parse(Submission {
+                line_number: 4,
+                line: String::from("//FIXME defect: flaky retries"),
+                file_path: String::from("src/main.rs"),
+                issuer: String::from("OthelloEngineer"),
+                date: String::new(),
+            })
+            .unwrap();
+        assert_eq!(todo.issue_type, IssueType::Bug);
+
+        let issue = config.issue_template().issue(&todo);
+        let labels: Vec<&str> = issue.issue_type.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(labels, ["bug", "tissue"]);
+        assert_eq!(issue.body.as_deref(), Some("bug in src/main.rs:4"));
+        assert_eq!(issue.issue_type(), IssueType::Bug);
+    }
+}
@@ -1,28 +1,17 @@
-use std::{collections::HashMap, fs};
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::walker::Walker;
+
 pub struct FileLines {
     pub file_path: String,
     pub lines: HashMap<usize, String>,
 }
-fn get_all_files_in_directory(directory: &str) -> Result<Vec<String>, String> {
-    let mut files = Vec::new();
-    let paths = match fs::read_dir(directory) {
-        Ok(paths) => paths,
-        Err(e) => return Err(e.to_string()),
-    };
-
-    for path in paths {
-        let path = path.map_err(|e| e.to_string())?.path();
-        if path.is_file() {
-            files.push(path.to_str().unwrap().to_string());
-        } else if path.is_dir() {
-            let mut sub_files = get_all_files_in_directory(path.to_str().unwrap())?;
-            files.append(&mut sub_files);
-        }
-    }
-    Ok(files)
+/// Every file under `directory`, including those symlinks lead to.
+pub fn get_all_files_in_directory(directory: &str) -> Result<Vec<String>, String> {
+    Walker::new(Path::new(directory)).walk_paths().collect()
 }
 
-fn read_comments_from_file(file_path: &str) -> Result<FileLines, String> {
+pub fn read_comments_from_file(file_path: &str) -> Result<FileLines, String> {
     let file = match fs::read_to_string(file_path) {
         Ok(file) => file,
         Err(e) => return Err(e.to_string()),
@@ -39,14 +28,10 @@ fn read_comments_from_file(file_path: &str) -> Result<FileLines, String> {
     })
 }
 
+/// The comment lines of every text file under `directory`, read in parallel. For large trees,
+/// iterate a `Walker` instead of collecting everything.
 pub fn comments_from_file_in_project(directory: &str) -> Result<Vec<FileLines>, String> {
-    let files = get_all_files_in_directory(directory)?;
-    let mut file_lines = Vec::new();
-    for file in files {
-        let lines = read_comments_from_file(&file)?;
-        file_lines.push(lines);
-    }
-    Ok(file_lines)
+    Walker::new(Path::new(directory)).walk().collect()
 }
 
 pub fn comments_from_strings(strings: Vec<String>, file_name: String) -> FileLines {
@@ -104,8 +89,7 @@ mod tests {
 
         let java_file = files
             .iter()
-            .filter(|file| file.file_path == "examples/example.java")
-            .next()
+            .find(|file| file.file_path == "examples/example.java")
             .unwrap();
         assert_eq!(java_file.file_path, "examples/example.java");
         assert_eq!(java_file.lines.len(), 2);
@@ -0,0 +1,352 @@
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use reqwest::{header, Client, Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::issue_repository::{
+    Issue, IssueBoard, IssueId, IssueState, IssueUpdateRequest, Label, User,
+};
+
+const PAGE_LIMIT: usize = 50;
+
+/// `IssueBoard` backed by the Gitea (and Forgejo) v1 issues API.
+///
+/// The API mirrors GitHub's, except that it authenticates with `Authorization: token ...`,
+/// pages with `page`/`limit` and sets labels by ID rather than by name.
+#[derive(Debug)]
+pub struct GiteaIntegration {
+    base_url: String,
+    owner: String,
+    repo: String,
+    token: String,
+    client: Client,
+}
+
+impl GiteaIntegration {
+    pub fn new(base_url: &str, owner: &str, repo: &str, token: &str) -> GiteaIntegration {
+        GiteaIntegration {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            owner: owner.to_string(),
+            repo: repo.to_string(),
+            token: token.to_string(),
+            client: Client::new(),
+        }
+    }
+
+    fn repo_url(&self) -> String {
+        format!(
+            "{}/api/v1/repos/{}/{}",
+            self.base_url, self.owner, self.repo
+        )
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .header(header::AUTHORIZATION, format!("token {}", self.token))
+            .header(header::USER_AGENT, "tissue")
+    }
+
+    /// Fetches every page of `url`, stopping at the first page shorter than `PAGE_LIMIT`.
+    async fn get_all<T: for<'de> Deserialize<'de>>(&self, url: &str) -> Result<Vec<T>> {
+        let mut items = Vec::new();
+        let limit = PAGE_LIMIT.to_string();
+        for page in 1.. {
+            let response = self
+                .request(Method::GET, url)
+                .query(&[("page", page.to_string().as_str()), ("limit", &limit)])
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(color_eyre::Report::msg(format!(
+                    "Failed to fetch {} from Gitea: {}",
+                    url,
+                    response.status()
+                )));
+            }
+            let batch = response.json::<Vec<T>>().await?;
+            let last_page = batch.len() < PAGE_LIMIT;
+            items.extend(batch);
+            if last_page {
+                break;
+            }
+        }
+        Ok(items)
+    }
+
+    /// Resolves label names to the repository's label IDs. Unknown names are an error, since
+    /// Gitea silently drops IDs it doesn't recognise.
+    async fn label_ids(&self, names: &[&str]) -> Result<Vec<u64>> {
+        if names.is_empty() {
+            return Ok(vec![]);
+        }
+        let labels: Vec<GiteaLabel> = self.get_all(&format!("{}/labels", self.repo_url())).await?;
+        names
+            .iter()
+            .map(|name| {
+                labels
+                    .iter()
+                    .find(|label| label.name == *name)
+                    .map(|label| label.id)
+                    .ok_or_else(|| {
+                        color_eyre::Report::msg(format!(
+                            "Label {} does not exist in {}/{}",
+                            name, self.owner, self.repo
+                        ))
+                    })
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GiteaUser {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiteaLabel {
+    id: u64,
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GiteaIssue {
+    number: u32,
+    title: String,
+    body: Option<String>,
+    state: String,
+    user: GiteaUser,
+    assignee: Option<GiteaUser>,
+    #[serde(default)]
+    labels: Vec<GiteaLabel>,
+}
+
+impl From<GiteaIssue> for Issue {
+    fn from(issue: GiteaIssue) -> Issue {
+        Issue {
+            number: Some(IssueId::Number(issue.number)),
+            title: issue.title,
+            author: User {
+                name: issue.user.login,
+            },
+            assignee: issue.assignee.map(|user| User { name: user.login }),
+            issue_type: issue
+                .labels
+                .into_iter()
+                .map(|label| Label { name: label.name })
+                .collect(),
+            state: issue.state,
+            body: issue.body,
+            source: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct GiteaIssueRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignees: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<Vec<u64>>,
+}
+
+#[async_trait]
+impl IssueBoard for GiteaIntegration {
+    async fn get_issues(&self) -> Result<Vec<Issue>> {
+        let url = format!("{}/issues?type=issues&state=all", self.repo_url());
+        let issues: Vec<GiteaIssue> = self.get_all(&url).await?;
+        Ok(issues.into_iter().map(Issue::from).collect())
+    }
+
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue> {
+        let url = format!("{}/issues/{}", self.repo_url(), id);
+        let response = self.request(Method::GET, &url).send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to fetch Gitea issue {}: {}",
+                id,
+                response.status()
+            )));
+        }
+        Ok(Issue::from(response.json::<GiteaIssue>().await?))
+    }
+
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId> {
+        let url = format!("{}/issues", self.repo_url());
+        let names: Vec<&str> = issue
+            .issue_type
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect();
+        let data = GiteaIssueRequest {
+            title: Some(issue.title.clone()),
+            body: issue.body.clone(),
+            state: None,
+            assignees: issue.assignee.as_ref().map(|user| vec![user.name.clone()]),
+            labels: Some(self.label_ids(&names).await?),
+        };
+        let response = self.request(Method::POST, &url).json(&data).send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Couldn't create Gitea issue: {}",
+                response.status()
+            )));
+        }
+        Ok(IssueId::Number(response.json::<GiteaIssue>().await?.number))
+    }
+
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()> {
+        let url = format!("{}/issues/{}", self.repo_url(), id);
+        // Labels live on their own endpoint; everything else is a PATCH on the issue.
+        let request = match update {
+            IssueUpdateRequest::IssueType(issue_type) => {
+                // PUT replaces every label, so keep the ones that aren't the type.
+                let labels = self.get_issue(id).await?.labels_with_type(issue_type);
+                let names: Vec<&str> = labels.iter().map(|label| label.name.as_str()).collect();
+                let labels = self.label_ids(&names).await?;
+                self.request(Method::PUT, &format!("{}/labels", url))
+                    .json(&serde_json::json!({ "labels": labels }))
+            }
+            IssueUpdateRequest::AddLabel(label) => {
+                let labels = self.label_ids(&[label.name.as_str()]).await?;
+                self.request(Method::POST, &format!("{}/labels", url))
+                    .json(&serde_json::json!({ "labels": labels }))
+            }
+            IssueUpdateRequest::State(state) => {
+                let state = match state {
+                    IssueState::Closed => IssueState::Closed,
+                    IssueState::Open | IssueState::Reopened => IssueState::Open,
+                };
+                self.request(Method::PATCH, &url).json(&GiteaIssueRequest {
+                    state: Some(state.as_str().to_string()),
+                    ..Default::default()
+                })
+            }
+            IssueUpdateRequest::Assignee(user) => {
+                self.request(Method::PATCH, &url).json(&GiteaIssueRequest {
+                    assignees: Some(vec![user.name.clone()]),
+                    ..Default::default()
+                })
+            }
+            IssueUpdateRequest::Title(title) => {
+                self.request(Method::PATCH, &url).json(&GiteaIssueRequest {
+                    title: Some(title.clone()),
+                    ..Default::default()
+                })
+            }
+            IssueUpdateRequest::Delete() => {
+                self.request(Method::PATCH, &url).json(&GiteaIssueRequest {
+                    state: Some(IssueState::Closed.as_str().to_string()),
+                    ..Default::default()
+                })
+            }
+        };
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Couldn't update Gitea issue {}: {}",
+                id,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()> {
+        let url = format!("{}/issues/{}/comments", self.repo_url(), id);
+        let response = self
+            .request(Method::POST, &url)
+            .json(&serde_json::json!({ "body": body }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Couldn't comment on Gitea issue {}: {}",
+                id,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    fn get_repository_name(&self) -> String {
+        format!("gitea:{}/{}", self.owner, self.repo)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn authenticates_with_token_header() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v1/repos/tools/tissue/issues/1")
+            .match_header("authorization", "token secret")
+            .with_body(
+                r#"{"number": 1, "title": "test issue", "body": null, "state": "open",
+                    "user": {"login": "OthelloEngineer"}, "assignee": null, "labels": []}"#,
+            )
+            .create_async()
+            .await;
+
+        let board = GiteaIntegration::new(&server.url(), "tools", "tissue", "secret");
+        let issue = board.get_issue(&IssueId::from(1)).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(issue.title, "test issue");
+    }
+
+    #[tokio::test]
+    async fn pages_with_page_and_limit() {
+        let mut server = mockito::Server::new_async().await;
+        let label = |id: usize| format!(r#"{{"id": {}, "name": "label{}"}}"#, id, id);
+        let full_page: Vec<String> = (0..PAGE_LIMIT).map(label).collect();
+        let first = server
+            .mock("GET", "/api/v1/repos/tools/tissue/labels")
+            .match_query(Matcher::AllOf(vec![
+                Matcher::UrlEncoded("page".into(), "1".into()),
+                Matcher::UrlEncoded("limit".into(), PAGE_LIMIT.to_string()),
+            ]))
+            .with_body(format!("[{}]", full_page.join(",")))
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/api/v1/repos/tools/tissue/labels")
+            .match_query(Matcher::UrlEncoded("page".into(), "2".into()))
+            .with_body(r#"[{"id": 99, "name": "bug"}]"#)
+            .create_async()
+            .await;
+
+        let board = GiteaIntegration::new(&server.url(), "tools", "tissue", "secret");
+        let ids = board.label_ids(&["bug", "label3"]).await.unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        assert_eq!(ids, vec![99, 3]);
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_labels() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v1/repos/tools/tissue/labels")
+            .match_query(Matcher::Any)
+            .with_body("[]")
+            .create_async()
+            .await;
+
+        let board = GiteaIntegration::new(&server.url(), "tools", "tissue", "secret");
+        assert!(board.label_ids(&["bug"]).await.is_err());
+    }
+}
@@ -1,6 +1,9 @@
+use chrono::{DateTime, FixedOffset};
 use color_eyre::eyre::Result;
 use regex::Regex;
+use std::collections::HashMap;
 use std::io::Error;
+use std::path::Path;
 use std::process::Command;
 
 #[derive(Debug)]
@@ -34,7 +37,7 @@ pub fn get_current_user() -> Result<String, Error> {
     if !output.status.success() {
         let err_msg = String::from_utf8_lossy(&output.stderr);
         eprintln!("Git command failed: {}", err_msg);
-        return Err(Error::new(std::io::ErrorKind::Other, "Git command failed"));
+        return Err(Error::other("Git command failed"));
     }
 
     let user = String::from_utf8(output.stdout).map_err(|e| {
@@ -70,7 +73,7 @@ pub fn blame_user_from_line(file_path: &str, line_number: usize) -> Result<Blame
     if !output.status.success() {
         let err_msg = String::from_utf8_lossy(&output.stderr);
         eprintln!("Git command failed: {}", err_msg);
-        return Err(Error::new(std::io::ErrorKind::Other, "Git command failed"));
+        return Err(Error::other("Git command failed"));
     }
 
     let user = String::from_utf8(output.stdout).map_err(|e| {
@@ -92,17 +95,270 @@ pub fn blame_user_from_line(file_path: &str, line_number: usize) -> Result<Blame
     Ok(blame_entry)
 }
 
+/// Blames every line of `file_path` in one `git blame` run from `directory`, keyed by line
+/// number. Dates use the same format as `blame_user_from_line`.
+pub fn blame_file(directory: &Path, file_path: &str) -> Result<HashMap<usize, BlameEntry>> {
+    blame_file_at(directory, None, file_path)
+}
+
+/// `blame_file` of the file as it was at `revision`, or in the working tree if that is `None`.
+pub fn blame_file_at(
+    directory: &Path,
+    revision: Option<&str>,
+    file_path: &str,
+) -> Result<HashMap<usize, BlameEntry>> {
+    let mut args = vec!["blame", "--line-porcelain"];
+    args.extend(revision);
+    args.extend(["--", file_path]);
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(color_eyre::Report::msg(format!(
+            "Couldn't blame {}: {}",
+            file_path,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_line_porcelain(&String::from_utf8_lossy(
+        &output.stdout,
+    )))
+}
+
+/// A commit and its committer date, e.g. `2024-03-24T02:45:51+01:00`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommitInfo {
+    pub revision: String,
+    pub date: String,
+}
+
+/// The commit checked out in `directory`.
+pub fn head_commit(directory: &Path) -> Result<String> {
+    Ok(git_output(directory, &["rev-parse", "HEAD"])?
+        .trim()
+        .to_string())
+}
+
+/// `org/repo` of the GitHub repository a remote of the repository in `directory` points at,
+/// preferring `origin`.
+pub fn github_repository(directory: &Path) -> Result<Option<String>> {
+    let output = git_output(directory, &["remote", "-v"])?;
+    let mut remotes: Vec<(&str, &str)> = output
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            Some((parts.next()?, parts.next()?))
+        })
+        .collect();
+    remotes.sort_by_key(|(name, _)| *name != "origin");
+    Ok(remotes
+        .into_iter()
+        .find_map(|(_, url)| parse_github_url(url)))
+}
+
+/// `org/repo` from an SSH or HTTPS GitHub URL.
+fn parse_github_url(url: &str) -> Option<String> {
+    let (_, path) = url.split_once("github.com")?;
+    let path = path
+        .trim_start_matches([':', '/'])
+        .trim_end_matches('/')
+        .trim_end_matches(".git");
+    let (org, repo) = path.split_once('/')?;
+    (!org.is_empty() && !repo.is_empty() && !repo.contains('/'))
+        .then(|| format!("{}/{}", org, repo))
+}
+
+/// The first-parent history of `revision`, oldest first.
+pub fn first_parent_commits(directory: &Path, revision: &str) -> Result<Vec<CommitInfo>> {
+    let output = git_output(
+        directory,
+        &[
+            "log",
+            "--first-parent",
+            "--reverse",
+            "--format=%H %cI",
+            revision,
+        ],
+    )?;
+    Ok(parse_commit_lines(&output))
+}
+
+/// Every tag, oldest first, with the date of the commit it points at.
+pub fn tags(directory: &Path) -> Result<Vec<CommitInfo>> {
+    let output = git_output(
+        directory,
+        &[
+            "for-each-ref",
+            "--sort=committerdate",
+            "--format=%(refname:short) %(*committerdate:iso-strict)%(committerdate:iso-strict)",
+            "refs/tags",
+        ],
+    )?;
+    Ok(parse_commit_lines(&output))
+}
+
+fn parse_commit_lines(output: &str) -> Vec<CommitInfo> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once(' '))
+        .map(|(revision, date)| CommitInfo {
+            revision: revision.to_string(),
+            date: date.to_string(),
+        })
+        .collect()
+}
+
+/// Lines mentioning one of `keywords`, ignoring case, in the tree of `revision`, as (file, line
+/// number, line), read straight from the object database without checking anything out.
+pub fn grep_todos_at(
+    directory: &Path,
+    revision: &str,
+    keywords: &[String],
+) -> Result<Vec<(String, usize, String)>> {
+    if keywords.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut args = vec!["grep", "-I", "-i", "-n", "-z", "--no-color", "-F"];
+    for keyword in keywords {
+        args.extend(["-e", keyword.as_str()]);
+    }
+    args.push(revision);
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(&args)
+        .output()?;
+    // git grep exits with 1 when nothing matches.
+    if !output.status.success() && output.status.code() != Some(1) {
+        return Err(color_eyre::Report::msg(format!(
+            "Couldn't search {}: {}",
+            revision,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(parse_grep(
+        &String::from_utf8_lossy(&output.stdout),
+        revision,
+    ))
+}
+
+fn parse_grep(output: &str, revision: &str) -> Vec<(String, usize, String)> {
+    let prefix = format!("{}:", revision);
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, '\0');
+            let file_path = fields.next()?;
+            let line_number = fields.next()?.parse().ok()?;
+            let text = fields.next()?;
+            let file_path = file_path.strip_prefix(&prefix).unwrap_or(file_path);
+            Some((file_path.to_string(), line_number, text.to_string()))
+        })
+        .collect()
+}
+
+/// Whether `file_path` is ignored by the .gitignore rules of the repository at `directory`.
+pub fn is_ignored(directory: &Path, file_path: &str) -> Result<bool> {
+    let status = Command::new("git")
+        .current_dir(directory)
+        .args(["check-ignore", "-q", "--", file_path])
+        .status()?;
+    // check-ignore exits with 1 when the path isn't ignored.
+    match status.code() {
+        Some(0) => Ok(true),
+        Some(1) => Ok(false),
+        _ => Err(color_eyre::Report::msg(format!(
+            "Couldn't check whether git ignores {}",
+            file_path
+        ))),
+    }
+}
+
+fn git_output(directory: &Path, args: &[&str]) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(color_eyre::Report::msg(format!(
+            "git {} failed: {}",
+            args[0],
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn parse_line_porcelain(porcelain: &str) -> HashMap<usize, BlameEntry> {
+    let mut entries = HashMap::new();
+    let (mut line_number, mut user, mut time, mut zone) = (0, "", 0, "+0000");
+    for line in porcelain.lines() {
+        if line.starts_with('\t') {
+            entries.insert(
+                line_number,
+                BlameEntry {
+                    user: user.to_string(),
+                    date: format_blame_date(time, zone),
+                },
+            );
+        } else if let Some(name) = line.strip_prefix("author ") {
+            user = name;
+        } else if let Some(seconds) = line.strip_prefix("author-time ") {
+            time = seconds.parse().unwrap_or_default();
+        } else if let Some(offset) = line.strip_prefix("author-tz ") {
+            zone = offset;
+        } else if let Some(number) = line.split(' ').nth(2).filter(|_| is_commit_header(line)) {
+            line_number = number.parse().unwrap_or_default();
+        }
+    }
+    entries
+}
+
+fn is_commit_header(line: &str) -> bool {
+    line.split(' ')
+        .next()
+        .is_some_and(|hash| hash.len() == 40 && hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+fn format_blame_date(seconds: i64, zone: &str) -> String {
+    let offset = zone
+        .parse::<i32>()
+        .ok()
+        .and_then(|hhmm| FixedOffset::east_opt((hhmm / 100 * 60 + hhmm % 100) * 60))
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap());
+    match DateTime::from_timestamp(seconds, 0) {
+        Some(date) => date
+            .with_timezone(&offset)
+            .format("%Y-%m-%d %H:%M:%S %z")
+            .to_string(),
+        None => String::new(),
+    }
+}
+
+/// `git diff` of `range`, e.g. `main..feature`, without context lines.
+pub fn diff(directory: &Path, range: &str) -> Result<String> {
+    let output = Command::new("git")
+        .current_dir(directory)
+        .args(["diff", "--no-color", "--no-ext-diff", "-U0", range])
+        .output()?;
+    if !output.status.success() {
+        return Err(color_eyre::Report::msg(format!(
+            "Couldn't diff {}: {}",
+            range,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
 pub fn changed_lines_per_diffed_file(diff: &str) -> Vec<DiffedFileChangedLines> {
     let lines = diff.split('\n');
     let mut lines_from_changed_files: Vec<DiffedFileChangedLines> = Vec::new();
     let mut idx = 0;
     for line in lines {
-        println!("line: {:?}", line);
         if line.starts_with("diff") {
-            let file_path = line.split(' ').collect::<Vec<&str>>()[2]
-                .split("a/")
-                .collect::<Vec<&str>>()[1];
-            println!("file_path: {:?}", file_path);
+            let file_path = line.split(' ').collect::<Vec<&str>>()[2];
+            let file_path = file_path.strip_prefix("a/").unwrap_or(file_path);
             lines_from_changed_files.push(DiffedFileChangedLines {
                 file_path: file_path.to_string(),
                 changed_lines: Vec::new(),
@@ -142,6 +398,60 @@ mod tests {
         assert_eq!(user.user, "jolee18");
     }
 
+    #[test]
+    fn parses_github_remote_urls() {
+        for url in [
+            "git@github.com:OthelloEngineer/tissue.git",
+            "https://github.com/OthelloEngineer/tissue",
+            "ssh://git@github.com/OthelloEngineer/tissue.git",
+        ] {
+            assert_eq!(
+                parse_github_url(url).as_deref(),
+                Some("OthelloEngineer/tissue")
+            );
+        }
+        assert_eq!(parse_github_url("git@gitlab.com:org/repo.git"), None);
+    }
+
+    #[test]
+    fn test_parse_line_porcelain() {
+        let porcelain = "\
+0123456789abcdef0123456789abcdef01234567 1 1 2
+author jolee18
+author-time 1711244751
+author-tz +0100
+filename src/main.rs
+\tfn main() {
+0123456789abcdef0123456789abcdef01234567 2 2
+author jolee18
+author-time 1711244751
+author-tz +0100
+filename src/main.rs
+\t    // TODO bug: parse flags
+";
+        let entries = parse_line_porcelain(porcelain);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[&2].user, "jolee18");
+        assert_eq!(entries[&2].date, "2024-03-24 02:45:51 +0100");
+    }
+
+    #[test]
+    fn test_parse_grep() {
+        let output = "v1.0:src/main.rs\x002\x00    //TODO bug: parse flags\n\
+                      v1.0:src/a:b.rs\x0010\x00// todo: x\n";
+        assert_eq!(
+            parse_grep(output, "v1.0"),
+            [
+                (
+                    "src/main.rs".to_string(),
+                    2,
+                    "    //TODO bug: parse flags".to_string()
+                ),
+                ("src/a:b.rs".to_string(), 10, "// todo: x".to_string()),
+            ]
+        );
+    }
+
     #[test]
     fn test_changed_lines_per_diffed_file() {
         let diff_lines = fs::read_to_string("examples/git_diff.txt").unwrap();
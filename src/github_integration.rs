@@ -1,24 +1,144 @@
-use std::env;
-
 use async_trait::async_trait;
 use color_eyre::eyre::Result;
 use reqwest::header::{self, HeaderMap, AUTHORIZATION};
 use reqwest::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::issue_repository::{Issue, IssueBoard, IssueId, IssueUpdateRequest, Label, User};
 
-use crate::issue_repository::{Issue, IssueBoard, IssueUpdateRequest};
+const GITHUB_API_URL: &str = "https://api.github.com";
 
 #[derive(Debug)]
-#[warn(dead_code)]
-struct GitHubIntegration {
+pub struct GitHubIntegration {
     project: String,
     org: String,
+    base_url: String,
+    token: Option<String>,
 }
 
 impl GitHubIntegration {
-    async fn new(org: &str, project: &str) -> GitHubIntegration {
+    pub async fn new(org: &str, project: &str) -> GitHubIntegration {
         GitHubIntegration {
             project: String::from(project),
             org: String::from(org),
+            base_url: String::from(GITHUB_API_URL),
+            token: None,
+        }
+    }
+
+    /// Points the integration at another API root, e.g. GitHub Enterprise or a local stand-in.
+    pub fn with_base_url(mut self, base_url: &str) -> GitHubIntegration {
+        self.base_url = base_url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Authenticates as the owner of `token`; `BoardsConfig::connect` reads it from the
+    /// environment.
+    pub fn with_token(mut self, token: &str) -> GitHubIntegration {
+        self.token = Some(token.to_string());
+        self
+    }
+
+    fn get_http_client(&self) -> Client {
+        let mut headers = HeaderMap::new();
+        if let Some(access_token) = &self.token {
+            headers.insert(
+                AUTHORIZATION,
+                header::HeaderValue::from_str(&format!("Bearer {}", access_token)).unwrap(),
+            );
+        }
+        reqwest::Client::builder()
+            .default_headers(headers)
+            .build()
+            .unwrap()
+    }
+
+    async fn post(&self, url: &str, data: serde_json::Value) -> Result<()> {
+        let response = self
+            .get_http_client()
+            .post(url)
+            .body(data.to_string())
+            .header(header::ACCEPT, "application/vnd.github+json")
+            .header("X-GitHub-Api-Version", "2022-11-28")
+            .header(header::USER_AGENT, "tissue")
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "GitHub rejected POST {}: {}",
+                url,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubUser {
+    login: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubLabel {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitHubIssue {
+    number: u32,
+    title: String,
+    user: GitHubUser,
+    assignee: Option<GitHubUser>,
+    labels: Vec<GitHubLabel>,
+    state: String,
+    body: Option<String>,
+}
+
+impl From<GitHubIssue> for Issue {
+    fn from(issue: GitHubIssue) -> Issue {
+        Issue {
+            number: Some(IssueId::Number(issue.number)),
+            title: issue.title,
+            author: User {
+                name: issue.user.login,
+            },
+            assignee: issue.assignee.map(|user| User { name: user.login }),
+            issue_type: issue
+                .labels
+                .into_iter()
+                .map(|label| Label { name: label.name })
+                .collect(),
+            state: issue.state,
+            body: issue.body,
+            source: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct GitHubNewIssue<'a> {
+    title: &'a str,
+    body: Option<&'a str>,
+    assignees: Vec<&'a str>,
+    labels: Vec<&'a str>,
+}
+
+impl<'a> From<&'a Issue> for GitHubNewIssue<'a> {
+    fn from(issue: &'a Issue) -> GitHubNewIssue<'a> {
+        GitHubNewIssue {
+            title: &issue.title,
+            body: issue.body.as_deref(),
+            assignees: issue
+                .assignee
+                .iter()
+                .map(|user| user.name.as_str())
+                .collect(),
+            labels: issue
+                .issue_type
+                .iter()
+                .map(|label| label.name.as_str())
+                .collect(),
         }
     }
 }
@@ -26,15 +146,14 @@ impl GitHubIntegration {
 #[async_trait]
 impl IssueBoard for GitHubIntegration {
     async fn get_issues(&self) -> Result<Vec<Issue>> {
-        let client = get_http_client();
+        let client = self.get_http_client();
         let url = format!(
-            "https://api.github.com/repos/{}/{}/issues",
-            self.org, self.project
+            "{}/repos/{}/{}/issues",
+            self.base_url, self.org, self.project
         );
 
         let response = client
             .get(&url)
-            .bearer_auth(env::var("TISSUE_GITHUB_TOKEN").expect("Token not found"))
             .header(header::ACCEPT, "application/vnd.github+json")
             .header("X-GitHub-Api-Version", "2022-11-28")
             .header(header::USER_AGENT, "tissue")
@@ -44,10 +163,10 @@ impl IssueBoard for GitHubIntegration {
 
         if response.status().is_success() {
             let issues = response
-                .json::<Vec<Issue>>()
+                .json::<Vec<GitHubIssue>>()
                 .await
                 .expect("Failed to parse JSON");
-            Ok(issues)
+            Ok(issues.into_iter().map(Issue::from).collect())
         } else {
             println!(
                 "Failed to fetch issues: {:?}",
@@ -58,16 +177,15 @@ impl IssueBoard for GitHubIntegration {
             ))
         }
     }
-    async fn get_issue(&self, number: u32) -> Result<Issue> {
-        let client = get_http_client();
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue> {
+        let client = self.get_http_client();
         let url = format!(
-            "https://api.github.com/repos/{}/{}/issues/{}",
-            self.org, self.project, number
+            "{}/repos/{}/{}/issues/{}",
+            self.base_url, self.org, self.project, id
         );
 
         let response = client
             .get(&url)
-            .bearer_auth(env::var("TISSUE_GITHUB_TOKEN").expect("Token not found"))
             .header(header::ACCEPT, "application/vnd.github+json")
             .header("X-GitHub-Api-Version", "2022-11-28")
             .header(header::USER_AGENT, "tissue")
@@ -77,10 +195,10 @@ impl IssueBoard for GitHubIntegration {
 
         if response.status().is_success() {
             let issue = response
-                .json::<Issue>()
+                .json::<GitHubIssue>()
                 .await
                 .expect("Failed to parse JSON");
-            Ok(issue)
+            Ok(Issue::from(issue))
         } else {
             println!(
                 "Failed to fetch issue: {:?}",
@@ -91,17 +209,17 @@ impl IssueBoard for GitHubIntegration {
             ))
         }
     }
-    async fn add_issue(&self, issue: Issue) -> Result<u32> {
-        let client = get_http_client();
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId> {
+        let client = self.get_http_client();
         let url = format!(
-            "https://api.github.com/repos/{}/{}/issues",
-            self.org, self.project
+            "{}/repos/{}/{}/issues",
+            self.base_url, self.org, self.project
         );
 
-        let data = serde_json::to_string(&issue).expect("Failed to serialize issue");
+        let data = serde_json::to_string(&GitHubNewIssue::from(&issue))
+            .expect("Failed to serialize issue");
         let response = client
             .post(&url)
-            .bearer_auth(env::var("TISSUE_GITHUB_TOKEN").expect("Token not found"))
             .body(data)
             .header(header::ACCEPT, "application/vnd.github+json")
             .header("X-GitHub-Api-Version", "2022-11-28")
@@ -112,10 +230,10 @@ impl IssueBoard for GitHubIntegration {
 
         if response.status().is_success() {
             let issue = response
-                .json::<Issue>()
+                .json::<GitHubIssue>()
                 .await
                 .expect("Failed to parse JSON");
-            Ok(issue.number.expect("Issue number not found"))
+            Ok(IssueId::Number(issue.number))
         } else {
             println!(
                 "Failed to create issue: {:?}",
@@ -124,11 +242,11 @@ impl IssueBoard for GitHubIntegration {
             Err(color_eyre::Report::msg("Couldn't create issue :("))
         }
     }
-    async fn update_issue(&self, number: u32, update: &IssueUpdateRequest) -> Result<()> {
-        let client = get_http_client();
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()> {
+        let client = self.get_http_client();
         let url = format!(
-            "https://api.github.com/repos/{}/{}/issues/{}",
-            self.org, self.project, number
+            "{}/repos/{}/{}/issues/{}",
+            self.base_url, self.org, self.project, id
         );
 
         let data = match update {
@@ -142,16 +260,26 @@ impl IssueBoard for GitHubIntegration {
                 format!("{{\"title\":\"{}\"}}", title)
             }
             IssueUpdateRequest::IssueType(issue_type) => {
-                format!("{{\"labels\":[\"{}\"]}}", issue_type.as_str())
+                // Setting labels replaces them all, so keep the ones that aren't the type.
+                let labels: Vec<String> = self
+                    .get_issue(id)
+                    .await?
+                    .labels_with_type(issue_type)
+                    .into_iter()
+                    .map(|label| label.name)
+                    .collect();
+                serde_json::json!({ "labels": labels }).to_string()
             }
-            IssueUpdateRequest::Delete() => {
-                format!("{{\"state\":\"closed\"}}")
+            IssueUpdateRequest::AddLabel(label) => {
+                // Labels are added on their own endpoint, keeping the existing ones.
+                let data = serde_json::json!({ "labels": [label.name] });
+                return self.post(&format!("{}/labels", url), data).await;
             }
+            IssueUpdateRequest::Delete() => "{\"state\":\"closed\"}".to_string(),
         };
 
         let response = client
             .patch(&url)
-            .bearer_auth(env::var("TISSUE_GITHUB_TOKEN").expect("Token not found"))
             .body(data)
             .header(header::ACCEPT, "application/vnd.github+json")
             .header("X-GitHub-Api-Version", "2022-11-28")
@@ -171,34 +299,35 @@ impl IssueBoard for GitHubIntegration {
         }
     }
 
-    fn get_repository_name(&self) -> String {
-        String::from("GitHub")
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()> {
+        let url = format!(
+            "{}/repos/{}/{}/issues/{}/comments",
+            self.base_url, self.org, self.project, id
+        );
+        self.post(&url, serde_json::json!({ "body": body })).await
     }
-}
 
-fn get_http_client() -> Client {
-    let access_token = match env::var("TISSUE_GITHUB_TOKEN") {
-        Ok(token) => token,
-        Err(err) => panic!("No GitHub token found: {}", err),
-    };
-    let mut headers = HeaderMap::new();
-    let mut token = "Bearer ".to_string();
-    token.push_str(&access_token);
-    headers.insert(
-        AUTHORIZATION,
-        header::HeaderValue::from_str(&token).unwrap(),
-    );
-    return reqwest::Client::builder()
-        .default_headers(headers)
-        .build()
-        .unwrap();
+    fn get_repository_name(&self) -> String {
+        format!("github:{}/{}", self.org, self.project)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::issue_repository::{Issue, IssueBoard};
+    use crate::{
+        config::BoardsConfig,
+        issue_repository::{Issue, IssueBoard, IssueId},
+    };
 
     use super::GitHubIntegration;
+
+    /// The test repository, with the token from the environment.
+    async fn test_repository() -> Box<dyn IssueBoard> {
+        BoardsConfig::default()
+            .connect("github:OthelloEngineer/tissue")
+            .await
+            .unwrap()
+    }
     #[tokio::test]
     async fn can_connect_with_github_client() {
         let _client = GitHubIntegration::new("OthelloEngineer", "tissue").await;
@@ -206,7 +335,7 @@ mod tests {
 
     #[tokio::test]
     async fn can_retrieve_test_issue() {
-        let client = GitHubIntegration::new("OthelloEngineer", "tissue").await;
+        let client = test_repository().await;
         let issues: Vec<Issue> = client.get_issues().await.unwrap();
         let test_issue = issues
             .iter()
@@ -217,16 +346,16 @@ mod tests {
 
     #[tokio::test]
     async fn can_retrieve_test_issue_by_number() {
-        let client = GitHubIntegration::new("OthelloEngineer", "tissue").await;
-        let test_issue = client.get_issue(1).await.unwrap();
+        let client = test_repository().await;
+        let test_issue = client.get_issue(&IssueId::from(1)).await.unwrap();
         assert_eq!(test_issue.title, "test issue".to_string())
     }
 
     #[tokio::test]
     async fn can_update_test_issue() {
-        let client = GitHubIntegration::new("OthelloEngineer", "tissue").await;
-        let test_number = 3;
-        let test_issue = client.get_issue(test_number).await.unwrap();
+        let client = test_repository().await;
+        let test_number = IssueId::from(3);
+        let test_issue = client.get_issue(&test_number).await.unwrap();
 
         let issue_update_request = match test_issue.title.as_str() {
             "test issue2 blue" => super::IssueUpdateRequest::Title("test issue2 green".to_string()),
@@ -234,15 +363,20 @@ mod tests {
             _ => panic!("test name not recognized"),
         };
 
-        client.update_issue(3, &issue_update_request).await.unwrap();
-        let updated_issue = client.get_issue(test_number).await.unwrap();
+        client
+            .update_issue(&test_number, &issue_update_request)
+            .await
+            .unwrap();
+        let updated_issue = client.get_issue(&test_number).await.unwrap();
         assert_eq!(updated_issue.title, issue_update_request.as_str())
     }
 
     #[cfg(feature = "manual_reversible")]
     #[tokio::test]
     async fn can_create_issue() {
-        let client = GitHubIntegration::new("OthelloEngineer", "tissue").await;
+        use crate::issue_repository::{Label, User};
+
+        let client = test_repository().await;
         let new_issue = Issue {
             body: Some("This is an automatic test issue".to_string()),
             title: "automatic test issue".to_string(),
@@ -255,9 +389,10 @@ mod tests {
             }],
             state: "open".to_string(),
             number: None,
+            source: None,
         };
         let issue_number = client.add_issue(new_issue).await.unwrap();
-        let issue = client.get_issue(issue_number).await.unwrap();
+        let issue = client.get_issue(&issue_number).await.unwrap();
         assert_eq!(issue.title, "automatic test issue".to_string())
     }
 }
@@ -0,0 +1,512 @@
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use reqwest::{header, Client, Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    issue_repository::{Issue, IssueBoard, IssueId, IssueState, IssueUpdateRequest, Label, User},
+    todofinder::IssueType,
+};
+
+/// `IssueBoard` backed by the GitLab v4 issues API.
+///
+/// `project` is either the numeric project ID or the full path, e.g. `group/subgroup/project`.
+/// Issues are addressed by their project-scoped IID, which is what GitLab shows in its UI.
+#[derive(Debug)]
+pub struct GitLabIntegration {
+    base_url: String,
+    project: String,
+    token: String,
+    client: Client,
+}
+
+impl GitLabIntegration {
+    pub fn new(base_url: &str, project: &str, token: &str) -> GitLabIntegration {
+        GitLabIntegration {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            project: project.to_string(),
+            token: token.to_string(),
+            client: Client::new(),
+        }
+    }
+
+    fn project_url(&self) -> String {
+        format!(
+            "{}/api/v4/projects/{}",
+            self.base_url,
+            encode_project(&self.project)
+        )
+    }
+
+    fn request(&self, method: Method, url: &str) -> RequestBuilder {
+        self.client
+            .request(method, url)
+            .header("PRIVATE-TOKEN", &self.token)
+            .header(header::USER_AGENT, "tissue")
+    }
+
+    async fn user_id(&self, username: &str) -> Result<u64> {
+        let url = format!("{}/api/v4/users", self.base_url);
+        let response = self
+            .request(Method::GET, &url)
+            .query(&[("username", username)])
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to look up GitLab user {}: {}",
+                username,
+                response.status()
+            )));
+        }
+        let users = response.json::<Vec<GitLabUser>>().await?;
+        users
+            .into_iter()
+            .find(|user| user.username == username)
+            .and_then(|user| user.id)
+            .ok_or_else(|| color_eyre::Report::msg(format!("GitLab user {} not found", username)))
+    }
+}
+
+/// Project paths must be URL-encoded (`group/project` -> `group%2Fproject`), numeric IDs are
+/// passed through unchanged.
+fn encode_project(project: &str) -> String {
+    project
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabUser {
+    id: Option<u64>,
+    username: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct GitLabIssue {
+    iid: u32,
+    title: String,
+    description: Option<String>,
+    state: String,
+    author: GitLabUser,
+    #[serde(default)]
+    assignees: Vec<GitLabUser>,
+    #[serde(default)]
+    labels: Vec<String>,
+}
+
+impl From<GitLabIssue> for Issue {
+    fn from(issue: GitLabIssue) -> Issue {
+        let state = match issue.state.as_str() {
+            "opened" => IssueState::Open.as_str(),
+            "closed" => IssueState::Closed.as_str(),
+            other => other,
+        };
+        Issue {
+            number: Some(IssueId::Number(issue.iid)),
+            title: issue.title,
+            author: User {
+                name: issue.author.username,
+            },
+            assignee: issue.assignees.into_iter().next().map(|user| User {
+                name: user.username,
+            }),
+            issue_type: issue
+                .labels
+                .into_iter()
+                .map(|name| Label { name })
+                .collect(),
+            state: state.to_string(),
+            body: issue.description,
+            source: None,
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+struct GitLabIssueRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    labels: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    add_labels: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    remove_labels: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assignee_ids: Option<Vec<u64>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state_event: Option<String>,
+}
+
+fn state_event(state: &IssueState) -> &'static str {
+    match state {
+        IssueState::Closed => "close",
+        IssueState::Open | IssueState::Reopened => "reopen",
+    }
+}
+
+#[async_trait]
+impl IssueBoard for GitLabIntegration {
+    async fn get_issues(&self) -> Result<Vec<Issue>> {
+        let url = format!("{}/issues", self.project_url());
+        let mut issues = Vec::new();
+        let mut page = String::from("1");
+        loop {
+            let response = self
+                .request(Method::GET, &url)
+                .query(&[("per_page", "100"), ("page", page.as_str())])
+                .send()
+                .await?;
+            if !response.status().is_success() {
+                return Err(color_eyre::Report::msg(format!(
+                    "Failed to fetch GitLab issues: {}",
+                    response.status()
+                )));
+            }
+            let next_page = response
+                .headers()
+                .get("x-next-page")
+                .and_then(|value| value.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            let batch = response.json::<Vec<GitLabIssue>>().await?;
+            issues.extend(batch.into_iter().map(Issue::from));
+            if next_page.is_empty() {
+                break;
+            }
+            page = next_page;
+        }
+        Ok(issues)
+    }
+
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue> {
+        let url = format!("{}/issues/{}", self.project_url(), id);
+        let response = self.request(Method::GET, &url).send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Failed to fetch GitLab issue {}: {}",
+                id,
+                response.status()
+            )));
+        }
+        Ok(Issue::from(response.json::<GitLabIssue>().await?))
+    }
+
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId> {
+        let url = format!("{}/issues", self.project_url());
+        let assignee_ids = match &issue.assignee {
+            Some(assignee) => Some(vec![self.user_id(&assignee.name).await?]),
+            None => None,
+        };
+        let labels: Vec<&str> = issue
+            .issue_type
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect();
+        let data = GitLabIssueRequest {
+            title: Some(issue.title.clone()),
+            description: issue.body.clone(),
+            labels: Some(labels.join(",")),
+            assignee_ids,
+            ..Default::default()
+        };
+        let response = self.request(Method::POST, &url).json(&data).send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Couldn't create GitLab issue: {}",
+                response.status()
+            )));
+        }
+        Ok(IssueId::Number(response.json::<GitLabIssue>().await?.iid))
+    }
+
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()> {
+        let url = format!("{}/issues/{}", self.project_url(), id);
+        let data = match update {
+            IssueUpdateRequest::State(state) => GitLabIssueRequest {
+                state_event: Some(state_event(state).to_string()),
+                ..Default::default()
+            },
+            IssueUpdateRequest::Assignee(user) => GitLabIssueRequest {
+                assignee_ids: Some(vec![self.user_id(&user.name).await?]),
+                ..Default::default()
+            },
+            IssueUpdateRequest::Title(title) => GitLabIssueRequest {
+                title: Some(title.clone()),
+                ..Default::default()
+            },
+            IssueUpdateRequest::IssueType(issue_type) => GitLabIssueRequest {
+                add_labels: Some(issue_type.as_str().to_string()),
+                remove_labels: Some(
+                    IssueType::ALL
+                        .iter()
+                        .filter(|other| *other != issue_type)
+                        .map(IssueType::as_str)
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                ..Default::default()
+            },
+            IssueUpdateRequest::AddLabel(label) => GitLabIssueRequest {
+                add_labels: Some(label.name.clone()),
+                ..Default::default()
+            },
+            IssueUpdateRequest::Delete() => GitLabIssueRequest {
+                state_event: Some(state_event(&IssueState::Closed).to_string()),
+                ..Default::default()
+            },
+        };
+        let response = self.request(Method::PUT, &url).json(&data).send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Couldn't update GitLab issue {}: {}",
+                id,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()> {
+        let url = format!("{}/issues/{}/notes", self.project_url(), id);
+        let response = self
+            .request(Method::POST, &url)
+            .json(&serde_json::json!({ "body": body }))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Couldn't comment on GitLab issue {}: {}",
+                id,
+                response.status()
+            )));
+        }
+        Ok(())
+    }
+
+    fn get_repository_name(&self) -> String {
+        format!("gitlab:{}", self.project)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+    use crate::todofinder::IssueType;
+
+    const ISSUE_JSON: &str = r#"{
+        "id": 90211,
+        "iid": 7,
+        "title": "test issue",
+        "description": "found in src/main.rs",
+        "state": "opened",
+        "author": {"id": 1, "username": "OthelloEngineer"},
+        "assignees": [{"id": 2, "username": "jolee18"}],
+        "labels": ["bug"]
+    }"#;
+
+    #[test]
+    fn test_encode_project() {
+        assert_eq!(encode_project("42"), "42");
+        assert_eq!(encode_project("ops/infra"), "ops%2Finfra");
+    }
+
+    #[tokio::test]
+    async fn can_retrieve_issue_by_iid() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/api/v4/projects/ops%2Finfra/issues/7")
+            .match_header("PRIVATE-TOKEN", "secret")
+            .with_body(ISSUE_JSON)
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "ops/infra", "secret");
+        let issue = board.get_issue(&IssueId::from(7)).await.unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(issue.number, Some(IssueId::Number(7)));
+        assert_eq!(issue.author.name, "OthelloEngineer");
+        assert_eq!(issue.assignee.as_ref().unwrap().name, "jolee18");
+        assert_eq!(issue.issue_type(), IssueType::Bug);
+        assert_eq!(issue.state, "open");
+    }
+
+    #[tokio::test]
+    async fn follows_pagination() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("GET", "/api/v4/projects/42/issues")
+            .match_query(Matcher::UrlEncoded("page".into(), "1".into()))
+            .with_header("x-next-page", "2")
+            .with_body(format!("[{}]", ISSUE_JSON))
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/api/v4/projects/42/issues")
+            .match_query(Matcher::UrlEncoded("page".into(), "2".into()))
+            .with_header("x-next-page", "")
+            .with_body(format!("[{}]", ISSUE_JSON))
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "42", "secret");
+        let issues = board.get_issues().await.unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        assert_eq!(issues.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn creates_issue_with_labels_and_assignee() {
+        let mut server = mockito::Server::new_async().await;
+        let user = server
+            .mock("GET", "/api/v4/users")
+            .match_query(Matcher::UrlEncoded("username".into(), "jolee18".into()))
+            .with_body(r#"[{"id": 2, "username": "jolee18"}]"#)
+            .create_async()
+            .await;
+        let create = server
+            .mock("POST", "/api/v4/projects/42/issues")
+            .match_body(Matcher::PartialJsonString(
+                r#"{"title": "test issue", "labels": "bug", "assignee_ids": [2]}"#.to_string(),
+            ))
+            .with_status(201)
+            .with_body(ISSUE_JSON)
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "42", "secret");
+        let number = board
+            .add_issue(Issue {
+                number: None,
+                title: "test issue".to_string(),
+                author: User {
+                    name: "OthelloEngineer".to_string(),
+                },
+                assignee: Some(User {
+                    name: "jolee18".to_string(),
+                }),
+                issue_type: vec![Label {
+                    name: "bug".to_string(),
+                }],
+                state: "open".to_string(),
+                body: None,
+                source: None,
+            })
+            .await
+            .unwrap();
+
+        user.assert_async().await;
+        create.assert_async().await;
+        assert_eq!(number, IssueId::Number(7));
+    }
+
+    #[tokio::test]
+    async fn closes_issue_with_state_event() {
+        let mut server = mockito::Server::new_async().await;
+        let close = server
+            .mock("PUT", "/api/v4/projects/42/issues/7")
+            .match_body(Matcher::Json(serde_json::json!({"state_event": "close"})))
+            .with_body(ISSUE_JSON)
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "42", "secret");
+        board
+            .update_issue(
+                &IssueId::from(7),
+                &IssueUpdateRequest::State(IssueState::Closed),
+            )
+            .await
+            .unwrap();
+
+        close.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn swaps_only_the_type_label() {
+        let mut server = mockito::Server::new_async().await;
+        let swap = server
+            .mock("PUT", "/api/v4/projects/42/issues/7")
+            .match_body(Matcher::Json(serde_json::json!({
+                "add_labels": "feature",
+                "remove_labels": "bug,improvement,other"
+            })))
+            .with_body(ISSUE_JSON)
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "42", "secret");
+        board
+            .update_issue(
+                &IssueId::from(7),
+                &IssueUpdateRequest::IssueType(IssueType::Feature),
+            )
+            .await
+            .unwrap();
+
+        swap.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn adds_labels_and_notes() {
+        let mut server = mockito::Server::new_async().await;
+        let label = server
+            .mock("PUT", "/api/v4/projects/42/issues/7")
+            .match_body(Matcher::Json(serde_json::json!({"add_labels": "stale"})))
+            .with_body(ISSUE_JSON)
+            .create_async()
+            .await;
+        let note = server
+            .mock("POST", "/api/v4/projects/42/issues/7/notes")
+            .match_body(Matcher::Json(serde_json::json!({"body": "still open?"})))
+            .with_status(201)
+            .with_body("{}")
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "42", "secret");
+        board
+            .update_issue(
+                &IssueId::from(7),
+                &IssueUpdateRequest::AddLabel(Label {
+                    name: "stale".to_string(),
+                }),
+            )
+            .await
+            .unwrap();
+        board
+            .add_comment(&IssueId::from(7), "still open?")
+            .await
+            .unwrap();
+
+        label.assert_async().await;
+        note.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn reports_failed_requests() {
+        let mut server = mockito::Server::new_async().await;
+        server
+            .mock("GET", "/api/v4/projects/42/issues/404")
+            .with_status(404)
+            .create_async()
+            .await;
+
+        let board = GitLabIntegration::new(&server.url(), "42", "secret");
+        assert!(board.get_issue(&IssueId::from(404)).await.is_err());
+    }
+}
@@ -0,0 +1,227 @@
+//! How the ToDo count changed over the git history: scans sampled commits or tags straight from
+//! the object database and counts their ToDos by type, author and directory.
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use chrono::DateTime;
+use color_eyre::eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    githandler::{self, CommitInfo},
+    scanner,
+    todofinder::{Syntax, ToDo},
+};
+
+/// Which commits `history` scans.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sampling {
+    /// Every nth commit on the first-parent line of HEAD, always including HEAD.
+    EveryNth(usize),
+    /// Every tag.
+    Tags,
+}
+
+/// ToDo counts at one commit.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct HistoryPoint {
+    pub revision: String,
+    pub date: String,
+    pub total: usize,
+    pub by_type: BTreeMap<String, usize>,
+    pub by_author: BTreeMap<String, usize>,
+    pub by_directory: BTreeMap<String, usize>,
+}
+
+impl HistoryPoint {
+    pub fn new(commit: &CommitInfo, todos: &[ToDo]) -> HistoryPoint {
+        let mut point = HistoryPoint {
+            revision: commit.revision.clone(),
+            date: commit.date.clone(),
+            total: todos.len(),
+            ..HistoryPoint::default()
+        };
+        for todo in todos {
+            let directory = match Path::new(&todo.submission.file_path).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+                _ => String::from("."),
+            };
+            *point
+                .by_type
+                .entry(todo.issue_type.as_str().to_string())
+                .or_default() += 1;
+            *point
+                .by_author
+                .entry(todo.submission.issuer.clone())
+                .or_default() += 1;
+            *point.by_directory.entry(directory).or_default() += 1;
+        }
+        point
+    }
+}
+
+/// Scans the commits chosen by `sampling` in the repository at `root`, oldest first.
+pub fn history(root: &Path, sampling: &Sampling, syntax: &Syntax) -> Result<Vec<HistoryPoint>> {
+    let commits = match sampling {
+        Sampling::EveryNth(n) => sample(githandler::first_parent_commits(root, "HEAD")?, *n),
+        Sampling::Tags => {
+            let mut tags = githandler::tags(root)?;
+            tags.sort_by_key(|tag| DateTime::parse_from_rfc3339(&tag.date).ok());
+            tags
+        }
+    };
+    commits
+        .iter()
+        .map(|commit| {
+            let scan = scanner::scan_revision(root, &commit.revision, syntax)?;
+            Ok(HistoryPoint::new(commit, &scan.todos))
+        })
+        .collect()
+}
+
+/// Every `n`th of `commits`, counting back from the last so it is always included.
+fn sample(commits: Vec<CommitInfo>, n: usize) -> Vec<CommitInfo> {
+    let n = n.max(1);
+    let last = commits.len().saturating_sub(1);
+    commits
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| (last - index).is_multiple_of(n))
+        .map(|(_, commit)| commit)
+        .collect()
+}
+
+/// One row per point, with a column per type, author and directory seen anywhere in the history.
+pub fn to_csv(points: &[HistoryPoint]) -> String {
+    let columns = |group: fn(&HistoryPoint) -> &BTreeMap<String, usize>| {
+        points
+            .iter()
+            .flat_map(|point| group(point).keys().cloned())
+            .collect::<BTreeSet<String>>()
+    };
+    let types = columns(|point| &point.by_type);
+    let authors = columns(|point| &point.by_author);
+    let directories = columns(|point| &point.by_directory);
+
+    let mut header = vec![
+        String::from("revision"),
+        String::from("date"),
+        String::from("total"),
+    ];
+    header.extend(types.iter().map(|t| format!("type:{}", t)));
+    header.extend(authors.iter().map(|a| format!("author:{}", a)));
+    header.extend(directories.iter().map(|d| format!("directory:{}", d)));
+    let mut csv = csv_row(&header);
+
+    for point in points {
+        let mut row = vec![
+            point.revision.clone(),
+            point.date.clone(),
+            point.total.to_string(),
+        ];
+        let counts = |group: &BTreeMap<String, usize>, keys: &BTreeSet<String>| {
+            keys.iter()
+                .map(|key| group.get(key).copied().unwrap_or_default().to_string())
+                .collect::<Vec<String>>()
+        };
+        row.extend(counts(&point.by_type, &types));
+        row.extend(counts(&point.by_author, &authors));
+        row.extend(counts(&point.by_directory, &directories));
+        csv.push_str(&csv_row(&row));
+    }
+    csv
+}
+
+fn csv_row(fields: &[String]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|field| {
+            if field.contains([',', '"', '\n']) {
+                format!("\"{}\"", field.replace('"', "\"\""))
+            } else {
+                field.clone()
+            }
+        })
+        .collect();
+    fields.join(",") + "\n"
+}
+
+/// The totals as a line of block characters, e.g. `▁▃▅█`.
+pub fn sparkline(values: &[usize]) -> String {
+    const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+    let (Some(min), Some(max)) = (values.iter().min(), values.iter().max()) else {
+        return String::new();
+    };
+    values
+        .iter()
+        .map(|value| {
+            let index = match max - min {
+                0 => 0,
+                range => (value - min) * (BARS.len() - 1) / range,
+            };
+            BARS[index]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::test_fixtures::git;
+
+    fn commit(directory: &Path, source: &str, tag: &str) {
+        fs::create_dir_all(directory.join("src")).unwrap();
+        fs::write(directory.join("src/main.rs"), source).unwrap();
+        git(directory, &["add", "."]);
+        git(directory, &["commit", "-q", "-m", tag]);
+        git(directory, &["tag", tag]);
+    }
+
+    #[test]
+    fn counts_todos_in_sampled_commits() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        git(root, &["init", "-q"]);
+        commit(root, "fn main() {}\n", "v1");
+        commit(root, "//TODO bug: parse flags\nfn main() {}\n", "v2");
+        commit(
+            root,
+            "//TODO bug: parse flags\n//TODO feature: retry; assigned OthelloEngineer\n",
+            "v3",
+        );
+
+        let every_other = history(root, &Sampling::EveryNth(2), &Syntax::default()).unwrap();
+        let totals: Vec<usize> = every_other.iter().map(|point| point.total).collect();
+        assert_eq!(totals, [0, 2]);
+
+        let tags = history(root, &Sampling::Tags, &Syntax::default()).unwrap();
+        assert_eq!(tags.len(), 3);
+        assert_eq!(tags[2].revision, "v3");
+        assert_eq!(tags[2].by_type["bug"], 1);
+        // Counted by who wrote them, not who they're assigned to.
+        assert_eq!(tags[2].by_author["jolee18"], 2);
+        assert!(!tags[2].by_author.contains_key("OthelloEngineer"));
+        assert_eq!(tags[2].by_directory["src"], 2);
+
+        let csv = to_csv(&tags);
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(
+            lines[0],
+            "revision,date,total,type:bug,type:feature,\
+             author:jolee18,directory:src"
+        );
+        assert!(lines[1].starts_with("v1,") && lines[1].ends_with(",0,0,0,0,0"));
+        assert!(lines[3].ends_with(",2,1,1,2,2"));
+    }
+
+    #[test]
+    fn draws_sparklines() {
+        assert_eq!(sparkline(&[0, 7, 14]), "▁▄█");
+        assert_eq!(sparkline(&[3, 3]), "▁▁");
+        assert_eq!(sparkline(&[]), "");
+    }
+}
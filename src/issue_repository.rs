@@ -1,4 +1,6 @@
-use crate::todofinder::IssueType;
+use std::fmt;
+
+use crate::todofinder::{IssueType, ToDo};
 use async_trait::async_trait;
 use color_eyre::eyre::Result;
 use serde::Deserialize;
@@ -7,17 +9,75 @@ use serde::Serialize;
 #[async_trait]
 pub trait IssueBoard {
     async fn get_issues(&self) -> Result<Vec<Issue>>;
-    async fn get_issue(&self, number: u32) -> Result<Issue>;
-    async fn add_issue(&self, issue: Issue) -> Result<u32>;
-    async fn update_issue(&self, number: u32, update: &IssueUpdateRequest) -> Result<()>;
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue>;
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId>;
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()>;
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()>;
     fn get_repository_name(&self) -> String;
 }
 
+/// Failures a board can report in a way callers may want to handle, e.g. by retrying.
+/// Returned wrapped in a `color_eyre::Report`, so use `downcast_ref` to inspect it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardError {
+    NotFound(IssueId),
+    RateLimited,
+    Timeout,
+}
+
+impl fmt::Display for BoardError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BoardError::NotFound(id) => write!(f, "issue {} not found", id),
+            BoardError::RateLimited => write!(f, "rate limited by the issue board"),
+            BoardError::Timeout => write!(f, "issue board timed out"),
+        }
+    }
+}
+
+impl std::error::Error for BoardError {}
+
+/// Identifies an issue on its board: a number on GitHub-style boards, a key such as
+/// `PROJ-123` on Jira. Serialised untagged, so a plain number stays a plain number.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(untagged)]
+pub enum IssueId {
+    Number(u32),
+    Key(String),
+}
+
+impl From<u32> for IssueId {
+    fn from(number: u32) -> IssueId {
+        IssueId::Number(number)
+    }
+}
+
+impl From<&str> for IssueId {
+    fn from(key: &str) -> IssueId {
+        match key.parse::<u32>() {
+            Ok(number) => IssueId::Number(number),
+            Err(_) => IssueId::Key(key.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for IssueId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IssueId::Number(number) => write!(f, "{}", number),
+            IssueId::Key(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum IssueUpdateRequest {
     State(IssueState),
     Assignee(User),
     Title(String),
     IssueType(IssueType),
+    /// Adds a label, keeping the ones the issue already has.
+    AddLabel(Label),
     Delete(),
 }
 impl IssueUpdateRequest {
@@ -27,22 +87,22 @@ impl IssueUpdateRequest {
             IssueUpdateRequest::Assignee(assignee) => assignee.name.as_str(),
             IssueUpdateRequest::Title(title) => title.as_str(),
             IssueUpdateRequest::IssueType(issue_type) => issue_type.as_str(),
+            IssueUpdateRequest::AddLabel(label) => label.name.as_str(),
             IssueUpdateRequest::Delete() => "delete",
         }
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct User {
-    #[serde(rename = "login")]
     pub name: String,
 }
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Label {
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize, Eq, Hash, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum IssueState {
     Open,
@@ -58,17 +118,88 @@ impl IssueState {
         }
     }
 }
-#[derive(Debug, Deserialize, Serialize)]
+/// Board-neutral issue model. Each `IssueBoard` maps its own wire format to and from this.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct Issue {
-    pub number: Option<u32>,
+    pub number: Option<IssueId>,
     pub title: String,
-    #[serde(rename = "user")]
     pub author: User,
     pub assignee: Option<User>,
-    #[serde(rename = "labels")]
     pub issue_type: Vec<Label>,
     pub state: String,
     pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<IssueSource>,
+}
+
+/// Where the ToDo behind an issue lives. Boards that support custom fields (e.g. Jira) can
+/// store these separately instead of only in the body.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct IssueSource {
+    pub file_path: String,
+    pub line_number: usize,
+    pub author: String,
+}
+
+impl From<&ToDo> for Issue {
+    /// The issue to file for a newly found ToDo.
+    fn from(todo: &ToDo) -> Issue {
+        IssueTemplate::default().issue(todo)
+    }
+}
+
+/// How a newly found ToDo becomes an issue. The body may use `{file}`, `{line}`, `{source}`,
+/// `{description}`, `{type}` and `{author}`; `labels` are added after the issue-type label.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IssueTemplate {
+    pub body: String,
+    pub labels: Vec<(IssueType, Vec<String>)>,
+}
+
+impl Default for IssueTemplate {
+    fn default() -> IssueTemplate {
+        IssueTemplate {
+            body: String::from("Found in {file} at line {line}:\n\n    {source}"),
+            labels: vec![],
+        }
+    }
+}
+
+impl IssueTemplate {
+    pub fn issue(&self, todo: &ToDo) -> Issue {
+        let mut labels = vec![Label {
+            name: todo.issue_type.as_str().to_string(),
+        }];
+        for (issue_type, names) in &self.labels {
+            if *issue_type == todo.issue_type {
+                labels.extend(names.iter().map(|name| Label { name: name.clone() }));
+            }
+        }
+        let body = self
+            .body
+            .replace("{file}", &todo.submission.file_path)
+            .replace("{line}", &todo.submission.line_number.to_string())
+            .replace("{source}", todo.submission.line.trim())
+            .replace("{description}", &todo.description)
+            .replace("{type}", todo.issue_type.as_str())
+            .replace("{author}", &todo.submission.issuer);
+        Issue {
+            number: None,
+            title: todo.description.clone(),
+            author: User {
+                name: todo.submission.issuer.clone(),
+            },
+            assignee: todo.assigned.clone().map(|name| User { name }),
+            issue_type: labels,
+            state: IssueState::Open.as_str().to_string(),
+            body: Some(body),
+            source: Some(IssueSource {
+                file_path: todo.submission.file_path.clone(),
+                line_number: todo.submission.line_number,
+                author: todo.submission.issuer.clone(),
+            }),
+        }
+    }
 }
 
 impl Issue {
@@ -81,6 +212,38 @@ impl Issue {
         }
     }
 
+    /// Applies `update` the way a board would, for boards that store issues themselves.
+    pub fn apply(&mut self, update: &IssueUpdateRequest) {
+        match update {
+            IssueUpdateRequest::State(state) => self.state = state.as_str().to_string(),
+            IssueUpdateRequest::Assignee(user) => self.assignee = Some(user.clone()),
+            IssueUpdateRequest::Title(title) => self.title = title.clone(),
+            IssueUpdateRequest::IssueType(issue_type) => {
+                self.issue_type = self.labels_with_type(issue_type)
+            }
+            IssueUpdateRequest::AddLabel(label) => {
+                if !self.issue_type.contains(label) {
+                    self.issue_type.push(label.clone());
+                }
+            }
+            IssueUpdateRequest::Delete() => self.state = IssueState::Closed.as_str().to_string(),
+        }
+    }
+
+    /// The issue's labels with its type label, if any, swapped for `issue_type`.
+    pub fn labels_with_type(&self, issue_type: &IssueType) -> Vec<Label> {
+        let mut labels: Vec<Label> = self
+            .issue_type
+            .iter()
+            .filter(|label| !is_type_label(&label.name))
+            .cloned()
+            .collect();
+        labels.push(Label {
+            name: issue_type.as_str().to_string(),
+        });
+        labels
+    }
+
     pub fn issue_type(&self) -> IssueType {
         let mut issue_type = IssueType::Other;
         for label in &self.issue_type {
@@ -94,3 +257,10 @@ impl Issue {
         issue_type
     }
 }
+
+/// Whether `name` is a label tissue gives issues of some `IssueType`.
+pub fn is_type_label(name: &str) -> bool {
+    IssueType::ALL
+        .iter()
+        .any(|issue_type| issue_type.as_str() == name)
+}
@@ -0,0 +1,544 @@
+use std::collections::HashMap;
+
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+use reqwest::{header, Client, Method, RequestBuilder};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+
+use crate::issue_repository::{
+    Issue, IssueBoard, IssueId, IssueState, IssueUpdateRequest, Label, User,
+};
+use crate::todofinder::IssueType;
+
+const PAGE_SIZE: usize = 50;
+
+#[derive(Debug)]
+pub enum JiraAuth {
+    /// Jira Cloud: account e-mail plus API token.
+    Basic { email: String, api_token: String },
+    /// Jira Server/Data Center personal access token.
+    Bearer(String),
+}
+
+/// Custom field IDs (e.g. `customfield_10042`) that receive the ToDo's location and author.
+#[derive(Debug, Default)]
+pub struct JiraFieldMapping {
+    pub file: Option<String>,
+    pub line: Option<String>,
+    pub author: Option<String>,
+}
+
+/// `IssueBoard` backed by the Jira REST API v2.
+///
+/// Issues are identified by their key (`PROJ-123`). `IssueType` maps to a Jira issue type and
+/// `IssueState` to the name of the workflow transition that reaches it; both default to the
+/// names used by Jira's built-in workflows and can be overridden per project.
+#[derive(Debug)]
+pub struct JiraIntegration {
+    base_url: String,
+    project_key: String,
+    auth: JiraAuth,
+    issue_types: HashMap<IssueType, String>,
+    transitions: HashMap<IssueState, String>,
+    fields: JiraFieldMapping,
+    client: Client,
+}
+
+impl JiraIntegration {
+    pub fn new(base_url: &str, project_key: &str, auth: JiraAuth) -> JiraIntegration {
+        let issue_types = HashMap::from([
+            (IssueType::Bug, String::from("Bug")),
+            (IssueType::Feature, String::from("Story")),
+            (IssueType::Improvement, String::from("Improvement")),
+            (IssueType::Other, String::from("Task")),
+        ]);
+        let transitions = HashMap::from([
+            (IssueState::Open, String::from("To Do")),
+            (IssueState::Reopened, String::from("To Do")),
+            (IssueState::Closed, String::from("Done")),
+        ]);
+        JiraIntegration {
+            base_url: base_url.trim_end_matches('/').to_string(),
+            project_key: project_key.to_string(),
+            auth,
+            issue_types,
+            transitions,
+            fields: JiraFieldMapping::default(),
+            client: Client::new(),
+        }
+    }
+
+    pub fn with_issue_type(mut self, issue_type: IssueType, jira_type: &str) -> JiraIntegration {
+        self.issue_types.insert(issue_type, jira_type.to_string());
+        self
+    }
+
+    pub fn with_transition(mut self, state: IssueState, transition: &str) -> JiraIntegration {
+        self.transitions.insert(state, transition.to_string());
+        self
+    }
+
+    pub fn with_custom_fields(mut self, fields: JiraFieldMapping) -> JiraIntegration {
+        self.fields = fields;
+        self
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let request = self
+            .client
+            .request(method, format!("{}/rest/api/2/{}", self.base_url, path))
+            .header(header::ACCEPT, "application/json")
+            .header(header::USER_AGENT, "tissue");
+        match &self.auth {
+            JiraAuth::Basic { email, api_token } => request.basic_auth(email, Some(api_token)),
+            JiraAuth::Bearer(token) => request.bearer_auth(token),
+        }
+    }
+
+    async fn send(&self, request: RequestBuilder, action: &str) -> Result<reqwest::Response> {
+        let response = request.send().await?;
+        if !response.status().is_success() {
+            return Err(color_eyre::Report::msg(format!(
+                "Jira failed to {}: {}",
+                action,
+                response.status()
+            )));
+        }
+        Ok(response)
+    }
+
+    fn issue_type_name(&self, issue_type: &IssueType) -> &str {
+        self.issue_types
+            .get(issue_type)
+            .map(String::as_str)
+            .unwrap_or("Task")
+    }
+
+    fn to_issue(&self, issue: JiraIssue) -> Issue {
+        let fields = issue.fields;
+        let done = fields
+            .status
+            .status_category
+            .map(|category| category.key == "done")
+            .unwrap_or(false);
+        let state = if done {
+            IssueState::Closed
+        } else {
+            IssueState::Open
+        };
+        // Surface the Jira issue type as a label so `Issue::issue_type` works unchanged.
+        let mut labels: Vec<Label> = fields
+            .issuetype
+            .and_then(|jira_type| {
+                self.issue_types
+                    .iter()
+                    .find(|(_, name)| **name == jira_type.name)
+                    .map(|(issue_type, _)| issue_type.as_str().to_string())
+            })
+            .into_iter()
+            .map(|name| Label { name })
+            .collect();
+        labels.extend(fields.labels.into_iter().map(|name| Label { name }));
+        Issue {
+            number: Some(IssueId::Key(issue.key)),
+            title: fields.summary,
+            author: User {
+                name: fields.reporter.map(JiraUser::name).unwrap_or_default(),
+            },
+            assignee: fields.assignee.map(|user| User { name: user.name() }),
+            issue_type: labels,
+            state: state.as_str().to_string(),
+            body: fields.description,
+            source: None,
+        }
+    }
+
+    async fn transition(&self, id: &IssueId, state: &IssueState) -> Result<()> {
+        let target = self
+            .transitions
+            .get(state)
+            .ok_or_else(|| color_eyre::Report::msg(format!("No transition for {:?}", state)))?;
+        let path = format!("issue/{}/transitions", id);
+        let available = self
+            .send(self.request(Method::GET, &path), "list transitions")
+            .await?
+            .json::<JiraTransitions>()
+            .await?;
+        let transition = available
+            .transitions
+            .iter()
+            .find(|transition| {
+                transition.name.eq_ignore_ascii_case(target)
+                    || transition.to.name.eq_ignore_ascii_case(target)
+            })
+            .ok_or_else(|| {
+                color_eyre::Report::msg(format!("Transition {} is not available on {}", target, id))
+            })?;
+        self.send(
+            self.request(Method::POST, &path)
+                .json(&json!({ "transition": { "id": transition.id } })),
+            "transition issue",
+        )
+        .await?;
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraUser {
+    name: Option<String>,
+    #[serde(rename = "displayName")]
+    display_name: Option<String>,
+}
+
+impl JiraUser {
+    fn name(self) -> String {
+        self.name.or(self.display_name).unwrap_or_default()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraStatusCategory {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraStatus {
+    #[serde(rename = "statusCategory")]
+    status_category: Option<JiraStatusCategory>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraIssueType {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraFields {
+    summary: String,
+    description: Option<String>,
+    status: JiraStatus,
+    reporter: Option<JiraUser>,
+    assignee: Option<JiraUser>,
+    #[serde(default)]
+    labels: Vec<String>,
+    issuetype: Option<JiraIssueType>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraIssue {
+    key: String,
+    fields: JiraFields,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraSearch {
+    issues: Vec<JiraIssue>,
+    total: usize,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraCreated {
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraTransitionTarget {
+    name: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraTransition {
+    id: String,
+    name: String,
+    to: JiraTransitionTarget,
+}
+
+#[derive(Debug, Deserialize)]
+struct JiraTransitions {
+    transitions: Vec<JiraTransition>,
+}
+
+#[async_trait]
+impl IssueBoard for JiraIntegration {
+    async fn get_issues(&self) -> Result<Vec<Issue>> {
+        let jql = format!("project = \"{}\" ORDER BY key ASC", self.project_key);
+        let mut issues = Vec::new();
+        loop {
+            let start_at = issues.len().to_string();
+            let page_size = PAGE_SIZE.to_string();
+            let request = self.request(Method::GET, "search").query(&[
+                ("jql", jql.as_str()),
+                ("startAt", start_at.as_str()),
+                ("maxResults", page_size.as_str()),
+            ]);
+            let search = self
+                .send(request, "search issues")
+                .await?
+                .json::<JiraSearch>()
+                .await?;
+            let fetched = search.issues.len();
+            issues.extend(search.issues.into_iter().map(|issue| self.to_issue(issue)));
+            if fetched == 0 || issues.len() >= search.total {
+                break;
+            }
+        }
+        Ok(issues)
+    }
+
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue> {
+        let request = self.request(Method::GET, &format!("issue/{}", id));
+        let issue = self
+            .send(request, &format!("fetch issue {}", id))
+            .await?
+            .json::<JiraIssue>()
+            .await?;
+        Ok(self.to_issue(issue))
+    }
+
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId> {
+        let mut fields = Map::new();
+        fields.insert("project".into(), json!({ "key": self.project_key }));
+        fields.insert("summary".into(), json!(issue.title));
+        fields.insert(
+            "issuetype".into(),
+            json!({ "name": self.issue_type_name(&issue.issue_type()) }),
+        );
+        if let Some(body) = &issue.body {
+            fields.insert("description".into(), json!(body));
+        }
+        let labels: Vec<&str> = issue
+            .issue_type
+            .iter()
+            .map(|label| label.name.as_str())
+            .collect();
+        fields.insert("labels".into(), json!(labels));
+        if let Some(assignee) = &issue.assignee {
+            fields.insert("assignee".into(), json!({ "name": assignee.name }));
+        }
+        if let Some(source) = &issue.source {
+            let custom = [
+                (&self.fields.file, json!(source.file_path)),
+                (&self.fields.line, json!(source.line_number)),
+                (&self.fields.author, json!(source.author)),
+            ];
+            for (field, value) in custom {
+                if let Some(field) = field {
+                    fields.insert(field.clone(), value);
+                }
+            }
+        }
+
+        let request = self
+            .request(Method::POST, "issue")
+            .json(&json!({ "fields": Value::Object(fields) }));
+        let created = self
+            .send(request, "create issue")
+            .await?
+            .json::<JiraCreated>()
+            .await?;
+        Ok(IssueId::Key(created.key))
+    }
+
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()> {
+        let body = match update {
+            IssueUpdateRequest::State(state) => return self.transition(id, state).await,
+            IssueUpdateRequest::Delete() => return self.transition(id, &IssueState::Closed).await,
+            IssueUpdateRequest::Assignee(user) => {
+                json!({ "fields": { "assignee": { "name": user.name } } })
+            }
+            IssueUpdateRequest::Title(title) => json!({ "fields": { "summary": title } }),
+            IssueUpdateRequest::IssueType(issue_type) => {
+                json!({ "fields": { "issuetype": { "name": self.issue_type_name(issue_type) } } })
+            }
+            IssueUpdateRequest::AddLabel(label) => {
+                json!({ "update": { "labels": [{ "add": label.name }] } })
+            }
+        };
+        let request = self
+            .request(Method::PUT, &format!("issue/{}", id))
+            .json(&body);
+        self.send(request, &format!("update issue {}", id)).await?;
+        Ok(())
+    }
+
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()> {
+        let request = self
+            .request(Method::POST, &format!("issue/{}/comment", id))
+            .json(&json!({ "body": body }));
+        self.send(request, &format!("comment on issue {}", id))
+            .await?;
+        Ok(())
+    }
+
+    fn get_repository_name(&self) -> String {
+        format!("jira:{}", self.project_key)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockito::Matcher;
+
+    use super::*;
+    use crate::issue_repository::IssueSource;
+
+    fn issue_json(key: &str, category: &str) -> Value {
+        json!({
+            "key": key,
+            "fields": {
+                "summary": "test issue",
+                "description": "found in src/main.rs",
+                "status": {"name": "In Review", "statusCategory": {"key": category}},
+                "reporter": {"name": "OthelloEngineer"},
+                "assignee": {"displayName": "jolee18"},
+                "labels": ["tissue"],
+                "issuetype": {"name": "Story"}
+            }
+        })
+    }
+
+    fn board(url: &str) -> JiraIntegration {
+        JiraIntegration::new(url, "PROJ", JiraAuth::Bearer("secret".to_string()))
+    }
+
+    #[tokio::test]
+    async fn maps_jira_issue_to_issue() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("GET", "/rest/api/2/issue/PROJ-123")
+            .match_header("authorization", "Bearer secret")
+            .with_body(issue_json("PROJ-123", "done").to_string())
+            .create_async()
+            .await;
+
+        let issue = board(&server.url())
+            .get_issue(&IssueId::from("PROJ-123"))
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(issue.number, Some(IssueId::Key("PROJ-123".to_string())));
+        assert_eq!(issue.issue_type(), IssueType::Feature);
+        assert_eq!(issue.state().as_str(), "closed");
+        assert_eq!(issue.author.name, "OthelloEngineer");
+        assert_eq!(issue.assignee.unwrap().name, "jolee18");
+    }
+
+    #[tokio::test]
+    async fn creates_issue_with_type_and_custom_fields() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/rest/api/2/issue")
+            .match_body(Matcher::PartialJson(json!({
+                "fields": {
+                    "project": {"key": "PROJ"},
+                    "summary": "test issue",
+                    "issuetype": {"name": "Defect"},
+                    "customfield_1": "src/main.rs",
+                    "customfield_2": 12,
+                    "customfield_3": "OthelloEngineer"
+                }
+            })))
+            .with_status(201)
+            .with_body(r#"{"id": "10000", "key": "PROJ-124"}"#)
+            .create_async()
+            .await;
+
+        let board = board(&server.url())
+            .with_issue_type(IssueType::Bug, "Defect")
+            .with_custom_fields(JiraFieldMapping {
+                file: Some("customfield_1".to_string()),
+                line: Some("customfield_2".to_string()),
+                author: Some("customfield_3".to_string()),
+            });
+        let key = board
+            .add_issue(Issue {
+                number: None,
+                title: "test issue".to_string(),
+                author: User {
+                    name: "OthelloEngineer".to_string(),
+                },
+                assignee: None,
+                issue_type: vec![Label {
+                    name: "bug".to_string(),
+                }],
+                state: "open".to_string(),
+                body: None,
+                source: Some(IssueSource {
+                    file_path: "src/main.rs".to_string(),
+                    line_number: 12,
+                    author: "OthelloEngineer".to_string(),
+                }),
+            })
+            .await
+            .unwrap();
+
+        mock.assert_async().await;
+        assert_eq!(key, IssueId::Key("PROJ-124".to_string()));
+    }
+
+    #[tokio::test]
+    async fn closes_issue_through_workflow_transition() {
+        let mut server = mockito::Server::new_async().await;
+        let list = server
+            .mock("GET", "/rest/api/2/issue/PROJ-123/transitions")
+            .with_body(
+                json!({"transitions": [
+                    {"id": "11", "name": "Start", "to": {"name": "In Progress"}},
+                    {"id": "31", "name": "Resolve", "to": {"name": "Done"}}
+                ]})
+                .to_string(),
+            )
+            .create_async()
+            .await;
+        let apply = server
+            .mock("POST", "/rest/api/2/issue/PROJ-123/transitions")
+            .match_body(Matcher::Json(json!({"transition": {"id": "31"}})))
+            .with_status(204)
+            .create_async()
+            .await;
+
+        board(&server.url())
+            .update_issue(
+                &IssueId::from("PROJ-123"),
+                &IssueUpdateRequest::State(IssueState::Closed),
+            )
+            .await
+            .unwrap();
+
+        list.assert_async().await;
+        apply.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn pages_through_search_results() {
+        let mut server = mockito::Server::new_async().await;
+        let first = server
+            .mock("GET", "/rest/api/2/search")
+            .match_query(Matcher::UrlEncoded("startAt".into(), "0".into()))
+            .with_body(
+                json!({"startAt": 0, "total": 2, "issues": [issue_json("PROJ-1", "new")]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+        let second = server
+            .mock("GET", "/rest/api/2/search")
+            .match_query(Matcher::UrlEncoded("startAt".into(), "1".into()))
+            .with_body(
+                json!({"startAt": 1, "total": 2, "issues": [issue_json("PROJ-2", "new")]})
+                    .to_string(),
+            )
+            .create_async()
+            .await;
+
+        let issues = board(&server.url()).get_issues().await.unwrap();
+
+        first.assert_async().await;
+        second.assert_async().await;
+        assert_eq!(issues.len(), 2);
+        assert_eq!(issues[1].number, Some(IssueId::Key("PROJ-2".to_string())));
+    }
+}
@@ -0,0 +1,38 @@
+#[path = "board-contract-test.rs"]
+pub mod board_contract_tests;
+pub mod config;
+pub mod filereader;
+pub mod gitea_integration;
+pub mod githandler;
+pub mod github_integration;
+pub mod gitlab_integration;
+pub mod history;
+#[path = "integration-test.rs"]
+pub mod integration_tests;
+pub mod issue_repository;
+pub mod jira_integration;
+pub mod local_board;
+pub mod lsp;
+pub mod patch;
+pub mod policy;
+pub mod pull;
+pub mod report;
+pub mod routing;
+pub mod sarif;
+pub mod scan_cache;
+pub mod scanner;
+pub mod stale;
+pub mod sync;
+#[cfg(test)]
+mod test_fixtures;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod tissuebox_file;
+pub mod tissuebox_merge;
+pub mod tissuebox_migration;
+pub mod tissuebox_repository;
+pub mod tissuebox_sqlite;
+pub mod todo_diff;
+pub mod todofinder;
+pub mod walker;
+pub mod watch;
@@ -0,0 +1,359 @@
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+};
+
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+
+use crate::issue_repository::{
+    Issue, IssueBoard, IssueId, IssueSource, IssueState, IssueUpdateRequest, Label, User,
+};
+
+pub const DEFAULT_LOCAL_BOARD_DIR: &str = ".tissue/issues";
+
+/// Offline `IssueBoard` that keeps one Markdown file per issue, e.g. `.tissue/issues/007.md`.
+///
+/// Each file starts with a `---` delimited front-matter block of `key: value` lines holding
+/// the issue's metadata, followed by the body. Numbers are allocated by the board itself.
+#[derive(Debug)]
+pub struct LocalBoard {
+    directory: PathBuf,
+}
+
+impl LocalBoard {
+    pub fn new(directory: impl AsRef<Path>) -> LocalBoard {
+        LocalBoard {
+            directory: directory.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Board stored under `.tissue/issues` in the current directory.
+    pub fn in_repository() -> LocalBoard {
+        LocalBoard::new(DEFAULT_LOCAL_BOARD_DIR)
+    }
+
+    fn issue_path(&self, number: u32) -> PathBuf {
+        self.directory.join(format!("{:03}.md", number))
+    }
+
+    fn number(id: &IssueId) -> Result<u32> {
+        match id {
+            IssueId::Number(number) => Ok(*number),
+            IssueId::Key(key) => Err(color_eyre::Report::msg(format!(
+                "Local boards only use numeric issue IDs, got {}",
+                key
+            ))),
+        }
+    }
+
+    fn numbers(&self) -> Result<Vec<u32>> {
+        let entries = match fs::read_dir(&self.directory) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(vec![]),
+            Err(e) => return Err(e.into()),
+        };
+        let mut numbers = Vec::new();
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|extension| extension == "md") {
+                if let Some(number) = path
+                    .file_stem()
+                    .and_then(|stem| stem.to_str())
+                    .and_then(|stem| stem.parse::<u32>().ok())
+                {
+                    numbers.push(number);
+                }
+            }
+        }
+        numbers.sort();
+        Ok(numbers)
+    }
+
+    fn read_issue(&self, number: u32) -> Result<Issue> {
+        let path = self.issue_path(number);
+        let content = fs::read_to_string(&path).map_err(|e| {
+            color_eyre::Report::msg(format!("Issue {} not found at {:?}: {}", number, path, e))
+        })?;
+        parse_issue(&content)
+            .ok_or_else(|| color_eyre::Report::msg(format!("Malformed issue file {:?}", path)))
+    }
+
+    fn write_issue(&self, number: u32, issue: &Issue) -> Result<()> {
+        fs::write(self.issue_path(number), render_issue(number, issue)?)?;
+        Ok(())
+    }
+}
+
+/// The issue file: one `key: value` line per field, then the body. Fails if a field would span
+/// lines, as it couldn't be read back.
+fn render_issue(number: u32, issue: &Issue) -> Result<String> {
+    let mut front_matter = vec![
+        format!("number: {}", number),
+        format!("title: {}", issue.title),
+        format!("state: {}", issue.state().as_str()),
+        format!("author: {}", issue.author.name),
+    ];
+    if let Some(assignee) = &issue.assignee {
+        front_matter.push(format!("assignee: {}", assignee.name));
+    }
+    let labels: Vec<&str> = issue
+        .issue_type
+        .iter()
+        .map(|label| label.name.as_str())
+        .collect();
+    front_matter.push(format!("labels: {}", labels.join(", ")));
+    if let Some(source) = &issue.source {
+        front_matter.push(format!("file: {}", source.file_path));
+        front_matter.push(format!("line: {}", source.line_number));
+        front_matter.push(format!("source_author: {}", source.author));
+    }
+    if let Some(field) = front_matter
+        .iter()
+        .find(|field| field.contains(['\n', '\r']))
+    {
+        return Err(color_eyre::Report::msg(format!(
+            "Can't store '{}' in an issue file: it spans several lines",
+            field.replace(['\n', '\r'], " ")
+        )));
+    }
+    Ok(format!(
+        "---\n{}\n---\n{}\n",
+        front_matter.join("\n"),
+        issue.body.as_deref().unwrap_or_default()
+    ))
+}
+
+fn parse_issue(content: &str) -> Option<Issue> {
+    let rest = content.strip_prefix("---\n")?;
+    let (front_matter, body) = rest.split_once("\n---\n")?;
+    let value = |key: &str| {
+        front_matter.lines().find_map(|line| {
+            let (line_key, value) = line.split_once(':')?;
+            (line_key.trim() == key).then(|| value.trim().to_string())
+        })
+    };
+    let labels = value("labels")
+        .unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| Label {
+            name: name.to_string(),
+        })
+        .collect();
+    // Optional fields that don't parse are left out rather than losing the issue.
+    let source = match (value("file"), value("line")) {
+        (Some(file_path), Some(line)) => line.parse().ok().map(|line_number| IssueSource {
+            file_path,
+            line_number,
+            author: value("source_author").unwrap_or_default(),
+        }),
+        _ => None,
+    };
+    let body = body.trim_end_matches('\n');
+    Some(Issue {
+        number: Some(IssueId::Number(value("number")?.parse().ok()?)),
+        title: value("title")?,
+        author: User {
+            name: value("author").unwrap_or_default(),
+        },
+        assignee: value("assignee").map(|name| User { name }),
+        issue_type: labels,
+        state: value("state").unwrap_or_else(|| IssueState::Open.as_str().to_string()),
+        body: (!body.is_empty()).then(|| body.to_string()),
+        source,
+    })
+}
+
+#[async_trait]
+impl IssueBoard for LocalBoard {
+    async fn get_issues(&self) -> Result<Vec<Issue>> {
+        self.numbers()?
+            .into_iter()
+            .map(|number| self.read_issue(number))
+            .collect()
+    }
+
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue> {
+        self.read_issue(Self::number(id)?)
+    }
+
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId> {
+        // Rendered up front so a rejected issue doesn't leave an empty file behind.
+        render_issue(0, &issue)?;
+        fs::create_dir_all(&self.directory)?;
+        let mut number = self.numbers()?.last().copied().unwrap_or(0) + 1;
+        // `create_new` makes allocation safe against a concurrent writer taking the same number.
+        let mut file = loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(self.issue_path(number))
+            {
+                Ok(file) => break file,
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => number += 1,
+                Err(e) => return Err(e.into()),
+            }
+        };
+        file.write_all(render_issue(number, &issue)?.as_bytes())?;
+        Ok(IssueId::Number(number))
+    }
+
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()> {
+        let number = Self::number(id)?;
+        let mut issue = self.read_issue(number)?;
+        issue.apply(update);
+        self.write_issue(number, &issue)
+    }
+
+    /// Appends the comment to the issue's body, as the file has no other place for it.
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()> {
+        let number = Self::number(id)?;
+        let mut issue = self.read_issue(number)?;
+        let comment = format!("## Comment\n\n{}", body);
+        issue.body = Some(match issue.body {
+            Some(existing) => format!("{}\n\n{}", existing, comment),
+            None => comment,
+        });
+        self.write_issue(number, &issue)
+    }
+
+    fn get_repository_name(&self) -> String {
+        format!("local:{}", self.directory.display())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::todofinder::IssueType;
+
+    fn new_issue(title: &str) -> Issue {
+        Issue {
+            number: None,
+            title: title.to_string(),
+            author: User {
+                name: "OthelloEngineer".to_string(),
+            },
+            assignee: Some(User {
+                name: "jolee18".to_string(),
+            }),
+            issue_type: vec![Label {
+                name: "bug".to_string(),
+            }],
+            state: "open".to_string(),
+            body: Some("found while testing\n\nsecond paragraph".to_string()),
+            source: Some(IssueSource {
+                file_path: "src/main.rs".to_string(),
+                line_number: 3,
+                author: "OthelloEngineer".to_string(),
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn allocates_numbers_and_round_trips_issues() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = LocalBoard::new(directory.path().join("issues"));
+
+        let first = board.add_issue(new_issue("first")).await.unwrap();
+        let second = board.add_issue(new_issue("second")).await.unwrap();
+        assert_eq!(first, IssueId::Number(1));
+        assert_eq!(second, IssueId::Number(2));
+        assert!(directory.path().join("issues/002.md").exists());
+
+        let issue = board.get_issue(&second).await.unwrap();
+        assert_eq!(issue.title, "second");
+        assert_eq!(issue.number, Some(second));
+        assert_eq!(
+            issue.assignee,
+            Some(User {
+                name: "jolee18".to_string()
+            })
+        );
+        assert_eq!(issue.issue_type(), IssueType::Bug);
+        assert_eq!(
+            issue.body.as_deref(),
+            Some("found while testing\n\nsecond paragraph")
+        );
+        assert_eq!(issue.source.unwrap().line_number, 3);
+        assert_eq!(board.get_issues().await.unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn updates_state_labels_and_assignee() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = LocalBoard::new(directory.path());
+        let id = board.add_issue(new_issue("first")).await.unwrap();
+
+        let updates = [
+            IssueUpdateRequest::AddLabel(Label {
+                name: "priority:high".to_string(),
+            }),
+            IssueUpdateRequest::State(IssueState::Closed),
+            IssueUpdateRequest::IssueType(IssueType::Feature),
+            IssueUpdateRequest::Assignee(User {
+                name: "OthelloEngineer".to_string(),
+            }),
+        ];
+        for update in &updates {
+            board.update_issue(&id, update).await.unwrap();
+        }
+
+        let issue = board.get_issue(&id).await.unwrap();
+        assert_eq!(issue.state().as_str(), "closed");
+        assert_eq!(issue.issue_type(), IssueType::Feature);
+        let labels: Vec<&str> = issue.issue_type.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(labels, ["priority:high", "feature"]);
+        assert_eq!(issue.assignee.unwrap().name, "OthelloEngineer");
+
+        board.add_comment(&id, "still needed?").await.unwrap();
+        let issue = board.get_issue(&id).await.unwrap();
+        assert!(issue.body.unwrap().ends_with("## Comment\n\nstill needed?"));
+    }
+
+    #[tokio::test]
+    async fn rejects_fields_spanning_lines() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = LocalBoard::new(directory.path());
+        assert!(board
+            .add_issue(new_issue("first\nstate: closed"))
+            .await
+            .is_err());
+        assert!(fs::read_dir(directory.path()).unwrap().next().is_none());
+
+        let id = board.add_issue(new_issue("first")).await.unwrap();
+        let assignee = IssueUpdateRequest::Assignee(User {
+            name: "jolee18\r\nlabels: feature".to_string(),
+        });
+        assert!(board.update_issue(&id, &assignee).await.is_err());
+        let issue = board.get_issue(&id).await.unwrap();
+        assert_eq!(issue.assignee.unwrap().name, "jolee18");
+    }
+
+    #[tokio::test]
+    async fn reads_issues_with_a_bad_source_line() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = LocalBoard::new(directory.path());
+        let id = board.add_issue(new_issue("first")).await.unwrap();
+        let path = directory.path().join("001.md");
+        let written = fs::read_to_string(&path).unwrap();
+        fs::write(&path, written.replace("line: 3", "line: three")).unwrap();
+
+        let issue = board.get_issue(&id).await.unwrap();
+        assert_eq!(issue.title, "first");
+        assert_eq!(issue.source, None);
+        assert_eq!(board.get_issues().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn missing_issues_are_errors() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = LocalBoard::new(directory.path());
+        assert!(board.get_issues().await.unwrap().is_empty());
+        assert!(board.get_issue(&IssueId::from(1)).await.is_err());
+        assert!(board.get_issue(&IssueId::from("PROJ-1")).await.is_err());
+    }
+}
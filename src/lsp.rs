@@ -0,0 +1,753 @@
+//! `tissue lsp`: a language server giving feedback on ToDo comments while they are typed.
+//! Malformed ToDos get diagnostics, filed ones show their issues on hover, issue types and
+//! assignees complete, and a code action files an unfiled ToDo on its boards.
+use std::{
+    collections::{BTreeSet, HashMap},
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::{
+    notification::{
+        DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+        Notification as NotificationTrait, PublishDiagnostics, ShowMessage,
+    },
+    request::{CodeActionRequest, Completion, ExecuteCommand, HoverRequest, Request as _},
+    CodeAction, CodeActionKind, CodeActionOrCommand, CodeActionParams,
+    CodeActionProviderCapability, Command, CompletionItem, CompletionItemKind, CompletionOptions,
+    CompletionParams, CompletionResponse, Diagnostic, DiagnosticSeverity,
+    DidChangeTextDocumentParams, DidCloseTextDocumentParams, DidOpenTextDocumentParams,
+    ExecuteCommandOptions, ExecuteCommandParams, Hover, HoverContents, HoverParams,
+    HoverProviderCapability, MarkupContent, MarkupKind, MessageType, Position,
+    PublishDiagnosticsParams, Range, ServerCapabilities, ShowMessageParams,
+    TextDocumentSyncCapability, TextDocumentSyncKind, Uri,
+};
+use regex::Regex;
+
+use crate::{
+    config::Config,
+    githandler,
+    issue_repository::IssueBoard,
+    tissuebox_file::TissueBoxFile,
+    tissuebox_repository::{BoardLink, Tissue, TissueBoxError, TissueBoxRepository},
+    todofinder::{IssueType, Submission, Syntax, ToDo},
+};
+
+/// Command the "Create issue now" code action runs, with the document URI and line as arguments.
+pub const CREATE_ISSUE_COMMAND: &str = "tissue.createIssue";
+
+/// Serves one client on `connection` until it shuts down. `root` is the repository, and new
+/// issues are filed on those of `boards` the configured routing rules pick.
+pub fn run(
+    connection: &Connection,
+    root: &Path,
+    boards: &[&dyn IssueBoard],
+    config: &Config,
+) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![String::from(" ")]),
+            ..CompletionOptions::default()
+        }),
+        code_action_provider: Some(CodeActionProviderCapability::Simple(true)),
+        execute_command_provider: Some(ExecuteCommandOptions {
+            commands: vec![CREATE_ISSUE_COMMAND.to_string()],
+            ..ExecuteCommandOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = LanguageServer {
+        root: root.to_path_buf(),
+        boards,
+        config,
+        syntax: config.syntax(),
+        documents: HashMap::new(),
+    };
+    for message in &connection.receiver {
+        match message {
+            Message::Request(request) => {
+                if connection.handle_shutdown(&request)? {
+                    return Ok(());
+                }
+                let (response, notifications) = server.handle_request(request);
+                connection.sender.send(response.into())?;
+                for notification in notifications {
+                    connection.sender.send(notification.into())?;
+                }
+            }
+            Message::Notification(notification) => {
+                if let Some(notification) = server.handle_notification(notification)? {
+                    connection.sender.send(notification.into())?;
+                }
+            }
+            Message::Response(_) => {}
+        }
+    }
+    Ok(())
+}
+
+struct LanguageServer<'a> {
+    root: PathBuf,
+    boards: &'a [&'a dyn IssueBoard],
+    config: &'a Config,
+    syntax: Syntax,
+    documents: HashMap<Uri, String>,
+}
+
+impl LanguageServer<'_> {
+    fn handle_notification(&mut self, notification: Notification) -> Result<Option<Notification>> {
+        let uri = match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: DidOpenTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                self.documents
+                    .insert(uri.clone(), params.text_document.text);
+                uri
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: DidChangeTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                // Full sync: the last change holds the whole document.
+                if let Some(change) = params.content_changes.into_iter().last() {
+                    self.documents.insert(uri.clone(), change.text);
+                }
+                uri
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: DidCloseTextDocumentParams =
+                    serde_json::from_value(notification.params)?;
+                self.documents.remove(&params.text_document.uri);
+                return Ok(Some(publish_diagnostics(params.text_document.uri, vec![])));
+            }
+            _ => return Ok(None),
+        };
+        let text = self.documents.get(&uri).map(String::as_str).unwrap_or("");
+        let diagnostics = diagnostics(&self.syntax, text, &self.file_path(&uri));
+        Ok(Some(publish_diagnostics(uri, diagnostics)))
+    }
+
+    fn handle_request(&mut self, request: Request) -> (Response, Vec<Notification>) {
+        let id = request.id.clone();
+        match self.respond(request) {
+            Ok((result, notifications)) => (Response::new_ok(id, result), notifications),
+            Err(e) => (
+                Response::new_err(id, ErrorCode::RequestFailed as i32, e.to_string()),
+                vec![],
+            ),
+        }
+    }
+
+    fn respond(&mut self, request: Request) -> Result<(serde_json::Value, Vec<Notification>)> {
+        let tissues = self.tissues()?;
+        let result = match request.method.as_str() {
+            HoverRequest::METHOD => {
+                let params: HoverParams = serde_json::from_value(request.params)?;
+                let position = params.text_document_position_params;
+                let (text, file_path) = self.document(&position.text_document.uri);
+                serde_json::to_value(hover(
+                    &self.syntax,
+                    text,
+                    &file_path,
+                    position.position.line as usize,
+                    &tissues,
+                ))?
+            }
+            Completion::METHOD => {
+                let params: CompletionParams = serde_json::from_value(request.params)?;
+                let position = params.text_document_position.position;
+                let (text, _) = self.document(&params.text_document_position.text_document.uri);
+                let line = text.lines().nth(position.line as usize).unwrap_or_default();
+                let before_cursor: String = String::from_utf16_lossy(
+                    &line
+                        .encode_utf16()
+                        .take(position.character as usize)
+                        .collect::<Vec<u16>>(),
+                );
+                serde_json::to_value(CompletionResponse::Array(completions(
+                    &self.syntax,
+                    &before_cursor,
+                    &tissues,
+                )))?
+            }
+            CodeActionRequest::METHOD => {
+                let params: CodeActionParams = serde_json::from_value(request.params)?;
+                let uri = params.text_document.uri;
+                let (text, file_path) = self.document(&uri);
+                serde_json::to_value(code_actions(
+                    &self.syntax,
+                    &uri,
+                    text,
+                    &file_path,
+                    params.range,
+                    &tissues,
+                ))?
+            }
+            ExecuteCommand::METHOD => {
+                let params: ExecuteCommandParams = serde_json::from_value(request.params)?;
+                if params.command != CREATE_ISSUE_COMMAND {
+                    return Err(color_eyre::Report::msg(format!(
+                        "Unknown command {}",
+                        params.command
+                    )));
+                }
+                let (uri, line): (Uri, usize) =
+                    serde_json::from_value(serde_json::Value::Array(params.arguments))?;
+                let message = self.create_issue(&uri, line)?;
+                let notification = Notification::new(
+                    ShowMessage::METHOD.to_string(),
+                    ShowMessageParams {
+                        typ: MessageType::INFO,
+                        message,
+                    },
+                );
+                return Ok((serde_json::Value::Null, vec![notification]));
+            }
+            method => {
+                return Err(color_eyre::Report::msg(format!(
+                    "Unsupported request {}",
+                    method
+                )))
+            }
+        };
+        Ok((result, vec![]))
+    }
+
+    /// Files the ToDo on `line` on the boards it routes to and records it in the Tissue Box.
+    fn create_issue(&self, uri: &Uri, line: usize) -> Result<String> {
+        let (text, file_path) = self.document(uri);
+        let mut todo = match todo_at(&self.syntax, text, &file_path, line) {
+            Some(Ok(todo)) => todo,
+            Some(Err(e)) => return Err(color_eyre::Report::msg(e)),
+            None => {
+                return Err(color_eyre::Report::msg(format!(
+                    "There is no ToDo on line {} of {}",
+                    line + 1,
+                    file_path
+                )))
+            }
+        };
+        todo.submission.issuer =
+            githandler::get_current_user().unwrap_or_else(|_| String::from("unknown"));
+
+        let mut tissue_box = TissueBoxFile::open(&self.config.box_path(&self.root))?;
+        let router = self.config.router(&tissue_box)?;
+        let mut tissue = tissue_box
+            .get_tissue_by_fingerprint(&todo.fingerprint())?
+            .unwrap_or_else(|| Tissue::new(todo.clone()));
+        let runtime = tokio::runtime::Runtime::new()?;
+        let mut filed = Vec::new();
+        let mut failed = Vec::new();
+        for board_name in router.route(&file_path) {
+            if tissue.link(board_name).is_some() {
+                continue;
+            }
+            let Some(board) = self
+                .boards
+                .iter()
+                .find(|board| board.get_repository_name() == *board_name)
+            else {
+                failed.push(format!("{} isn't connected", board_name));
+                continue;
+            };
+            let mut issue = self.config.issue_template().issue(&todo);
+            match runtime.block_on(board.add_issue(issue.clone())) {
+                Ok(number) => {
+                    issue.number = Some(number);
+                    let link = BoardLink {
+                        board: board_name.clone(),
+                        issue,
+                    };
+                    filed.push(link.to_string());
+                    tissue.links.push(link);
+                }
+                Err(e) => failed.push(format!("{}: {}", board_name, e)),
+            }
+        }
+        // Issues already filed are recorded even if another board failed.
+        if !filed.is_empty() {
+            tissue_box.upsert_tissues(vec![tissue])?;
+        }
+        match (filed.is_empty(), failed.is_empty()) {
+            (true, true) => Ok(format!("'{}' is already filed", todo.description)),
+            (false, true) => Ok(format!(
+                "Filed '{}' as {}",
+                todo.description,
+                filed.join(", ")
+            )),
+            (true, false) => Err(color_eyre::Report::msg(format!(
+                "Couldn't file '{}': {}",
+                todo.description,
+                failed.join("; ")
+            ))),
+            (false, false) => Err(color_eyre::Report::msg(format!(
+                "Filed '{}' as {}, but couldn't file it on {}",
+                todo.description,
+                filed.join(", "),
+                failed.join("; ")
+            ))),
+        }
+    }
+
+    fn tissues(&self) -> Result<Vec<Tissue>> {
+        match TissueBoxFile::open(&self.config.box_path(&self.root)) {
+            Ok(tissue_box) => tissue_box.get_tissues(),
+            Err(error) if matches!(error.downcast_ref(), Some(TissueBoxError::NotFound { .. })) => {
+                Ok(vec![])
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    fn document(&self, uri: &Uri) -> (&str, String) {
+        let text = self.documents.get(uri).map(String::as_str).unwrap_or("");
+        (text, self.file_path(uri))
+    }
+
+    /// The path of the document relative to the root, as the scanner would report it.
+    fn file_path(&self, uri: &Uri) -> String {
+        let path = uri
+            .path()
+            .as_estr()
+            .decode()
+            .into_string_lossy()
+            .to_string();
+        match Path::new(&path).strip_prefix(&self.root) {
+            Ok(relative) => relative.display().to_string(),
+            Err(_) => path,
+        }
+    }
+}
+
+fn publish_diagnostics(uri: Uri, diagnostics: Vec<Diagnostic>) -> Notification {
+    Notification::new(
+        PublishDiagnostics::METHOD.to_string(),
+        PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        },
+    )
+}
+
+/// The ToDo on line `line` (0-based) of `text`: `None` if there is no ToDo comment there, an
+/// error if there is one that doesn't parse.
+fn todo_at(
+    syntax: &Syntax,
+    text: &str,
+    file_path: &str,
+    line: usize,
+) -> Option<Result<ToDo, String>> {
+    let source = text.lines().nth(line)?;
+    if !syntax.is_comment(source) || !syntax.is_to_do(source) {
+        return None;
+    }
+    Some(syntax.parse(Submission {
+        line_number: line + 1,
+        line: source.trim().to_string(),
+        file_path: file_path.to_string(),
+        issuer: String::from("unknown"),
+        date: String::new(),
+    }))
+}
+
+fn line_range(text: &str, line: usize) -> Range {
+    let source = text.lines().nth(line).unwrap_or_default();
+    let indent = source.len() - source.trim_start().len();
+    let start = source[..indent].encode_utf16().count() as u32;
+    let end = source.encode_utf16().count() as u32;
+    Range::new(
+        Position::new(line as u32, start),
+        Position::new(line as u32, end),
+    )
+}
+
+/// A warning for every ToDo comment in `text` that doesn't parse.
+fn diagnostics(syntax: &Syntax, text: &str, file_path: &str) -> Vec<Diagnostic> {
+    (0..text.lines().count())
+        .filter_map(|line| match todo_at(syntax, text, file_path, line)? {
+            Ok(_) => None,
+            Err(message) => Some(Diagnostic {
+                range: line_range(text, line),
+                severity: Some(DiagnosticSeverity::WARNING),
+                source: Some(String::from("tissue")),
+                message,
+                ..Diagnostic::default()
+            }),
+        })
+        .collect()
+}
+
+/// The issues filed for the ToDo on `line`, as the Tissue Box last saw them.
+fn hover(
+    syntax: &Syntax,
+    text: &str,
+    file_path: &str,
+    line: usize,
+    tissues: &[Tissue],
+) -> Option<Hover> {
+    let todo = todo_at(syntax, text, file_path, line)?.ok()?;
+    let fingerprint = todo.fingerprint();
+    let tissue = tissues
+        .iter()
+        .find(|tissue| tissue.todo.fingerprint() == fingerprint);
+    let mut value = format!("**{}**: {}\n\n", todo.issue_type.as_str(), todo.description);
+    match tissue.filter(|tissue| !tissue.links.is_empty()) {
+        Some(tissue) => {
+            for link in &tissue.links {
+                let assignee = match &link.issue.assignee {
+                    Some(user) => format!("assigned {}", user.name),
+                    None => String::from("unassigned"),
+                };
+                value.push_str(&format!("- {}: {}, {}\n", link, link.issue.state, assignee));
+            }
+        }
+        None => value.push_str("Not filed on any board yet.\n"),
+    }
+    Some(Hover {
+        contents: HoverContents::Markup(MarkupContent {
+            kind: MarkupKind::Markdown,
+            value,
+        }),
+        range: Some(line_range(text, line)),
+    })
+}
+
+/// Issue types and labels right after `TODO`, assignees right after `; assigned`.
+fn completions(syntax: &Syntax, before_cursor: &str, tissues: &[Tissue]) -> Vec<CompletionItem> {
+    let keywords: Vec<String> = syntax.keywords.iter().map(|k| regex::escape(k)).collect();
+    let after_todo = Regex::new(&format!(r"(?i)(?:{})\s+[^\s:]*$", keywords.join("|"))).unwrap();
+    let after_assigned = Regex::new(r"(?i);\s*assigned\s+\S*$").unwrap();
+
+    if after_assigned.is_match(before_cursor) {
+        let mut assignees = BTreeSet::new();
+        for tissue in tissues {
+            assignees.extend(tissue.todo.assigned.clone());
+            assignees.insert(tissue.todo.submission.issuer.clone());
+            for link in &tissue.links {
+                assignees.extend(link.issue.assignee.as_ref().map(|user| user.name.clone()));
+            }
+        }
+        assignees.remove("unknown");
+        return assignees
+            .into_iter()
+            .map(|name| CompletionItem {
+                label: name,
+                kind: Some(CompletionItemKind::REFERENCE),
+                detail: Some(String::from("assignee")),
+                ..CompletionItem::default()
+            })
+            .collect();
+    }
+
+    if after_todo.is_match(before_cursor) && !before_cursor.contains(':') {
+        let mut labels: BTreeSet<String> = tissues
+            .iter()
+            .flat_map(|tissue| &tissue.links)
+            .flat_map(|link| &link.issue.issue_type)
+            .map(|label| label.name.clone())
+            .collect();
+        for issue_type in [IssueType::Bug, IssueType::Feature, IssueType::Improvement] {
+            labels.remove(issue_type.as_str());
+        }
+        labels.remove(IssueType::Other.as_str());
+        let issue_types = syntax
+            .issue_types
+            .iter()
+            .flat_map(|(_, names)| names)
+            .map(|name| (name.clone(), "issue type"));
+        return issue_types
+            .chain(labels.into_iter().map(|label| (label, "label")))
+            .map(|(label, detail)| CompletionItem {
+                insert_text: Some(format!("{}: ", label)),
+                label: format!("{}:", label),
+                kind: Some(CompletionItemKind::ENUM_MEMBER),
+                detail: Some(detail.to_string()),
+                ..CompletionItem::default()
+            })
+            .collect();
+    }
+    vec![]
+}
+
+/// "Create issue now" for each unfiled ToDo in `range`.
+fn code_actions(
+    syntax: &Syntax,
+    uri: &Uri,
+    text: &str,
+    file_path: &str,
+    range: Range,
+    tissues: &[Tissue],
+) -> Vec<CodeActionOrCommand> {
+    (range.start.line as usize..=range.end.line as usize)
+        .filter_map(|line| {
+            let todo = todo_at(syntax, text, file_path, line)?.ok()?;
+            let fingerprint = todo.fingerprint();
+            let filed = tissues
+                .iter()
+                .any(|tissue| tissue.todo.fingerprint() == fingerprint && !tissue.links.is_empty());
+            if filed {
+                return None;
+            }
+            Some(CodeActionOrCommand::CodeAction(CodeAction {
+                title: format!("Create issue now: {}", todo.description),
+                kind: Some(CodeActionKind::QUICKFIX),
+                command: Some(Command {
+                    title: String::from("Create issue now"),
+                    command: CREATE_ISSUE_COMMAND.to_string(),
+                    arguments: Some(vec![
+                        serde_json::to_value(uri).unwrap(),
+                        serde_json::Value::from(line),
+                    ]),
+                }),
+                ..CodeAction::default()
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, thread};
+
+    use lsp_server::RequestId;
+    use lsp_types::{
+        notification::{Exit, Initialized},
+        request::{Initialize, Shutdown},
+        InitializeParams, InitializedParams, TextDocumentIdentifier, TextDocumentItem,
+        TextDocumentPositionParams, WorkDoneProgressParams,
+    };
+
+    use super::*;
+    use crate::{
+        issue_repository::{BoardError, Issue},
+        test_fixtures::init_repository,
+        testing::InMemoryBoard,
+    };
+
+    const SOURCE: &str = "fn main() {\n    //TODO bug: parse flags; assigned jolee18\n    \
+                          // TODO fix this\n}\n";
+
+    fn filed_tissue() -> Tissue {
+        let todo = todo_at(&Syntax::default(), SOURCE, "src/main.rs", 1)
+            .unwrap()
+            .unwrap();
+        let mut tissue = Tissue::new(todo.clone());
+        let mut issue = Issue::from(&todo);
+        issue.number = Some(crate::issue_repository::IssueId::Number(12));
+        issue.issue_type.push(crate::issue_repository::Label {
+            name: String::from("priority:high"),
+        });
+        tissue.links.push(BoardLink {
+            board: String::from("memory"),
+            issue,
+        });
+        tissue
+    }
+
+    #[test]
+    fn reports_malformed_todos() {
+        let diagnostics = diagnostics(&Syntax::default(), SOURCE, "src/main.rs");
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].range, line_range(SOURCE, 2));
+        assert_eq!(diagnostics[0].range.start.character, 4);
+        assert!(diagnostics[0].message.contains("Cannot confirm issue type"));
+    }
+
+    #[test]
+    fn hovers_and_completes_from_the_tissue_box() {
+        let tissues = [filed_tissue()];
+
+        let Some(Hover {
+            contents: HoverContents::Markup(markup),
+            ..
+        }) = hover(&Syntax::default(), SOURCE, "src/main.rs", 1, &tissues)
+        else {
+            panic!("expected a hover");
+        };
+        assert!(markup
+            .value
+            .contains("- memory#12: open, assigned jolee18\n"));
+        assert!(hover(&Syntax::default(), SOURCE, "src/main.rs", 0, &tissues).is_none());
+
+        let labels: Vec<String> = completions(&Syntax::default(), "    //TODO ", &tissues)
+            .into_iter()
+            .map(|item| item.label)
+            .collect();
+        assert_eq!(
+            labels,
+            ["bug:", "feature:", "improvement:", "priority:high:"]
+        );
+        let assignees: Vec<String> =
+            completions(&Syntax::default(), "//TODO bug: x; assigned jo", &tissues)
+                .into_iter()
+                .map(|item| item.label)
+                .collect();
+        assert_eq!(assignees, ["jolee18"]);
+        assert!(completions(&Syntax::default(), "//TODO bug: x", &tissues).is_empty());
+    }
+
+    #[test]
+    fn records_issues_filed_before_a_board_fails() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        init_repository(root);
+        let (github, jira) = (InMemoryBoard::new("memory"), InMemoryBoard::new("jira:OPS"));
+        TissueBoxFile::init(root.to_str().unwrap(), &[&github, &jira]).unwrap();
+        jira.fail_next(BoardError::Timeout);
+        let config = Config {
+            routing: crate::config::RoutingConfig {
+                rules: vec![String::from("** -> memory, jira:OPS")],
+            },
+            ..Config::default()
+        };
+        let boards: [&dyn IssueBoard; 2] = [&github, &jira];
+        let uri = Uri::from_str(&format!("file://{}/src/main.rs", root.display())).unwrap();
+        let mut server = LanguageServer {
+            root: root.to_path_buf(),
+            boards: &boards,
+            config: &config,
+            syntax: config.syntax(),
+            documents: HashMap::new(),
+        };
+        server.documents.insert(uri.clone(), SOURCE.to_string());
+
+        let error = server.create_issue(&uri, 1).unwrap_err();
+        assert!(error
+            .to_string()
+            .contains("as memory#1, but couldn't file it on jira:OPS"));
+        let tissue_box = TissueBoxFile::open(&config.box_path(root)).unwrap();
+        let tissues = tissue_box.get_tissues().unwrap();
+        assert_eq!(tissues[0].links_to_string(), "memory#1");
+
+        // Retrying files it only on the board that failed.
+        let message = server.create_issue(&uri, 1).unwrap();
+        assert_eq!(message, "Filed 'parse flags' as jira:OPS#1");
+        assert_eq!(github.issues().len(), 1);
+    }
+
+    #[test]
+    fn serves_a_client_and_creates_issues() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        init_repository(root);
+        let board = InMemoryBoard::new("memory");
+        TissueBoxFile::init(root.to_str().unwrap(), &[&board]).unwrap();
+        let uri = Uri::from_str(&format!("file://{}/src/main.rs", root.display())).unwrap();
+        let (server, client) = Connection::memory();
+
+        let client = thread::spawn(move || {
+            let request = |id: i32, method: &str, params: serde_json::Value| {
+                client
+                    .sender
+                    .send(Request::new(RequestId::from(id), method.to_string(), params).into())
+                    .unwrap();
+                loop {
+                    match client.receiver.recv().unwrap() {
+                        Message::Response(response) => return response,
+                        Message::Notification(_) | Message::Request(_) => continue,
+                    }
+                }
+            };
+            let notify = |method: &str, params: serde_json::Value| {
+                client
+                    .sender
+                    .send(Notification::new(method.to_string(), params).into())
+                    .unwrap();
+            };
+            #[allow(deprecated)]
+            let initialize = InitializeParams::default();
+            request(
+                1,
+                Initialize::METHOD,
+                serde_json::to_value(initialize).unwrap(),
+            );
+            notify(
+                Initialized::METHOD,
+                serde_json::to_value(InitializedParams {}).unwrap(),
+            );
+            notify(
+                DidOpenTextDocument::METHOD,
+                serde_json::to_value(DidOpenTextDocumentParams {
+                    text_document: TextDocumentItem::new(
+                        uri.clone(),
+                        String::from("rust"),
+                        1,
+                        SOURCE.to_string(),
+                    ),
+                })
+                .unwrap(),
+            );
+            let diagnostics = loop {
+                let Message::Notification(notification) = client.receiver.recv().unwrap() else {
+                    continue;
+                };
+                if notification.method == PublishDiagnostics::METHOD {
+                    break serde_json::from_value::<PublishDiagnosticsParams>(notification.params)
+                        .unwrap()
+                        .diagnostics;
+                }
+            };
+
+            let actions = request(
+                2,
+                CodeActionRequest::METHOD,
+                serde_json::json!({
+                    "textDocument": { "uri": uri },
+                    "range": line_range(SOURCE, 1),
+                    "context": { "diagnostics": [] },
+                }),
+            );
+            let actions: Vec<CodeActionOrCommand> =
+                serde_json::from_value(actions.result.unwrap()).unwrap();
+            let CodeActionOrCommand::CodeAction(action) = &actions[0] else {
+                panic!("expected a code action");
+            };
+            let command = action.command.clone().unwrap();
+            let created = request(
+                3,
+                ExecuteCommand::METHOD,
+                serde_json::to_value(ExecuteCommandParams {
+                    command: command.command,
+                    arguments: command.arguments.unwrap(),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                })
+                .unwrap(),
+            );
+            let hovered = request(
+                4,
+                HoverRequest::METHOD,
+                serde_json::to_value(HoverParams {
+                    text_document_position_params: TextDocumentPositionParams::new(
+                        TextDocumentIdentifier::new(uri.clone()),
+                        Position::new(1, 8),
+                    ),
+                    work_done_progress_params: WorkDoneProgressParams::default(),
+                })
+                .unwrap(),
+            );
+            request(5, Shutdown::METHOD, serde_json::Value::Null);
+            notify(Exit::METHOD, serde_json::Value::Null);
+            (diagnostics, created, hovered)
+        });
+
+        run(&server, root, &[&board], &Config::default()).unwrap();
+        let (diagnostics, created, hovered) = client.join().unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert!(created.error.is_none(), "{:?}", created.error);
+        assert_eq!(board.issues().len(), 1);
+        let hover: Hover = serde_json::from_value(hovered.result.unwrap()).unwrap();
+        let HoverContents::Markup(markup) = hover.contents else {
+            panic!("expected markup");
+        };
+        assert!(markup.value.contains("memory#1: open"));
+    }
+}
@@ -1,16 +1,980 @@
+use std::{
+    collections::HashSet,
+    io::{IsTerminal, Write},
+    path::Path,
+    sync::mpsc,
+    time::{Duration, Instant},
+};
+
+use clap::{Parser, Subcommand, ValueEnum};
 use color_eyre::eyre::{Ok, Result};
+use notify::{RecursiveMode, Watcher};
+use tissue::{
+    config::{self, Config},
+    history::{self, Sampling},
+    issue_repository::{IssueBoard, IssueId},
+    lsp,
+    policy::{self, Policy},
+    pull::{self, BoardChangeKind, ClosedPolicy},
+    report::Report,
+    sarif,
+    scan_cache::ScanCache,
+    scanner::{self, ScanResult},
+    stale::{self, StaleFinding},
+    sync,
+    tissuebox_file::TissueBoxFile,
+    tissuebox_merge,
+    tissuebox_repository::{Tissue, TissueBoxError, TissueBoxRepository},
+    todo_diff,
+    todofinder::{Syntax, ToDo},
+    walker::Walker,
+    watch::WatchIndex,
+};
+
+#[derive(Parser)]
+#[command(name = "tissue", about = "Turns TODO comments into tracked issues")]
+struct Cli {
+    /// Answer yes to every prompt, e.g. to create a missing Tissue Box in CI
+    #[arg(long, short, global = true)]
+    yes: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage the Tissue Box
+    #[command(name = "box")]
+    TissueBox {
+        #[command(subcommand)]
+        command: TissueBoxCommand,
+    },
+    /// List the ToDos in the files git tracks, and the ToDo comments that don't parse
+    ///
+    /// The csv, jsonl, markdown and html formats are an inventory of the ToDos and the issues
+    /// in the Tissue Box for them.
+    Scan {
+        /// Root of the git repository to scan, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+        #[arg(long, value_enum, default_value_t = Format::Text)]
+        format: Format,
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+        /// Prefix of links to source files in reports, e.g. https://github.com/org/repo/blob/main
+        #[arg(long)]
+        source_url: Option<String>,
+        /// Walk every file under the path in parallel, tracked by git or not, showing progress
+        #[arg(long)]
+        all_files: bool,
+        /// Rescan every file instead of reusing .tissue/cache for unchanged ones
+        #[arg(long)]
+        no_cache: bool,
+    },
+    /// Check the ToDos against CI policies
+    ///
+    /// Exits with 2 for ToDos without an issue type, 3 for ToDos without a linked issue, 4 for
+    /// new ToDos in a protected path and 5 for too many ToDos. The first applies if several do.
+    Check {
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+        /// Fail on ToDos that aren't a bug, feature or improvement, or don't parse
+        #[arg(long)]
+        require_type: bool,
+        /// Fail on ToDos with no issue on any board
+        #[arg(long)]
+        require_link: bool,
+        /// Fail on ToDos added to paths matching this glob; repeat for more paths
+        #[arg(long = "protect", value_name = "GLOB")]
+        protected_paths: Vec<String>,
+        /// Fail if there are more ToDos than this
+        #[arg(long)]
+        max_todos: Option<usize>,
+        #[arg(long, value_enum, default_value_t = CheckFormat::Text)]
+        format: CheckFormat,
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Summarise the ToDos a range of commits adds, changes or removes, in Markdown
+    Diff {
+        /// Commits to compare, e.g. `main..feature`
+        range: String,
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+        /// Also post the summary on a pull request, e.g. `github:org/repo#42`
+        #[arg(long, value_name = "BOARD#NUMBER")]
+        comment_on: Option<String>,
+    },
+    /// Show how the number of ToDos changed over the git history
+    ///
+    /// Scans sampled commits without checking them out and counts their ToDos by type, author
+    /// and directory.
+    History {
+        /// Root of the git repository
+        #[arg(long, default_value = ".")]
+        path: String,
+        /// Scan every nth commit on the first-parent line of HEAD
+        #[arg(long, default_value_t = 10, conflicts_with = "tags")]
+        every: usize,
+        /// Scan every tag instead
+        #[arg(long)]
+        tags: bool,
+        #[arg(long, value_enum, default_value_t = HistoryFormat::Text)]
+        format: HistoryFormat,
+        /// File to write to instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Report ToDos older than a given age or past their due date, by owner and directory
+    ///
+    /// A ToDo's age is the date git blame gives its line; a due date is written in the comment
+    /// as `due 2025-01-31`. Rules in the `[stale]` section of tissue.toml replace the defaults
+    /// and may escalate flagged ToDos on their boards, once per rule.
+    Stale {
+        /// Root of the git repository to scan
+        #[arg(long, default_value = ".")]
+        path: String,
+        /// Flag ToDos older than this many days, unless tissue.toml has stale rules
+        #[arg(long, default_value_t = 90)]
+        older_than: i64,
+        /// Exit with an error if any ToDo is flagged, e.g. to fail CI
+        #[arg(long)]
+        fail: bool,
+    },
+    /// File new ToDos on their boards, update moved ones and close the issues of removed ones
+    ///
+    /// Each ToDo goes to the boards the routing rules pick for its file.
+    Sync {
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+    /// Propose code changes for issues closed or reassigned on their boards, as a patch
+    ///
+    /// Reassignments rewrite the `assigned` part of the ToDo comment. Review the patch and
+    /// apply it with `git apply`.
+    Pull {
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+        /// What to do about issues closed while their ToDo is still in the code
+        #[arg(long, value_enum, default_value_t = OnClosed::Flag)]
+        on_closed: OnClosed,
+        /// File to write the patch to instead of stdout
+        #[arg(long, short)]
+        output: Option<String>,
+    },
+    /// Run a language server over stdio for ToDo diagnostics, hover, completion and filing
+    ///
+    /// Issues created from the editor go to the Tissue Box's boards, connected to with the
+    /// settings in tissue.toml.
+    Lsp {
+        /// Root of the git repository, where tissue_box.json is
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+    /// Watch the working tree and report ToDos as they are added or removed
+    ///
+    /// Only changed files are re-parsed. Changes are printed, and sent as JSON lines to
+    /// `--socket` if given.
+    Watch {
+        /// Root of the git repository to watch
+        #[arg(long, default_value = ".")]
+        path: String,
+        /// Unix socket to push each change to, as a JSON line
+        #[arg(long)]
+        socket: Option<String>,
+        /// File new ToDos on their boards once they have been unchanged for this many seconds
+        #[arg(long, value_name = "SECONDS", num_args = 0..=1, default_missing_value = "300")]
+        auto_sync: Option<u64>,
+    },
+    /// Inspect the configuration from tissue.toml, ~/.config/tissue and the environment
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Git merge driver for tissue_box.json
+    ///
+    /// Unions tissues added or synced on either branch and fails only on real conflicts. Enable
+    /// it with `git config merge.tissue.driver "tissue merge-driver %O %A %B"` and a
+    /// `tissue_box.json merge=tissue` line in .gitattributes.
+    MergeDriver {
+        /// Common ancestor (%O)
+        base: String,
+        /// Our version, overwritten with the result (%A)
+        ours: String,
+        /// Their version (%B)
+        theirs: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Text,
+    /// SARIF 2.1.0, for code-scanning dashboards
+    Sarif,
+    Csv,
+    /// JSON Lines, one ToDo per line
+    Jsonl,
+    /// Markdown report grouped by directory, type, author and age
+    Markdown,
+    /// Standalone HTML report grouped by directory, type, author and age
+    Html,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CheckFormat {
+    Text,
+    /// GitHub Actions annotations
+    Github,
+    /// GitLab code-quality report
+    Gitlab,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum HistoryFormat {
+    /// Totals per commit and a sparkline
+    Text,
+    Csv,
+    Json,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OnClosed {
+    /// Reopen the issue; the code is the source of truth
+    Reopen,
+    /// Delete the ToDo comment in the patch
+    DeleteTodo,
+    /// Only report it
+    Flag,
+}
+
+impl From<OnClosed> for ClosedPolicy {
+    fn from(on_closed: OnClosed) -> ClosedPolicy {
+        match on_closed {
+            OnClosed::Reopen => ClosedPolicy::Reopen,
+            OnClosed::DeleteTodo => ClosedPolicy::DeleteToDo,
+            OnClosed::Flag => ClosedPolicy::Flag,
+        }
+    }
+}
 
-pub mod filereader;
-pub mod githandler;
-pub mod github_integration;
-pub mod issue_repository;
-#[path = "integration-test.rs"]
-pub mod tests;
-pub mod tissuebox_file;
-pub mod tissuebox_repository;
-pub mod todofinder;
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Print every setting with the file or environment variable it came from
+    Show {
+        /// Root of the git repository, containing tissue.toml
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum TissueBoxCommand {
+    /// Create an empty tissue_box.json
+    Init {
+        /// Root of the git repository to create the Tissue Box in
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+    /// List the tissues in the Tissue Box
+    List {
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+    /// Add a routing rule such as `infra/** -> gitlab:ops/infra`, or list the rules
+    Route {
+        /// `pattern -> board`; use `default` as the pattern for everything else
+        rule: Option<String>,
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+    /// Upgrade tissue_box.json to the current schema and report what changed
+    Migrate {
+        /// Root of the git repository, containing tissue_box.json
+        #[arg(long, default_value = ".")]
+        path: String,
+    },
+}
 
 fn main() -> Result<()> {
     color_eyre::install()?;
+    let cli = Cli::parse();
+    match cli.command {
+        Command::TissueBox { command } => match command {
+            TissueBoxCommand::Init { path } => init(&box_path(&path)?),
+            TissueBoxCommand::List { path } => list(&open_or_init(&box_path(&path)?, cli.yes)?),
+            TissueBoxCommand::Route { rule, path } => route(
+                &mut open_or_init(&box_path(&path)?, cli.yes)?,
+                rule.as_deref(),
+            ),
+            TissueBoxCommand::Migrate { path } => migrate(&box_path(&path)?),
+        },
+        Command::Scan {
+            path,
+            format,
+            output,
+            source_url,
+            all_files,
+            no_cache,
+        } => scan(
+            &path,
+            format,
+            output.as_deref(),
+            source_url.as_deref(),
+            all_files,
+            no_cache,
+        ),
+        Command::Check {
+            path,
+            require_type,
+            require_link,
+            protected_paths,
+            max_todos,
+            format,
+            output,
+        } => {
+            let flags = Policy {
+                require_issue_type: require_type,
+                require_link,
+                protected_paths,
+                max_todos,
+            };
+            check(&path, flags, format, output.as_deref())
+        }
+        Command::Diff {
+            range,
+            path,
+            comment_on,
+        } => diff(&path, &range, comment_on.as_deref()),
+        Command::History {
+            path,
+            every,
+            tags,
+            format,
+            output,
+        } => {
+            let sampling = if tags {
+                Sampling::Tags
+            } else {
+                Sampling::EveryNth(every)
+            };
+            history(&path, &sampling, format, output.as_deref())
+        }
+        Command::Stale {
+            path,
+            older_than,
+            fail,
+        } => stale(&path, older_than, fail),
+        Command::Sync { path } => sync_todos(&path),
+        Command::Pull {
+            path,
+            on_closed,
+            output,
+        } => pull(&path, on_closed.into(), output.as_deref()),
+        Command::Lsp { path } => lsp(&path),
+        Command::Watch {
+            path,
+            socket,
+            auto_sync,
+        } => watch(&path, socket.as_deref(), auto_sync),
+        Command::Config {
+            command: ConfigCommand::Show { path },
+        } => {
+            print!("{}", config::load(Path::new(&path))?.show());
+            Ok(())
+        }
+        Command::MergeDriver { base, ours, theirs } => merge_driver(&base, &ours, &theirs),
+    }
+}
+
+fn init(path: &str) -> Result<()> {
+    let tissue_box = TissueBoxFile::init(path, &[])?;
+    println!("Created an empty Tissue Box in {}", tissue_box.path);
+    Ok(())
+}
+
+/// Opens the Tissue Box in `path`, offering to create it if there is none. Without a terminal to
+/// ask on, only `--yes` creates it.
+fn open_or_init(path: &str, yes: bool) -> Result<TissueBoxFile> {
+    let error = match TissueBoxFile::open(path) {
+        Result::Ok(tissue_box) => return Ok(tissue_box),
+        Err(error) => error,
+    };
+    if !matches!(error.downcast_ref(), Some(TissueBoxError::NotFound { .. })) {
+        return Err(error);
+    }
+    let create = yes
+        || std::io::stdin().is_terminal()
+            && dialoguer::Confirm::new()
+                .with_prompt("No Tissue Box was found. Do you want to create a new Tissue Box?")
+                .interact()?;
+    if !create {
+        return Err(error);
+    }
+    TissueBoxFile::init(path, &[])
+}
+
+fn list(tissue_box: &TissueBoxFile) -> Result<()> {
+    for tissue in tissue_box.get_tissues()? {
+        let links = if tissue.links.is_empty() {
+            String::from("unsynced")
+        } else {
+            tissue.links_to_string()
+        };
+        println!(
+            "{}:{} {} ({})",
+            tissue.todo.submission.file_path,
+            tissue.todo.submission.line_number,
+            tissue.todo.description,
+            links
+        );
+    }
+    Ok(())
+}
+
+fn route(tissue_box: &mut TissueBoxFile, rule: Option<&str>) -> Result<()> {
+    if let Some(rule) = rule {
+        tissue_box.add_route(rule.parse()?)?;
+    }
+    for route in &tissue_box.routes {
+        println!("{}", route);
+    }
+    Ok(())
+}
+
+fn scan(
+    path: &str,
+    format: Format,
+    output: Option<&str>,
+    source_url: Option<&str>,
+    all_files: bool,
+    no_cache: bool,
+) -> Result<()> {
+    let config = load_config(path)?;
+    let (root, syntax) = (Path::new(path), config.syntax());
+    let scan = if all_files {
+        scan_all_files(root, &syntax)
+    } else if no_cache {
+        scanner::scan(root, &syntax)?
+    } else {
+        let mut cache = ScanCache::load(root, &syntax);
+        let scan = scanner::scan_cached(root, &syntax, &mut cache)?;
+        cache.save(root)?;
+        scan
+    };
+    let report = || -> Result<Report> {
+        let today = chrono::Local::now().date_naive();
+        let report = Report::new(&scan.todos, &known_tissues(path, &config)?, today);
+        Ok(match source_url {
+            Some(source_url) => report.with_source_url(source_url),
+            None => report,
+        })
+    };
+    let rendered = match format {
+        Format::Text => {
+            let mut text = String::new();
+            for todo in &scan.todos {
+                text.push_str(&format!(
+                    "{}:{} {}: {}\n",
+                    todo.submission.file_path,
+                    todo.submission.line_number,
+                    todo.issue_type.as_str(),
+                    todo.description
+                ));
+            }
+            for error in &scan.errors {
+                text.push_str(&format!(
+                    "{}:{} error: {}\n",
+                    error.file_path, error.line_number, error.message
+                ));
+            }
+            text
+        }
+        Format::Sarif => serde_json::to_string_pretty(&sarif::to_sarif(&scan))? + "\n",
+        Format::Csv => report()?.to_csv(),
+        Format::Jsonl => report()?.to_json_lines(),
+        Format::Markdown => report()?.to_markdown(),
+        Format::Html => report()?.to_html(),
+    };
+    match output {
+        Some(output) => std::fs::write(output, rendered)?,
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+/// Walks every file under `root`, showing the walk's progress on stderr if it's a terminal.
+fn scan_all_files(root: &Path, syntax: &Syntax) -> ScanResult {
+    let walk = Walker::new(root).with_syntax(syntax.clone()).walk();
+    let progress = walk.progress();
+    let display = std::io::stderr().is_terminal().then(|| {
+        let progress = progress.clone();
+        std::thread::spawn(move || {
+            while !progress.is_finished() {
+                eprint!("\rWalking: {}", progress);
+                std::thread::sleep(Duration::from_millis(100));
+            }
+            eprintln!("\rWalked {}", progress);
+        })
+    });
+    let scan = scanner::scan_walk(root, walk, syntax);
+    if let Some(display) = display {
+        let _ = display.join();
+    }
+    scan
+}
+
+fn load_config(path: &str) -> Result<Config> {
+    config::load(Path::new(path))?.config()
+}
+
+/// The directory of the configured Tissue Box of the repository at `path`.
+fn box_path(path: &str) -> Result<String> {
+    Ok(load_config(path)?.box_path(Path::new(path)))
+}
+
+/// The tissues in the Tissue Box of the repository at `path`, or none if there is no Tissue Box
+/// yet.
+fn known_tissues(path: &str, config: &Config) -> Result<Vec<Tissue>> {
+    match TissueBoxFile::open(&config.box_path(Path::new(path))) {
+        Result::Ok(tissue_box) => tissue_box.get_tissues(),
+        Err(error) if matches!(error.downcast_ref(), Some(TissueBoxError::NotFound { .. })) => {
+            Ok(vec![])
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Checks against the configured policy, tightened by the policy given as `flags`.
+fn check(path: &str, flags: Policy, format: CheckFormat, output: Option<&str>) -> Result<()> {
+    let config = load_config(path)?;
+    let mut policy = config.policy();
+    policy.require_issue_type |= flags.require_issue_type;
+    policy.require_link |= flags.require_link;
+    policy.protected_paths.extend(flags.protected_paths);
+    policy.max_todos = flags.max_todos.or(policy.max_todos);
+    let scan = scanner::scan(Path::new(path), &config.syntax())?;
+    let tissues = known_tissues(path, &config)?;
+    let violations = policy::check(&policy, &scan, &tissues)?;
+    let rendered = match format {
+        CheckFormat::Text => violations.iter().map(|v| format!("{}\n", v)).collect(),
+        CheckFormat::Github => policy::github_annotations(&violations),
+        CheckFormat::Gitlab => {
+            serde_json::to_string_pretty(&policy::gitlab_code_quality(&violations))? + "\n"
+        }
+    };
+    match output {
+        Some(output) => std::fs::write(output, rendered)?,
+        None => print!("{}", rendered),
+    }
+    let exit_code = policy::exit_code(&violations);
+    if exit_code != 0 {
+        std::process::exit(exit_code);
+    }
+    Ok(())
+}
+
+fn diff(path: &str, range: &str, comment_on: Option<&str>) -> Result<()> {
+    let config = load_config(path)?;
+    let tissues = known_tissues(path, &config)?;
+    let todo_diff = todo_diff::diff_range(Path::new(path), range, &tissues, &config.syntax())?;
+    print!("{}", todo_diff.to_markdown());
+    let Some(target) = comment_on else {
+        return Ok(());
+    };
+    let (board_name, number) = target.rsplit_once('#').ok_or_else(|| {
+        color_eyre::Report::msg(format!(
+            "'{}' should name a board and a pull request, e.g. github:org/repo#42",
+            target
+        ))
+    })?;
+    let pull_request = IssueId::from(number);
+    tokio::runtime::Runtime::new()?.block_on(async {
+        let board = config.boards.connect(board_name).await?;
+        todo_diff.post(board.as_ref(), &pull_request).await
+    })
+}
+
+fn history(
+    path: &str,
+    sampling: &Sampling,
+    format: HistoryFormat,
+    output: Option<&str>,
+) -> Result<()> {
+    let config = load_config(path)?;
+    let points = history::history(Path::new(path), sampling, &config.syntax())?;
+    let rendered = match format {
+        HistoryFormat::Text => {
+            let totals: Vec<usize> = points.iter().map(|point| point.total).collect();
+            let mut text = String::new();
+            for point in &points {
+                let revision: String = point.revision.chars().take(12).collect();
+                text.push_str(&format!("{} {} {}\n", revision, point.date, point.total));
+            }
+            text.push_str(&format!("{}\n", history::sparkline(&totals)));
+            text
+        }
+        HistoryFormat::Csv => history::to_csv(&points),
+        HistoryFormat::Json => serde_json::to_string_pretty(&points)? + "\n",
+    };
+    match output {
+        Some(output) => std::fs::write(output, rendered)?,
+        None => print!("{}", rendered),
+    }
+    Ok(())
+}
+
+fn stale(path: &str, older_than: i64, fail: bool) -> Result<()> {
+    let config = load_config(path)?;
+    let scan = scanner::scan(Path::new(path), &config.syntax())?;
+    let rules = config.stale_rules(older_than);
+    let today = chrono::Local::now().date_naive();
+    let findings = stale::find_stale(&scan.todos, &rules, today);
+    let mut stale_count = 0;
+    for ((owner, directory), mut findings) in stale::group_by_owner(&findings) {
+        // A ToDo flagged by both rules is listed once.
+        findings.dedup_by(|a, b| a.todo == b.todo);
+        stale_count += findings.len();
+        println!("{} in {}:", owner, directory);
+        for finding in findings {
+            println!(
+                "  {}:{} {} ({})",
+                finding.todo.submission.file_path,
+                finding.todo.submission.line_number,
+                finding.todo.description,
+                staleness(finding)
+            );
+        }
+    }
+    if !findings.is_empty() && stale::escalates_on_boards(&rules) {
+        let mut tissue_box = TissueBoxFile::open(&config.box_path(Path::new(path)))?;
+        let runtime = tokio::runtime::Runtime::new()?;
+        let boards = runtime.block_on(connect_boards(path, &config))?;
+        let boards: Vec<&dyn IssueBoard> = boards.iter().map(|board| board.as_ref()).collect();
+        let report =
+            runtime.block_on(stale::escalate(&mut tissue_box, &boards, &rules, &findings))?;
+        for link in &report.labelled {
+            println!("Labelled {}", link);
+        }
+        for link in &report.commented {
+            println!("Commented on {}", link);
+        }
+        for todo in &report.unsynced {
+            eprintln!("'{}' has no issue to escalate on", todo.description);
+        }
+        for failure in &report.failed {
+            eprintln!(
+                "Couldn't escalate '{}' on {}: {}",
+                failure.todo.description, failure.board, failure.error
+            );
+        }
+    }
+    if fail && stale_count > 0 || stale::fails_ci(&rules, &findings) {
+        eprintln!("{} stale ToDo(s) found", stale_count);
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn sync_todos(path: &str) -> Result<()> {
+    let root = Path::new(path);
+    let config = load_config(path)?;
+    let scan = scanner::scan(root, &config.syntax())?;
+    let mut tissue_box = TissueBoxFile::open(&config.box_path(root))?;
+    let router = config.router(&tissue_box)?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let boards = runtime.block_on(connect_boards(path, &config))?;
+    let boards: Vec<&dyn IssueBoard> = boards.iter().map(|board| board.as_ref()).collect();
+    let report = runtime.block_on(sync::sync_with_template(
+        &mut tissue_box,
+        &boards,
+        &router,
+        &config.issue_template(),
+        &scan.todos,
+    ))?;
+    for (verb, tissues) in [
+        ("Filed", &report.created),
+        ("Updated", &report.moved),
+        ("Closed", &report.closed),
+    ] {
+        for tissue in tissues {
+            println!(
+                "{} '{}' ({})",
+                verb,
+                tissue.todo.description,
+                tissue.links_to_string()
+            );
+        }
+    }
+    for link in &report.mirrored {
+        println!("Mirrored {}", link);
+    }
+    for divergence in &report.diverged {
+        println!(
+            "{}#{} changed on the board: {}",
+            divergence.board,
+            divergence.issue,
+            divergence.differences.join(", ")
+        );
+    }
+    for todo in &report.unrouted {
+        eprintln!(
+            "No routing rule covers {}, so '{}' wasn't filed",
+            todo.submission.file_path, todo.description
+        );
+    }
+    for failure in &report.failed {
+        eprintln!(
+            "Couldn't sync '{}' with {}: {}",
+            failure.todo.description, failure.board, failure.error
+        );
+    }
+    if !report.failed.is_empty() {
+        return Err(color_eyre::Report::msg(format!(
+            "{} ToDo(s) failed to sync",
+            report.failed.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Prints the patch, and what changed on the boards to stderr.
+fn pull(path: &str, on_closed: ClosedPolicy, output: Option<&str>) -> Result<()> {
+    let root = Path::new(path);
+    let config = load_config(path)?;
+    let mut tissue_box = TissueBoxFile::open(&config.box_path(root))?;
+    let runtime = tokio::runtime::Runtime::new()?;
+    let boards = runtime.block_on(connect_boards(path, &config))?;
+    let boards: Vec<&dyn IssueBoard> = boards.iter().map(|board| board.as_ref()).collect();
+    let report = runtime.block_on(pull::pull(&mut tissue_box, &boards, root, on_closed))?;
+    for change in &report.changes {
+        let what = match &change.kind {
+            BoardChangeKind::Closed => String::from("closed"),
+            BoardChangeKind::Reassigned(Some(assignee)) => format!("assigned to {}", assignee),
+            BoardChangeKind::Reassigned(None) => String::from("unassigned"),
+        };
+        eprintln!(
+            "{}#{} {}: '{}'",
+            change.board, change.issue, what, change.todo.description
+        );
+    }
+    for change in &report.reopened {
+        eprintln!("Reopened {}#{}", change.board, change.issue);
+    }
+    for change in &report.unlocated {
+        eprintln!(
+            "Couldn't find '{}' in {} to edit",
+            change.todo.description, change.todo.submission.file_path
+        );
+    }
+    for failure in &report.failed {
+        eprintln!(
+            "Couldn't pull '{}' from {}: {}",
+            failure.todo.description, failure.board, failure.error
+        );
+    }
+    let patch = report.patch.render(root)?;
+    match output {
+        Some(output) => std::fs::write(output, patch)?,
+        None => print!("{}", patch),
+    }
+    Ok(())
+}
+
+fn lsp(path: &str) -> Result<()> {
+    let root = Path::new(path).canonicalize()?;
+    let config = load_config(path)?;
+    let boards = tokio::runtime::Runtime::new()?.block_on(connect_boards(path, &config))?;
+    let boards: Vec<&dyn IssueBoard> = boards.iter().map(|board| board.as_ref()).collect();
+    let (connection, io_threads) = lsp_server::Connection::stdio();
+    lsp::run(&connection, &root, &boards, &config)?;
+    drop(connection);
+    io_threads.join()?;
+    Ok(())
+}
+
+/// The boards of the Tissue Box of the repository at `path` that can be connected to, warning
+/// about the rest.
+async fn connect_boards(path: &str, config: &Config) -> Result<Vec<Box<dyn IssueBoard>>> {
+    let board_names = match TissueBoxFile::open(&config.box_path(Path::new(path))) {
+        Result::Ok(tissue_box) => tissue_box.get_boards()?,
+        Err(error) if matches!(error.downcast_ref(), Some(TissueBoxError::NotFound { .. })) => {
+            vec![]
+        }
+        Err(error) => return Err(error),
+    };
+    let mut boards = Vec::new();
+    for name in &board_names {
+        match config.boards.connect(name).await {
+            Result::Ok(board) => boards.push(board),
+            Err(error) => eprintln!("{}", error),
+        }
+    }
+    Ok(boards)
+}
+
+fn watch(path: &str, socket: Option<&str>, auto_sync: Option<u64>) -> Result<()> {
+    let root = Path::new(path).canonicalize()?;
+    let config = load_config(path)?;
+    let mut index = WatchIndex::new(&root, config.syntax())?;
+    println!(
+        "Watching {} ToDos in {}",
+        index.todos().count(),
+        root.display()
+    );
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)?;
+    watcher.watch(&root, RecursiveMode::Recursive)?;
+    let mut socket = match socket {
+        Some(socket) => Some(connect_socket(socket)?),
+        None => None,
+    };
+
+    let runtime = tokio::runtime::Runtime::new()?;
+    let boards = match auto_sync {
+        Some(_) => runtime.block_on(connect_boards(path, &config))?,
+        None => vec![],
+    };
+    let boards: Vec<&dyn IssueBoard> = boards.iter().map(|board| board.as_ref()).collect();
+    let mut offered = HashSet::new();
+    loop {
+        for event in index.next_changes(&receiver, Duration::from_secs(1))? {
+            println!("{}", event);
+            if let Some(socket) = socket.as_mut() {
+                writeln!(socket, "{}", serde_json::to_string(&event)?)?;
+            }
+        }
+        let Some(stable_for) = auto_sync else {
+            continue;
+        };
+        // Each stable ToDo is offered to the boards until it's filed or needn't be.
+        let stable: Vec<ToDo> = index
+            .stable(Duration::from_secs(stable_for), Instant::now())
+            .into_iter()
+            .filter(|todo| !offered.contains(&todo.fingerprint()))
+            .cloned()
+            .collect();
+        if stable.is_empty() {
+            continue;
+        }
+        let report = match file_stable(&root, &config, &runtime, &boards, &stable) {
+            Result::Ok(report) => report,
+            Err(e) => {
+                eprintln!("Couldn't file new ToDos, will retry: {}", e);
+                continue;
+            }
+        };
+        for tissue in &report.created {
+            println!(
+                "Filed '{}' as {}",
+                tissue.todo.description,
+                tissue.links_to_string()
+            );
+        }
+        for failure in &report.failed {
+            eprintln!(
+                "Couldn't file '{}' on {}: {}",
+                failure.todo.description, failure.board, failure.error
+            );
+        }
+        let created: HashSet<String> = report
+            .created
+            .iter()
+            .map(|tissue| tissue.todo.fingerprint())
+            .collect();
+        let failed: HashSet<String> = report
+            .failed
+            .iter()
+            .map(|failure| failure.todo.fingerprint())
+            .filter(|fingerprint| !created.contains(fingerprint))
+            .collect();
+        offered.extend(
+            stable
+                .iter()
+                .map(ToDo::fingerprint)
+                .filter(|fingerprint| !failed.contains(fingerprint)),
+        );
+    }
+}
+
+/// Files the `stable` ToDos the Tissue Box doesn't know yet on the boards they route to.
+fn file_stable(
+    root: &Path,
+    config: &Config,
+    runtime: &tokio::runtime::Runtime,
+    boards: &[&dyn IssueBoard],
+    stable: &[ToDo],
+) -> Result<sync::SyncReport> {
+    let mut tissue_box = TissueBoxFile::open(&config.box_path(root))?;
+    let router = config.router(&tissue_box)?;
+    runtime.block_on(sync::file_new(
+        &mut tissue_box,
+        boards,
+        &router,
+        &config.issue_template(),
+        stable,
+    ))
+}
+
+#[cfg(unix)]
+fn connect_socket(path: &str) -> Result<std::os::unix::net::UnixStream> {
+    std::os::unix::net::UnixStream::connect(path)
+        .map_err(|e| color_eyre::Report::msg(format!("Couldn't connect to {}: {}", path, e)))
+}
+
+#[cfg(not(unix))]
+fn connect_socket(path: &str) -> Result<std::fs::File> {
+    Err(color_eyre::Report::msg(format!(
+        "Can't push to {}: sockets are only supported on Unix",
+        path
+    )))
+}
+
+fn staleness(finding: &StaleFinding) -> String {
+    match (finding.overdue_days, finding.age_days) {
+        (Some(days), _) if days > 0 => format!("{} days overdue", days),
+        (_, Some(days)) => format!("{} days old", days),
+        _ => finding.rule.clone(),
+    }
+}
+
+fn merge_driver(base: &str, ours: &str, theirs: &str) -> Result<()> {
+    let conflicts = tissuebox_merge::merge_files(base, ours, theirs)?;
+    if conflicts.is_empty() {
+        return Ok(());
+    }
+    eprintln!(
+        "Tissue Box merge kept our side of {} conflict(s):",
+        conflicts.len()
+    );
+    for conflict in &conflicts {
+        eprintln!("  - {}", conflict);
+    }
+    std::process::exit(1);
+}
+
+fn migrate(path: &str) -> Result<()> {
+    let report = TissueBoxFile::migrate(path)?;
+    if report.is_empty() {
+        println!(
+            "Tissue Box is already at schema version {}",
+            report.to_version
+        );
+        return Ok(());
+    }
+    println!(
+        "Migrated Tissue Box from schema version {} to {}",
+        report.from_version, report.to_version
+    );
+    for change in &report.changes {
+        println!("  - {}", change);
+    }
     Ok(())
 }
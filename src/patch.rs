@@ -0,0 +1,232 @@
+//! Unified diffs of line edits to source files, for changes tissue proposes rather than makes.
+use std::{collections::BTreeMap, fs, path::Path};
+
+use color_eyre::eyre::Result;
+
+const CONTEXT_LINES: usize = 3;
+
+/// Replaces line `line_number` (1-based) with `replacement`, or deletes it if that is `None`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineEdit {
+    pub line_number: usize,
+    pub replacement: Option<String>,
+}
+
+/// Line edits grouped by file, rendered as a patch `git apply` accepts.
+#[derive(Debug, Default)]
+pub struct Patch {
+    edits: BTreeMap<String, Vec<LineEdit>>,
+}
+
+impl Patch {
+    pub fn new() -> Patch {
+        Patch::default()
+    }
+
+    /// Adds an edit to `file_path`. A later edit of the same line replaces the earlier one.
+    pub fn edit(&mut self, file_path: &str, edit: LineEdit) {
+        let file_path = file_path.strip_prefix("./").unwrap_or(file_path);
+        let edits = self.edits.entry(file_path.to_string()).or_default();
+        edits.retain(|e| e.line_number != edit.line_number);
+        edits.push(edit);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Renders the patch against the files under `root`.
+    pub fn render(&self, root: &Path) -> Result<String> {
+        let mut patch = String::new();
+        for (file_path, edits) in &self.edits {
+            let original = fs::read_to_string(root.join(file_path))?;
+            patch.push_str(&diff_file(file_path, &original, edits));
+        }
+        Ok(patch)
+    }
+}
+
+/// Writes `line` to a hunk body with `prefix`, marking a last line without a line ending the way
+/// `git apply` expects.
+fn push_line(body: &mut String, prefix: char, line: &str) {
+    body.push(prefix);
+    body.push_str(line);
+    if !line.ends_with('\n') {
+        body.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+fn diff_file(file_path: &str, original: &str, edits: &[LineEdit]) -> String {
+    // Lines keep their endings, so CRLF files and files without a final newline diff as they are.
+    let lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let mut edits: Vec<&LineEdit> = edits
+        .iter()
+        .filter(|edit| edit.line_number >= 1 && edit.line_number <= lines.len())
+        .collect();
+    edits.sort_by_key(|edit| edit.line_number);
+
+    // Group edits whose context overlaps into one hunk.
+    let mut hunks: Vec<(usize, usize, Vec<&LineEdit>)> = Vec::new();
+    for edit in edits {
+        let start = edit.line_number.saturating_sub(CONTEXT_LINES).max(1);
+        let end = (edit.line_number + CONTEXT_LINES).min(lines.len());
+        match hunks.last_mut() {
+            Some((_, last_end, hunk_edits)) if start <= *last_end + 1 => {
+                *last_end = end;
+                hunk_edits.push(edit);
+            }
+            _ => hunks.push((start, end, vec![edit])),
+        }
+    }
+    if hunks.is_empty() {
+        return String::new();
+    }
+
+    let mut diff = format!("--- a/{}\n+++ b/{}\n", file_path, file_path);
+    let mut offset: isize = 0;
+    for (start, end, hunk_edits) in hunks {
+        let mut body = String::new();
+        let mut deleted = 0;
+        for line_number in start..=end {
+            let line = lines[line_number - 1];
+            match hunk_edits.iter().find(|e| e.line_number == line_number) {
+                Some(edit) => {
+                    push_line(&mut body, '-', line);
+                    match &edit.replacement {
+                        Some(replacement) => {
+                            let ending = &line[line.trim_end_matches(['\r', '\n']).len()..];
+                            push_line(&mut body, '+', &format!("{}{}", replacement, ending));
+                        }
+                        None => deleted += 1,
+                    }
+                }
+                None => push_line(&mut body, ' ', line),
+            }
+        }
+        let old_len = end - start + 1;
+        let new_len = old_len - deleted;
+        let mut new_start = start as isize + offset;
+        if new_len == 0 {
+            // An empty range is numbered after the line it follows.
+            new_start -= 1;
+        }
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n{}",
+            start, old_len, new_start, new_len, body
+        ));
+        offset -= deleted as isize;
+    }
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_replacements_and_deletions_as_hunks() {
+        let original = (1..=12)
+            .map(|n| format!("line {}", n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let edits = vec![
+            LineEdit {
+                line_number: 2,
+                replacement: None,
+            },
+            LineEdit {
+                line_number: 11,
+                replacement: Some("line eleven".to_string()),
+            },
+        ];
+
+        let diff = diff_file("src/lib.rs", &original, &edits);
+
+        assert_eq!(
+            diff,
+            "--- a/src/lib.rs\n+++ b/src/lib.rs\n\
+             @@ -1,5 +1,4 @@\n line 1\n-line 2\n line 3\n line 4\n line 5\n\
+             @@ -8,5 +7,5 @@\n line 8\n line 9\n line 10\n-line 11\n+line eleven\n line 12\n\
+             \\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn keeps_line_endings() {
+        let diff = diff_file(
+            "main.rs",
+            "fn main() {\r\n    // TODO bug: a\r\n}\r\n",
+            &[LineEdit {
+                line_number: 2,
+                replacement: Some("    // TODO bug: b".to_string()),
+            }],
+        );
+        assert!(diff.contains("\n-    // TODO bug: a\r\n+    // TODO bug: b\r\n }\r\n"));
+    }
+
+    #[test]
+    fn git_applies_the_patches() {
+        let directory = tempfile::tempdir().unwrap();
+        let twelve_lines = (1..=12)
+            .map(|n| format!("line {}", n))
+            .collect::<Vec<_>>()
+            .join("\n");
+        fs::write(directory.path().join("lib.rs"), twelve_lines).unwrap();
+        fs::write(
+            directory.path().join("crlf.rs"),
+            "fn main() {\r\n    // TODO bug: a\r\n}\r\n",
+        )
+        .unwrap();
+        fs::write(
+            directory.path().join("last.rs"),
+            "fn main() {}\n// TODO bug: a",
+        )
+        .unwrap();
+        let edit = |line_number, replacement: Option<&str>| LineEdit {
+            line_number,
+            replacement: replacement.map(String::from),
+        };
+        let mut patch = Patch::new();
+        patch.edit("lib.rs", edit(2, None));
+        patch.edit("lib.rs", edit(11, Some("line eleven")));
+        patch.edit("crlf.rs", edit(2, Some("    // TODO bug: b")));
+        patch.edit("last.rs", edit(2, None));
+        fs::write(
+            directory.path().join("tissue.patch"),
+            patch.render(directory.path()).unwrap(),
+        )
+        .unwrap();
+
+        let output = std::process::Command::new("git")
+            .args(["apply", "--check", "tissue.patch"])
+            .current_dir(directory.path())
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    #[test]
+    fn renders_against_files_on_disk() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut patch = Patch::new();
+        assert!(patch.is_empty());
+        assert_eq!(patch.render(directory.path()).unwrap(), "");
+
+        fs::write(directory.path().join("main.rs"), "// TODO bug: a\n").unwrap();
+        patch.edit(
+            "./main.rs",
+            LineEdit {
+                line_number: 1,
+                replacement: None,
+            },
+        );
+        assert_eq!(
+            patch.render(directory.path()).unwrap(),
+            "--- a/main.rs\n+++ b/main.rs\n@@ -1,1 +0,0 @@\n-// TODO bug: a\n"
+        );
+    }
+}
@@ -0,0 +1,343 @@
+//! Policy checks for CI, e.g. every ToDo must have an issue type or a linked issue. Each class
+//! of violation has its own exit code, so a pipeline can tell them apart.
+use std::{collections::HashMap, fmt};
+
+use color_eyre::eyre::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use serde_json::{json, Value};
+
+use crate::{
+    scanner::ScanResult,
+    tissuebox_repository::Tissue,
+    todofinder::{fnv1a, IssueType},
+};
+
+/// A class of violation. Earlier classes take precedence for the exit code.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum PolicyKind {
+    /// A ToDo without a known issue type, or one that doesn't parse.
+    Untyped,
+    /// A ToDo with no issue on any board.
+    Unlinked,
+    /// A ToDo added to a protected path since the Tissue Box last saw it.
+    Protected,
+    /// More open ToDos than allowed.
+    TooMany,
+}
+
+impl PolicyKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            PolicyKind::Untyped => "untyped-todo",
+            PolicyKind::Unlinked => "unlinked-todo",
+            PolicyKind::Protected => "protected-path",
+            PolicyKind::TooMany => "too-many-todos",
+        }
+    }
+
+    /// 1 is left for errors that stop the check from running at all.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            PolicyKind::Untyped => 2,
+            PolicyKind::Unlinked => 3,
+            PolicyKind::Protected => 4,
+            PolicyKind::TooMany => 5,
+        }
+    }
+}
+
+/// What `check` enforces. Everything is off by default.
+#[derive(Clone, Debug, Default)]
+pub struct Policy {
+    pub require_issue_type: bool,
+    pub require_link: bool,
+    /// Globs of paths no new ToDo may be added to.
+    pub protected_paths: Vec<String>,
+    pub max_todos: Option<usize>,
+}
+
+impl Policy {
+    pub fn with_required_issue_type(mut self) -> Policy {
+        self.require_issue_type = true;
+        self
+    }
+
+    pub fn with_required_link(mut self) -> Policy {
+        self.require_link = true;
+        self
+    }
+
+    pub fn with_protected_path(mut self, pattern: &str) -> Policy {
+        self.protected_paths.push(pattern.to_string());
+        self
+    }
+
+    pub fn with_max_todos(mut self, max_todos: usize) -> Policy {
+        self.max_todos = Some(max_todos);
+        self
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violation {
+    pub kind: PolicyKind,
+    /// Where the violation is; `None` for violations of the whole repository.
+    pub file_path: Option<String>,
+    pub line_number: usize,
+    pub message: String,
+}
+
+impl Violation {
+    /// Stable across runs as long as the offending line keeps its text, for code-quality reports.
+    pub fn fingerprint(&self) -> String {
+        let key = format!(
+            "{}\0{}\0{}",
+            self.kind.as_str(),
+            self.file_path.as_deref().unwrap_or_default(),
+            self.message
+        );
+        format!("{:016x}", fnv1a(key.as_bytes()))
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.file_path {
+            Some(file_path) => write!(
+                f,
+                "{}:{} {}: {}",
+                file_path,
+                self.line_number,
+                self.kind.as_str(),
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.kind.as_str(), self.message),
+        }
+    }
+}
+
+/// Checks the scanned ToDos against `policy`, using the tissues in the Tissue Box to tell which
+/// are linked and which are new.
+pub fn check(policy: &Policy, scan: &ScanResult, tissues: &[Tissue]) -> Result<Vec<Violation>> {
+    let protected = protected_paths(&policy.protected_paths)?;
+    let known: HashMap<String, &Tissue> = tissues
+        .iter()
+        .map(|tissue| (tissue.todo.fingerprint(), tissue))
+        .collect();
+    let mut violations = Vec::new();
+
+    if policy.require_issue_type {
+        for error in &scan.errors {
+            violations.push(Violation {
+                kind: PolicyKind::Untyped,
+                file_path: Some(error.file_path.clone()),
+                line_number: error.line_number,
+                message: error.message.clone(),
+            });
+        }
+    }
+    for todo in &scan.todos {
+        let violation = |kind, message: String| Violation {
+            kind,
+            file_path: Some(todo.submission.file_path.clone()),
+            line_number: todo.submission.line_number,
+            message,
+        };
+        if policy.require_issue_type && todo.issue_type == IssueType::Other {
+            violations.push(violation(
+                PolicyKind::Untyped,
+                format!(
+                    "'{}' should be a bug, feature or improvement",
+                    todo.description
+                ),
+            ));
+        }
+        let tissue = known.get(&todo.fingerprint());
+        if policy.require_link && tissue.is_none_or(|tissue| tissue.links.is_empty()) {
+            violations.push(violation(
+                PolicyKind::Unlinked,
+                format!("'{}' has no issue on any board", todo.description),
+            ));
+        }
+        let file_path = todo.submission.file_path.as_str();
+        let file_path = file_path.strip_prefix("./").unwrap_or(file_path);
+        if tissue.is_none() && protected.is_match(file_path) {
+            violations.push(violation(
+                PolicyKind::Protected,
+                format!("'{}' is a new ToDo in a protected path", todo.description),
+            ));
+        }
+    }
+    if let Some(max_todos) = policy.max_todos {
+        if scan.todos.len() > max_todos {
+            violations.push(Violation {
+                kind: PolicyKind::TooMany,
+                file_path: None,
+                line_number: 0,
+                message: format!(
+                    "{} open ToDos, more than the {} allowed",
+                    scan.todos.len(),
+                    max_todos
+                ),
+            });
+        }
+    }
+    Ok(violations)
+}
+
+fn protected_paths(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns {
+        builder.add(Glob::new(pattern).map_err(|e| {
+            color_eyre::Report::msg(format!("Invalid protected path '{}': {}", pattern, e))
+        })?);
+    }
+    builder
+        .build()
+        .map_err(|e| color_eyre::Report::msg(format!("Invalid protected paths: {}", e)))
+}
+
+/// The exit code for `violations`: 0 if there are none, otherwise that of the first class found.
+pub fn exit_code(violations: &[Violation]) -> i32 {
+    violations
+        .iter()
+        .map(|violation| violation.kind)
+        .min()
+        .map_or(0, |kind| kind.exit_code())
+}
+
+/// Workflow commands GitHub Actions shows as annotations on the changed files.
+pub fn github_annotations(violations: &[Violation]) -> String {
+    let mut annotations = String::new();
+    for violation in violations {
+        let location = match &violation.file_path {
+            Some(file_path) => format!("file={},line={},", file_path, violation.line_number),
+            None => String::new(),
+        };
+        annotations.push_str(&format!(
+            "::error {}title={}::{}\n",
+            location,
+            violation.kind.as_str(),
+            escape_annotation(&violation.message)
+        ));
+    }
+    annotations
+}
+
+fn escape_annotation(message: &str) -> String {
+    message
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// A GitLab code-quality report, to upload as the `codequality` artifact.
+pub fn gitlab_code_quality(violations: &[Violation]) -> Value {
+    let issues: Vec<Value> = violations
+        .iter()
+        .map(|violation| {
+            json!({
+                "description": violation.message,
+                "check_name": violation.kind.as_str(),
+                "fingerprint": violation.fingerprint(),
+                "severity": "major",
+                "location": {
+                    "path": violation.file_path.as_deref().unwrap_or("."),
+                    "lines": { "begin": violation.line_number.max(1) },
+                },
+            })
+        })
+        .collect();
+    Value::Array(issues)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        scanner::ParseError,
+        test_fixtures::{parsed_todo, FIXTURE_DATE},
+    };
+
+    fn scan() -> ScanResult {
+        ScanResult {
+            todos: vec![
+                parsed_todo("//TODO bug: parse flags", "src/main.rs", 3, FIXTURE_DATE),
+                parsed_todo("//TODO later: tidy up", "src/lib.rs", 3, FIXTURE_DATE),
+                parsed_todo(
+                    "//TODO feature: retry payments",
+                    "services/billing/pay.rs",
+                    3,
+                    FIXTURE_DATE,
+                ),
+            ],
+            errors: vec![ParseError {
+                file_path: "src/lib.rs".to_string(),
+                line_number: 9,
+                line: "// todo".to_string(),
+                message: "No issue type found".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn reports_each_class_of_violation() {
+        let scan = scan();
+        let known = Tissue::new(scan.todos[0].clone());
+        let policy = Policy::default()
+            .with_required_issue_type()
+            .with_required_link()
+            .with_protected_path("services/billing/**")
+            .with_max_todos(2);
+
+        let violations = check(&policy, &scan, &[known]).unwrap();
+        let kinds: Vec<PolicyKind> = violations.iter().map(|v| v.kind).collect();
+
+        assert_eq!(
+            kinds,
+            [
+                PolicyKind::Untyped,
+                PolicyKind::Unlinked,
+                PolicyKind::Untyped,
+                PolicyKind::Unlinked,
+                PolicyKind::Unlinked,
+                PolicyKind::Protected,
+                PolicyKind::TooMany,
+            ]
+        );
+        assert_eq!(exit_code(&violations), 2);
+        assert_eq!(exit_code(&violations[5..]), 4);
+        assert_eq!(exit_code(&[]), 0);
+        assert!(check(&Policy::default(), &scan, &[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn renders_annotations() {
+        let violations = vec![
+            Violation {
+                kind: PolicyKind::Unlinked,
+                file_path: Some("src/main.rs".to_string()),
+                line_number: 3,
+                message: "100% unlinked\nreally".to_string(),
+            },
+            Violation {
+                kind: PolicyKind::TooMany,
+                file_path: None,
+                line_number: 0,
+                message: "3 open ToDos".to_string(),
+            },
+        ];
+
+        assert_eq!(
+            github_annotations(&violations),
+            "::error file=src/main.rs,line=3,title=unlinked-todo::100%25 unlinked%0Areally\n\
+             ::error title=too-many-todos::3 open ToDos\n"
+        );
+        let report = gitlab_code_quality(&violations);
+        assert_eq!(report[0]["check_name"], "unlinked-todo");
+        assert_eq!(report[0]["location"]["path"], "src/main.rs");
+        assert_eq!(report[0]["location"]["lines"]["begin"], 3);
+        assert_eq!(report[1]["location"]["lines"]["begin"], 1);
+        assert_eq!(report[0]["fingerprint"], violations[0].fingerprint());
+    }
+}
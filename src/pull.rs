@@ -0,0 +1,369 @@
+//! The board-to-code half of sync: notices issues closed or reassigned on their board and
+//! proposes matching changes to the code as a patch, for someone to review and apply.
+use std::{collections::HashMap, fs, path::Path};
+
+use color_eyre::eyre::Result;
+
+use crate::{
+    issue_repository::{IssueBoard, IssueId, IssueState, IssueUpdateRequest},
+    patch::{LineEdit, Patch},
+    sync::SyncFailure,
+    tissuebox_repository::TissueBoxRepository,
+    todofinder::{self, ToDo},
+};
+
+/// What to do about an issue closed on its board while its ToDo is still in the code.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ClosedPolicy {
+    /// Reopen the issue; the code is the source of truth.
+    Reopen,
+    /// Propose deleting the ToDo comment in the patch.
+    DeleteToDo,
+    /// Only report it.
+    #[default]
+    Flag,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardChangeKind {
+    Closed,
+    /// Assigned to someone else, or to nobody.
+    Reassigned(Option<String>),
+}
+
+/// A change made on a board to an issue tissue filed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct BoardChange {
+    pub board: String,
+    pub issue: IssueId,
+    pub todo: ToDo,
+    pub kind: BoardChangeKind,
+}
+
+#[derive(Debug, Default)]
+pub struct PullReport {
+    pub changes: Vec<BoardChange>,
+    pub reopened: Vec<BoardChange>,
+    /// Closed issues left for someone to decide about.
+    pub flagged: Vec<BoardChange>,
+    /// Proposed edits to the ToDo comments. Render it with `Patch::render`.
+    pub patch: Patch,
+    /// Changes that called for an edit, but whose ToDo comment is no longer in its file as it
+    /// was synced. No edit is proposed for them.
+    pub unlocated: Vec<BoardChange>,
+    pub failed: Vec<SyncFailure>,
+}
+
+/// Fetches every linked issue from its board and applies `on_closed` to the ones closed there.
+/// Reassignments become edits of the `assigned` part of the ToDo comment in the patch, which is
+/// made against the files under `root`.
+pub async fn pull(
+    tissue_box: &mut dyn TissueBoxRepository,
+    boards: &[&dyn IssueBoard],
+    root: &Path,
+    on_closed: ClosedPolicy,
+) -> Result<PullReport> {
+    let mut report = PullReport::default();
+    let mut upserts = Vec::new();
+    let mut files = HashMap::new();
+
+    for mut tissue in tissue_box.get_tissues()? {
+        let mut changed = false;
+        for link in &mut tissue.links {
+            let Some(id) = link.id().cloned() else {
+                continue;
+            };
+            let Some(board) = boards
+                .iter()
+                .find(|board| board.get_repository_name() == link.board)
+            else {
+                continue;
+            };
+            let current = match board.get_issue(&id).await {
+                Ok(issue) => issue,
+                Err(e) => {
+                    report.failed.push(SyncFailure {
+                        board: link.board.clone(),
+                        todo: tissue.todo.clone(),
+                        error: e.to_string(),
+                    });
+                    continue;
+                }
+            };
+            let change = |kind| BoardChange {
+                board: link.board.clone(),
+                issue: id.clone(),
+                todo: tissue.todo.clone(),
+                kind,
+            };
+
+            let assignee = current.assignee.as_ref().map(|user| user.name.clone());
+            let recorded = link.issue.assignee.as_ref().map(|user| user.name.clone());
+            if assignee != recorded && assignee != tissue.todo.assigned {
+                let change = change(BoardChangeKind::Reassigned(assignee.clone()));
+                match locate(root, &mut files, &tissue.todo) {
+                    Some((line_number, line)) => report.patch.edit(
+                        &tissue.todo.submission.file_path,
+                        LineEdit {
+                            line_number,
+                            replacement: Some(todofinder::with_assignee(
+                                &line,
+                                assignee.as_deref(),
+                            )),
+                        },
+                    ),
+                    None => report.unlocated.push(change.clone()),
+                }
+                report.changes.push(change);
+                // Reported once; the patch carries it from here.
+                link.issue.assignee = current.assignee.clone();
+                changed = true;
+            }
+
+            let closed = current.state() == IssueState::Closed;
+            if !closed || link.issue.state() == IssueState::Closed {
+                continue;
+            }
+            let change = change(BoardChangeKind::Closed);
+            report.changes.push(change.clone());
+            match on_closed {
+                ClosedPolicy::Reopen => {
+                    let reopen = IssueUpdateRequest::State(IssueState::Reopened);
+                    match board.update_issue(&id, &reopen).await {
+                        Ok(()) => {
+                            link.issue = current;
+                            link.issue.apply(&reopen);
+                            changed = true;
+                            report.reopened.push(change);
+                        }
+                        Err(e) => report.failed.push(SyncFailure {
+                            board: link.board.clone(),
+                            todo: tissue.todo.clone(),
+                            error: e.to_string(),
+                        }),
+                    }
+                }
+                ClosedPolicy::DeleteToDo => match locate(root, &mut files, &tissue.todo) {
+                    Some((line_number, _)) => report.patch.edit(
+                        &tissue.todo.submission.file_path,
+                        LineEdit {
+                            line_number,
+                            replacement: None,
+                        },
+                    ),
+                    None => report.unlocated.push(change),
+                },
+                ClosedPolicy::Flag => report.flagged.push(change),
+            }
+        }
+        if changed {
+            upserts.push(tissue);
+        }
+    }
+
+    if !upserts.is_empty() {
+        tissue_box.upsert_tissues(upserts)?;
+    }
+    Ok(report)
+}
+
+/// Finds the comment of `todo` in its file by content, since code may have moved since the last
+/// sync, preferring the match nearest the line it was synced at. Returns the line number and the
+/// line as it is, indentation included. `files` caches the files read so far.
+fn locate(
+    root: &Path,
+    files: &mut HashMap<String, Vec<String>>,
+    todo: &ToDo,
+) -> Option<(usize, String)> {
+    let file_path = &todo.submission.file_path;
+    let lines = files.entry(file_path.clone()).or_insert_with(|| {
+        fs::read_to_string(root.join(file_path))
+            .map(|source| source.lines().map(String::from).collect())
+            .unwrap_or_default()
+    });
+    let wanted = todo.submission.line.trim();
+    lines
+        .iter()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line))
+        .filter(|(_, line)| line.trim() == wanted)
+        .min_by_key(|(line_number, _)| line_number.abs_diff(todo.submission.line_number))
+        .map(|(line_number, line)| (line_number, line.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+
+    use super::*;
+    use crate::{
+        issue_repository::User,
+        routing::Router,
+        sync::sync,
+        test_fixtures::{parsed_todo, FIXTURE_DATE},
+        testing::{InMemoryBoard, InMemoryTissueBox},
+    };
+
+    const SOURCE: &str = "fn main() {\n    //TODO bug: parse flags; assigned OthelloEngineer\n}\n";
+
+    async fn synced(root: &Path, board: &InMemoryBoard) -> InMemoryTissueBox {
+        fs::write(root.join("main.rs"), SOURCE).unwrap();
+        let line = SOURCE.lines().nth(1).unwrap().trim();
+        let todo = parsed_todo(line, "main.rs", 2, FIXTURE_DATE);
+        let mut tissue_box = InMemoryTissueBox::new();
+        sync(
+            &mut tissue_box,
+            &[board],
+            &Router::single("memory"),
+            &[todo],
+        )
+        .await
+        .unwrap();
+        tissue_box
+    }
+
+    #[tokio::test]
+    async fn proposes_new_assignees_as_a_patch() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = InMemoryBoard::new("memory");
+        let mut tissue_box = synced(directory.path(), &board).await;
+        board
+            .update_issue(
+                &IssueId::Number(1),
+                &IssueUpdateRequest::Assignee(User {
+                    name: "jolee18".to_string(),
+                }),
+            )
+            .await
+            .unwrap();
+
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Flag,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(
+            report.changes[0].kind,
+            BoardChangeKind::Reassigned(Some("jolee18".to_string()))
+        );
+        let patch = report.patch.render(directory.path()).unwrap();
+        assert!(patch.contains("-    //TODO bug: parse flags; assigned OthelloEngineer\n"));
+        assert!(patch.contains("+    //TODO bug: parse flags; assigned jolee18\n"));
+
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Flag,
+        )
+        .await
+        .unwrap();
+        assert!(report.changes.is_empty());
+    }
+
+    #[tokio::test]
+    async fn finds_moved_todos_and_reports_missing_ones() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = InMemoryBoard::new("memory");
+        let mut tissue_box = synced(directory.path(), &board).await;
+        let reassign = |name: &str| {
+            IssueUpdateRequest::Assignee(User {
+                name: name.to_string(),
+            })
+        };
+        board
+            .update_issue(&IssueId::Number(1), &reassign("jolee18"))
+            .await
+            .unwrap();
+        let moved = format!("use std::env;\n\n{}", SOURCE);
+        fs::write(directory.path().join("main.rs"), &moved).unwrap();
+
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Flag,
+        )
+        .await
+        .unwrap();
+        let patch = report.patch.render(directory.path()).unwrap();
+        assert!(patch.contains("-    //TODO bug: parse flags; assigned OthelloEngineer\n"));
+        assert!(patch.contains("+    //TODO bug: parse flags; assigned jolee18\n"));
+        assert!(!patch.contains("-use std::env;"));
+
+        fs::write(directory.path().join("main.rs"), "fn main() {}\n").unwrap();
+        board
+            .update_issue(&IssueId::Number(1), &reassign("carol"))
+            .await
+            .unwrap();
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Flag,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.unlocated.len(), 1);
+        assert!(report.patch.is_empty());
+    }
+
+    #[tokio::test]
+    async fn applies_the_closed_policy() {
+        let directory = tempfile::tempdir().unwrap();
+        let board = InMemoryBoard::new("memory");
+        let mut tissue_box = synced(directory.path(), &board).await;
+        let close = IssueUpdateRequest::State(IssueState::Closed);
+        board
+            .update_issue(&IssueId::Number(1), &close)
+            .await
+            .unwrap();
+
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Flag,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.flagged.len(), 1);
+        assert!(report.patch.is_empty());
+
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::DeleteToDo,
+        )
+        .await
+        .unwrap();
+        let patch = report.patch.render(directory.path()).unwrap();
+        assert!(patch.contains("-    //TODO bug: parse flags; assigned OthelloEngineer\n"));
+        assert!(!patch.contains("+    //TODO"));
+
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Reopen,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.reopened.len(), 1);
+        assert_eq!(board.issues()[0].state, "reopened");
+        let report = pull(
+            &mut tissue_box,
+            &[&board],
+            directory.path(),
+            ClosedPolicy::Reopen,
+        )
+        .await
+        .unwrap();
+        assert!(report.changes.is_empty());
+    }
+}
@@ -0,0 +1,382 @@
+//! Inventory of every ToDo and the issues filed for it: CSV and JSON Lines for spreadsheets and
+//! scripts, Markdown and standalone HTML reports for publishing as a CI artifact.
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::NaiveDate;
+use serde::Serialize;
+
+use crate::{
+    stale,
+    tissuebox_repository::{BoardLink, Tissue},
+    todofinder::ToDo,
+};
+
+/// One ToDo in the inventory.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct ReportRow {
+    pub file: String,
+    pub line: usize,
+    #[serde(rename = "type")]
+    pub issue_type: String,
+    pub description: String,
+    pub assigned: Option<String>,
+    pub author: String,
+    pub date: String,
+    pub age_days: Option<i64>,
+    pub fingerprint: String,
+    /// `board#id` of each linked issue.
+    pub issues: Vec<String>,
+    #[serde(skip)]
+    links: Vec<BoardLink>,
+}
+
+/// The rows of an inventory, and where to link source files to.
+pub struct Report {
+    pub rows: Vec<ReportRow>,
+    /// Prefix of links to source files, e.g. `https://github.com/org/repo/blob/main`.
+    pub source_url: Option<String>,
+}
+
+impl Report {
+    /// An inventory of `todos`, with the issues the Tissue Box has for each. Ages are counted up
+    /// to `today`.
+    pub fn new(todos: &[ToDo], tissues: &[Tissue], today: NaiveDate) -> Report {
+        let mut rows: Vec<ReportRow> = todos
+            .iter()
+            .map(|todo| {
+                let fingerprint = todo.fingerprint();
+                let links = tissues
+                    .iter()
+                    .find(|tissue| tissue.todo.fingerprint() == fingerprint)
+                    .map(|tissue| tissue.links.clone())
+                    .unwrap_or_default();
+                let file = todo.submission.file_path.as_str();
+                ReportRow {
+                    file: file.strip_prefix("./").unwrap_or(file).to_string(),
+                    line: todo.submission.line_number,
+                    issue_type: todo.issue_type.as_str().to_string(),
+                    description: todo.description.clone(),
+                    assigned: todo.assigned.clone(),
+                    author: todo.submission.issuer.clone(),
+                    date: todo.submission.date.clone(),
+                    age_days: stale::age_days(todo, today),
+                    fingerprint,
+                    issues: links.iter().map(BoardLink::to_string).collect(),
+                    links,
+                }
+            })
+            .collect();
+        rows.sort_by(|a, b| (&a.file, a.line).cmp(&(&b.file, b.line)));
+        Report {
+            rows,
+            source_url: None,
+        }
+    }
+
+    pub fn with_source_url(mut self, source_url: &str) -> Report {
+        self.source_url = Some(source_url.trim_end_matches('/').to_string());
+        self
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "file,line,type,description,assigned,author,date,age_days,fingerprint,issues\n",
+        );
+        for row in &self.rows {
+            let fields = [
+                row.file.clone(),
+                row.line.to_string(),
+                row.issue_type.clone(),
+                row.description.clone(),
+                row.assigned.clone().unwrap_or_default(),
+                row.author.clone(),
+                row.date.clone(),
+                row.age_days
+                    .map(|days| days.to_string())
+                    .unwrap_or_default(),
+                row.fingerprint.clone(),
+                row.issues.join(" "),
+            ];
+            let fields: Vec<String> = fields.iter().map(|field| csv_field(field)).collect();
+            csv.push_str(&fields.join(","));
+            csv.push('\n');
+        }
+        csv
+    }
+
+    pub fn to_json_lines(&self) -> String {
+        self.rows
+            .iter()
+            .map(|row| serde_json::to_string(row).unwrap() + "\n")
+            .collect()
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = format!("# ToDo inventory\n\n{} ToDos.\n", self.rows.len());
+        for (title, counts) in self.summaries() {
+            markdown.push_str(&format!(
+                "\n## By {}\n\n| {} | ToDos |\n| --- | ---: |\n",
+                title, title
+            ));
+            for (group, count) in counts {
+                markdown.push_str(&format!("| {} | {} |\n", markdown_text(&group), count));
+            }
+        }
+        for (directory, rows) in self.by_directory() {
+            markdown.push_str(&format!(
+                "\n## {}\n\n| ToDo | Type | Author | Age | Issues |\n| --- | --- | --- | ---: | --- |\n",
+                markdown_text(&directory)
+            ));
+            for row in rows {
+                let location = format!("{}:{}", row.file, row.line);
+                let location = match self.source_link(row) {
+                    Some(url) => format!("[{}]({})", location, url),
+                    None => format!("`{}`", location),
+                };
+                let issues: Vec<String> = row
+                    .links
+                    .iter()
+                    .map(|link| match issue_url(link) {
+                        Some(url) => format!("[{}]({})", link, url),
+                        None => link.to_string(),
+                    })
+                    .collect();
+                markdown.push_str(&format!(
+                    "| {} {} | {} | {} | {} | {} |\n",
+                    location,
+                    markdown_text(&row.description),
+                    row.issue_type,
+                    markdown_text(row.owner()),
+                    row.age_days.map(|d| d.to_string()).unwrap_or_default(),
+                    issues.join(", ")
+                ));
+            }
+        }
+        markdown
+    }
+
+    /// A standalone HTML page with the same content as `to_markdown`.
+    pub fn to_html(&self) -> String {
+        let mut html = String::from(
+            "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+             <title>ToDo inventory</title>\n<style>\n\
+             body { font-family: sans-serif; margin: 2em; }\n\
+             table { border-collapse: collapse; margin-bottom: 1.5em; }\n\
+             th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }\n\
+             </style>\n</head>\n<body>\n<h1>ToDo inventory</h1>\n",
+        );
+        html.push_str(&format!("<p>{} ToDos.</p>\n", self.rows.len()));
+        for (title, counts) in self.summaries() {
+            html.push_str(&format!(
+                "<h2>By {}</h2>\n<table>\n<tr><th>{}</th><th>ToDos</th></tr>\n",
+                title, title
+            ));
+            for (group, count) in counts {
+                html.push_str(&format!(
+                    "<tr><td>{}</td><td>{}</td></tr>\n",
+                    html_text(&group),
+                    count
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        for (directory, rows) in self.by_directory() {
+            html.push_str(&format!(
+                "<h2>{}</h2>\n<table>\n<tr><th>ToDo</th><th>Type</th><th>Author</th>\
+                 <th>Age</th><th>Issues</th></tr>\n",
+                html_text(&directory)
+            ));
+            for row in rows {
+                let location = html_text(&format!("{}:{}", row.file, row.line));
+                let location = match self.source_link(row) {
+                    Some(url) => format!("<a href=\"{}\">{}</a>", html_text(&url), location),
+                    None => format!("<code>{}</code>", location),
+                };
+                let issues: Vec<String> = row
+                    .links
+                    .iter()
+                    .map(|link| match issue_url(link) {
+                        Some(url) => format!(
+                            "<a href=\"{}\">{}</a>",
+                            html_text(&url),
+                            html_text(&link.to_string())
+                        ),
+                        None => html_text(&link.to_string()),
+                    })
+                    .collect();
+                html.push_str(&format!(
+                    "<tr><td>{} {}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                    location,
+                    html_text(&row.description),
+                    row.issue_type,
+                    html_text(row.owner()),
+                    row.age_days.map(|d| d.to_string()).unwrap_or_default(),
+                    issues.join(", ")
+                ));
+            }
+            html.push_str("</table>\n");
+        }
+        html.push_str("</body>\n</html>\n");
+        html
+    }
+
+    /// ToDo counts by type, author and age.
+    fn summaries(&self) -> [(&str, BTreeMap<String, usize>); 3] {
+        let count = |group: fn(&ReportRow) -> String| {
+            let mut counts = BTreeMap::new();
+            for row in &self.rows {
+                *counts.entry(group(row)).or_default() += 1;
+            }
+            counts
+        };
+        [
+            ("type", count(|row| row.issue_type.clone())),
+            ("author", count(|row| row.author.clone())),
+            ("age", count(|row| age_bucket(row.age_days).to_string())),
+        ]
+    }
+
+    fn by_directory(&self) -> BTreeMap<String, Vec<&ReportRow>> {
+        let mut directories: BTreeMap<String, Vec<&ReportRow>> = BTreeMap::new();
+        for row in &self.rows {
+            let directory = match Path::new(&row.file).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+                _ => String::from("."),
+            };
+            directories.entry(directory).or_default().push(row);
+        }
+        directories
+    }
+
+    fn source_link(&self, row: &ReportRow) -> Option<String> {
+        let source_url = self.source_url.as_ref()?;
+        Some(format!("{}/{}#L{}", source_url, row.file, row.line))
+    }
+}
+
+impl ReportRow {
+    /// The assignee, or else whoever wrote the ToDo.
+    pub fn owner(&self) -> &str {
+        self.assigned.as_deref().unwrap_or(&self.author)
+    }
+}
+
+/// Buckets named so they sort from youngest to oldest.
+fn age_bucket(age_days: Option<i64>) -> &'static str {
+    match age_days {
+        None => "unknown",
+        Some(days) if days < 30 => "1. under 30 days",
+        Some(days) if days < 90 => "2. 30 to 90 days",
+        Some(days) if days < 365 => "3. 90 days to a year",
+        Some(_) => "4. over a year",
+    }
+}
+
+/// Web address of a linked issue, for boards whose address follows from their name.
+fn issue_url(link: &BoardLink) -> Option<String> {
+    let repository = link.board.strip_prefix("github:")?;
+    Some(format!(
+        "https://github.com/{}/issues/{}",
+        repository,
+        link.id()?
+    ))
+}
+
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn markdown_text(text: &str) -> String {
+    text.replace('|', "\\|").replace('\n', " ")
+}
+
+fn html_text(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        issue_repository::{Issue, IssueId},
+        test_fixtures::{parsed_todo, FIXTURE_DATE},
+    };
+
+    fn report() -> Report {
+        let todos = vec![
+            parsed_todo(
+                "//TODO bug: parse \"quoted\", flags; assigned jolee18",
+                "./src/main.rs",
+                4,
+                FIXTURE_DATE,
+            ),
+            parsed_todo("//TODO feature: <retry> payments", "billing/pay.rs", 4, ""),
+        ];
+        let mut tissue = Tissue::new(todos[0].clone());
+        tissue.links.push(BoardLink {
+            board: "github:org/repo".to_string(),
+            issue: Issue {
+                number: Some(IssueId::Number(12)),
+                ..Issue::from(&todos[0])
+            },
+        });
+        Report::new(
+            &todos,
+            &[tissue],
+            NaiveDate::from_ymd_opt(2024, 9, 1).unwrap(),
+        )
+        .with_source_url("https://github.com/org/repo/blob/main/")
+    }
+
+    #[test]
+    fn exports_csv_and_json_lines() {
+        let report = report();
+
+        let csv = report.to_csv();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[2].starts_with(
+            "src/main.rs,4,bug,\"parse \"\"quoted\"\", flags\",jolee18,OthelloEngineer,\
+             2024-03-24 02:45:51 +0100,161,"
+        ));
+        assert!(lines[2].ends_with(",github:org/repo#12"));
+
+        let json_lines = report.to_json_lines();
+        let first: serde_json::Value =
+            serde_json::from_str(json_lines.lines().next().unwrap()).unwrap();
+        assert_eq!(first["file"], "billing/pay.rs");
+        assert_eq!(first["type"], "feature");
+        assert_eq!(first["age_days"], serde_json::Value::Null);
+        assert_eq!(first["issues"], serde_json::json!([]));
+    }
+
+    #[test]
+    fn renders_grouped_markdown_and_html() {
+        let report = report();
+
+        let markdown = report.to_markdown();
+        assert!(markdown.contains(
+            "## By author\n\n| author | ToDos |\n| --- | ---: |\n\
+             | OthelloEngineer | 2 |\n\n"
+        ));
+        assert!(markdown.contains("| 3. 90 days to a year | 1 |\n"));
+        assert!(markdown.contains("## billing\n"));
+        assert!(markdown.contains(
+            "| [src/main.rs:4](https://github.com/org/repo/blob/main/src/main.rs#L4) \
+             parse \"quoted\", flags | bug | jolee18 | 161 | \
+             [github:org/repo#12](https://github.com/org/repo/issues/12) |\n"
+        ));
+
+        let html = report.to_html();
+        assert!(html.starts_with("<!DOCTYPE html>"));
+        assert!(html.contains("&lt;retry&gt; payments"));
+        assert!(html.contains("<a href=\"https://github.com/org/repo/issues/12\">"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Decides which boards a ToDo is filed on, from rules such as `infra/** -> gitlab:ops/infra`.
+//! A rule can list several boards, e.g. `api/** -> github:org/repo, jira:API`, to mirror its
+//! ToDos on all of them.
+use std::{fmt, str::FromStr};
+
+use color_eyre::eyre::Result;
+use globset::{Glob, GlobMatcher};
+use serde::{Deserialize, Serialize};
+
+/// Pattern of the rule that catches every file no other rule matches.
+pub const DEFAULT_ROUTE: &str = "default";
+
+/// Sends ToDos in files matching `pattern` to each of `boards`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RoutingRule {
+    pub pattern: String,
+    pub boards: Vec<String>,
+}
+
+impl FromStr for RoutingRule {
+    type Err = color_eyre::Report;
+
+    /// Parses `pattern -> board, board`, also accepting `→` as the arrow.
+    fn from_str(rule: &str) -> Result<RoutingRule> {
+        let (pattern, boards) = rule
+            .split_once("->")
+            .or_else(|| rule.split_once('→'))
+            .ok_or_else(|| {
+                color_eyre::Report::msg(format!(
+                    "Routing rule '{}' should look like 'services/billing/** -> jira:BILL'",
+                    rule
+                ))
+            })?;
+        let boards: Vec<String> = boards
+            .split(',')
+            .map(str::trim)
+            .filter(|board| !board.is_empty())
+            .map(String::from)
+            .collect();
+        if boards.is_empty() {
+            return Err(color_eyre::Report::msg(format!(
+                "Routing rule '{}' names no board",
+                rule
+            )));
+        }
+        Ok(RoutingRule {
+            pattern: pattern.trim().to_string(),
+            boards,
+        })
+    }
+}
+
+impl fmt::Display for RoutingRule {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} -> {}", self.pattern, self.boards.join(", "))
+    }
+}
+
+/// Compiled routing rules. The first matching rule wins; the `default` rule applies last,
+/// wherever it is listed.
+pub struct Router {
+    rules: Vec<(GlobMatcher, Vec<String>)>,
+    default: Vec<String>,
+}
+
+impl Router {
+    pub fn new(rules: &[RoutingRule]) -> Result<Router> {
+        let mut router = Router {
+            rules: vec![],
+            default: vec![],
+        };
+        for rule in rules {
+            if rule.pattern == DEFAULT_ROUTE {
+                router.default = rule.boards.clone();
+                continue;
+            }
+            let matcher = Glob::new(&rule.pattern)
+                .map_err(|e| {
+                    color_eyre::Report::msg(format!("Invalid routing rule '{}': {}", rule, e))
+                })?
+                .compile_matcher();
+            router.rules.push((matcher, rule.boards.clone()));
+        }
+        Ok(router)
+    }
+
+    /// Sends everything to `board`, for boxes with a single board and no rules.
+    pub fn single(board: &str) -> Router {
+        Router {
+            rules: vec![],
+            default: vec![board.to_string()],
+        }
+    }
+
+    /// Names of the boards ToDos in `file_path` go to. Empty if no rule covers it.
+    pub fn route(&self, file_path: &str) -> &[String] {
+        let file_path = file_path.strip_prefix("./").unwrap_or(file_path);
+        self.rules
+            .iter()
+            .find(|(matcher, _)| matcher.is_match(file_path))
+            .map(|(_, boards)| boards.as_slice())
+            .unwrap_or(&self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_rules() {
+        assert_eq!(
+            "services/billing/** -> jira:BILL"
+                .parse::<RoutingRule>()
+                .unwrap(),
+            RoutingRule {
+                pattern: "services/billing/**".to_string(),
+                boards: vec!["jira:BILL".to_string()],
+            }
+        );
+        assert_eq!(
+            "infra/** → gitlab:ops/infra, jira:OPS"
+                .parse::<RoutingRule>()
+                .unwrap()
+                .boards,
+            vec!["gitlab:ops/infra", "jira:OPS"]
+        );
+        assert!("infra/**".parse::<RoutingRule>().is_err());
+        assert!("infra/** -> ".parse::<RoutingRule>().is_err());
+    }
+
+    #[test]
+    fn first_matching_rule_wins_and_default_comes_last() {
+        let rules: Vec<RoutingRule> = [
+            "default -> github:org/repo",
+            "services/billing/** -> jira:BILL",
+            "services/** -> gitlab:ops/services",
+        ]
+        .iter()
+        .map(|rule| rule.parse().unwrap())
+        .collect();
+        let router = Router::new(&rules).unwrap();
+
+        assert_eq!(router.route("services/billing/src/lib.rs"), ["jira:BILL"]);
+        assert_eq!(
+            router.route("./services/auth/main.rs"),
+            ["gitlab:ops/services"]
+        );
+        assert_eq!(router.route("src/main.rs"), ["github:org/repo"]);
+        assert!(Router::new(&rules[1..])
+            .unwrap()
+            .route("src/main.rs")
+            .is_empty());
+    }
+}
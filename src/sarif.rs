@@ -0,0 +1,190 @@
+//! SARIF 2.1.0 export of a scan, for code-scanning dashboards and IDE SARIF viewers. Each ToDo
+//! is a `note` under a rule per issue type; comments that didn't parse are `warning`s.
+use serde_json::{json, Value};
+
+use crate::{
+    scanner::{ParseError, ScanResult},
+    todofinder::{fnv1a, IssueType, ToDo},
+};
+
+const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+const PARSE_ERROR_RULE: &str = "tissue/parse-error";
+/// Key of the fingerprint in `partialFingerprints`; bump the version if the fingerprint changes.
+const FINGERPRINT_KEY: &str = "tissue/v1";
+
+const ISSUE_TYPES: [IssueType; 4] = [
+    IssueType::Bug,
+    IssueType::Feature,
+    IssueType::Improvement,
+    IssueType::Other,
+];
+
+/// Rule ID of ToDos of `issue_type`, e.g. `tissue/todo-bug`.
+pub fn rule_id(issue_type: &IssueType) -> String {
+    format!("tissue/todo-{}", issue_type.as_str())
+}
+
+/// The scan as a SARIF log with a single run. File paths are relative to `%SRCROOT%`.
+pub fn to_sarif(scan: &ScanResult) -> Value {
+    let mut rules: Vec<Value> = ISSUE_TYPES
+        .iter()
+        .map(|issue_type| {
+            json!({
+                "id": rule_id(issue_type),
+                "name": format!("ToDo{}", capitalise(issue_type.as_str())),
+                "shortDescription": { "text": format!("{} ToDo", capitalise(issue_type.as_str())) },
+                "defaultConfiguration": { "level": "note" },
+            })
+        })
+        .collect();
+    rules.push(json!({
+        "id": PARSE_ERROR_RULE,
+        "name": "ToDoParseError",
+        "shortDescription": { "text": "ToDo comment tissue can't parse" },
+        "defaultConfiguration": { "level": "warning" },
+    }));
+
+    let results: Vec<Value> = scan
+        .todos
+        .iter()
+        .map(todo_result)
+        .chain(scan.errors.iter().map(parse_error_result))
+        .collect();
+
+    json!({
+        "$schema": SCHEMA,
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": "tissue",
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": "https://github.com/OthelloEngineer/tissue",
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+fn todo_result(todo: &ToDo) -> Value {
+    let rule_index = ISSUE_TYPES
+        .iter()
+        .position(|issue_type| *issue_type == todo.issue_type)
+        .unwrap_or(ISSUE_TYPES.len() - 1);
+    let mut result = json!({
+        "ruleId": rule_id(&todo.issue_type),
+        "ruleIndex": rule_index,
+        "level": "note",
+        "message": { "text": todo.description },
+        "locations": [location(
+            &todo.submission.file_path,
+            todo.submission.line_number,
+            &todo.submission.line,
+        )],
+        "partialFingerprints": { FINGERPRINT_KEY: todo.fingerprint() },
+    });
+    if let Some(assigned) = &todo.assigned {
+        result["properties"] = json!({ "assigned": assigned });
+    }
+    result
+}
+
+fn parse_error_result(error: &ParseError) -> Value {
+    let key = format!("{}\0{}", error.file_path, error.line.trim());
+    json!({
+        "ruleId": PARSE_ERROR_RULE,
+        "ruleIndex": ISSUE_TYPES.len(),
+        "level": "warning",
+        "message": { "text": error.message },
+        "locations": [location(&error.file_path, error.line_number, &error.line)],
+        "partialFingerprints": { FINGERPRINT_KEY: format!("{:016x}", fnv1a(key.as_bytes())) },
+    })
+}
+
+fn location(file_path: &str, line_number: usize, line: &str) -> Value {
+    let file_path = file_path.strip_prefix("./").unwrap_or(file_path);
+    json!({
+        "physicalLocation": {
+            "artifactLocation": { "uri": file_path, "uriBaseId": "%SRCROOT%" },
+            "region": { "startLine": line_number, "snippet": { "text": line } },
+        }
+    })
+}
+
+fn capitalise(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_fixtures::{parsed_todo, FIXTURE_DATE};
+
+    fn scan() -> ScanResult {
+        let todo = parsed_todo(
+            "//TODO feature: parse flags; assigned jolee18",
+            "./src/main.rs",
+            2,
+            FIXTURE_DATE,
+        );
+        ScanResult {
+            todos: vec![todo],
+            errors: vec![ParseError {
+                file_path: "src/lib.rs".to_string(),
+                line_number: 7,
+                line: "// todo".to_string(),
+                message: "No issue type found".to_string(),
+            }],
+        }
+    }
+
+    #[test]
+    fn exports_todos_and_parse_errors() {
+        let scan = scan();
+        let sarif = to_sarif(&scan);
+        let run = &sarif["runs"][0];
+
+        assert_eq!(sarif["version"], "2.1.0");
+        assert_eq!(run["tool"]["driver"]["rules"].as_array().unwrap().len(), 5);
+        let todo = &run["results"][0];
+        assert_eq!(todo["ruleId"], "tissue/todo-feature");
+        assert_eq!(run["tool"]["driver"]["rules"][1]["id"], todo["ruleId"]);
+        assert_eq!(todo["message"]["text"], "parse flags");
+        let location = &todo["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "src/main.rs");
+        assert_eq!(location["region"]["startLine"], 2);
+        assert_eq!(
+            todo["partialFingerprints"]["tissue/v1"],
+            scan.todos[0].fingerprint()
+        );
+        assert_eq!(todo["properties"]["assigned"], "jolee18");
+
+        let error = &run["results"][1];
+        assert_eq!(error["ruleId"], PARSE_ERROR_RULE);
+        assert_eq!(error["ruleIndex"], 4);
+        assert_eq!(error["level"], "warning");
+    }
+
+    #[test]
+    fn fingerprints_survive_moving_lines() {
+        let mut moved = scan();
+        moved.todos[0].submission.line_number = 40;
+        moved.errors[0].line_number = 41;
+
+        let before = to_sarif(&scan());
+        let after = to_sarif(&moved);
+
+        for i in 0..2 {
+            assert_eq!(
+                before["runs"][0]["results"][i]["partialFingerprints"],
+                after["runs"][0]["results"][i]["partialFingerprints"]
+            );
+        }
+    }
+}
@@ -0,0 +1,235 @@
+//! `.tissue/cache`: what `scanner::scan_cached` found in each file, kept between runs so a repeat
+//! scan only reads and blames the files that changed.
+use std::{
+    collections::{BTreeMap, HashSet},
+    fs::{self, Metadata},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    githandler,
+    scanner::ParseError,
+    todofinder::{fnv1a, Syntax, ToDo},
+};
+
+pub const CACHE_DIR: &str = ".tissue/cache";
+const CACHE_FILE: &str = "scan.json";
+/// Who `git blame` says wrote lines in the working tree that aren't committed.
+const UNCOMMITTED_AUTHOR: &str = "Not Committed Yet";
+
+/// A file's ToDo comments and what they parsed to, with blame.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct FileScan {
+    pub comments: Vec<(usize, String)>,
+    pub todos: Vec<ToDo>,
+    pub errors: Vec<ParseError>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct CachedFile {
+    /// Nanoseconds since the epoch, or 0 if the file was modified too recently to trust.
+    modified: u64,
+    size: u64,
+    hash: String,
+    /// The commit blamed at if some ToDo was uncommitted, whose blame changes once it is.
+    head: Option<String>,
+    scan: FileScan,
+}
+
+/// Scans by file path, valid for one set of parsing rules.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ScanCache {
+    rules: String,
+    files: BTreeMap<String, CachedFile>,
+    #[serde(skip)]
+    head: Option<String>,
+    #[serde(skip)]
+    rescanned: usize,
+}
+
+impl ScanCache {
+    /// An empty cache for `syntax`, blaming at `head`.
+    pub fn new(syntax: &Syntax, head: Option<String>) -> ScanCache {
+        ScanCache {
+            rules: rules_key(syntax),
+            files: BTreeMap::new(),
+            head,
+            rescanned: 0,
+        }
+    }
+
+    /// The cache saved in `root`, or an empty one if there is none, it can't be read or it was
+    /// filled with other parsing rules than `syntax`.
+    pub fn load(root: &Path, syntax: &Syntax) -> ScanCache {
+        let head = githandler::head_commit(root).ok();
+        let saved = fs::read_to_string(root.join(CACHE_DIR).join(CACHE_FILE))
+            .ok()
+            .and_then(|json| serde_json::from_str::<ScanCache>(&json).ok())
+            .filter(|saved| saved.rules == rules_key(syntax));
+        match saved {
+            Some(saved) => ScanCache { head, ..saved },
+            None => ScanCache::new(syntax, head),
+        }
+    }
+
+    /// Writes the cache to `.tissue/cache` in `root`, which git is told to ignore.
+    pub fn save(&self, root: &Path) -> Result<()> {
+        let directory = root.join(CACHE_DIR);
+        fs::create_dir_all(&directory)?;
+        fs::write(directory.join(".gitignore"), "*\n")?;
+        let temporary = directory.join(format!("{}.tmp", CACHE_FILE));
+        fs::write(&temporary, serde_json::to_string(self)?)?;
+        fs::rename(temporary, directory.join(CACHE_FILE))?;
+        Ok(())
+    }
+
+    /// The cached scan of `file_path` if the file hasn't changed since. Without `source`, only
+    /// its modification time and size are compared; with it, the content hash.
+    pub fn get(
+        &mut self,
+        file_path: &str,
+        metadata: &Metadata,
+        source: Option<&[u8]>,
+    ) -> Option<FileScan> {
+        let head = &self.head;
+        let cached = self
+            .files
+            .get_mut(file_path)
+            .filter(|cached| cached.head.is_none() || cached.head == *head)?;
+        let modified = trusted_modified(metadata);
+        match source {
+            None if modified != 0
+                && cached.modified == modified
+                && cached.size == metadata.len() =>
+            {
+                Some(cached.scan.clone())
+            }
+            Some(source) if cached.hash == hash(source) => {
+                cached.modified = modified;
+                cached.size = metadata.len();
+                Some(cached.scan.clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Caches the scan of `file_path`, whose content is `source`.
+    pub fn insert(&mut self, file_path: &str, metadata: &Metadata, source: &[u8], scan: FileScan) {
+        let uncommitted = scan.todos.iter().any(|todo| {
+            matches!(
+                todo.submission.issuer.as_str(),
+                "unknown" | UNCOMMITTED_AUTHOR
+            )
+        });
+        self.rescanned += 1;
+        self.files.insert(
+            file_path.to_string(),
+            CachedFile {
+                modified: trusted_modified(metadata),
+                size: metadata.len(),
+                hash: hash(source),
+                head: self.head.clone().filter(|_| uncommitted),
+                scan,
+            },
+        );
+    }
+
+    /// Forgets the files not in `file_paths`, e.g. deleted ones.
+    pub fn retain(&mut self, file_paths: &[String]) {
+        let keep: HashSet<&String> = file_paths.iter().collect();
+        self.files.retain(|file_path, _| keep.contains(file_path));
+    }
+
+    /// How many files were scanned afresh since the cache was loaded.
+    pub fn rescanned(&self) -> usize {
+        self.rescanned
+    }
+}
+
+/// Identifies the parsing rules and the parser: a change to either makes every entry stale.
+fn rules_key(syntax: &Syntax) -> String {
+    let key = format!("{}\0{:?}", env!("CARGO_PKG_VERSION"), syntax);
+    format!("{:016x}", fnv1a(key.as_bytes()))
+}
+
+fn hash(source: &[u8]) -> String {
+    format!("{:016x}{:016x}", fnv1a(source), source.len())
+}
+
+/// The modification time, or 0 for a file modified in the last couple of seconds: a write in the
+/// same clock tick as the scan wouldn't change it, so such files get their content hashed.
+fn trusted_modified(metadata: &Metadata) -> u64 {
+    let Some(modified) = metadata.modified().ok() else {
+        return 0;
+    };
+    let recent = SystemTime::now()
+        .checked_sub(Duration::from_secs(2))
+        .is_some_and(|cutoff| modified > cutoff);
+    match modified.duration_since(UNIX_EPOCH) {
+        Ok(since) if !recent => since.as_nanos() as u64,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scanner, test_fixtures::git};
+
+    fn rescan(root: &Path, syntax: &Syntax) -> (usize, Vec<String>) {
+        let mut cache = ScanCache::load(root, syntax);
+        let scan = scanner::scan_cached(root, syntax, &mut cache).unwrap();
+        cache.save(root).unwrap();
+        let todos = scan
+            .todos
+            .iter()
+            .map(|todo| format!("{} {}", todo.description, todo.submission.issuer))
+            .collect();
+        (cache.rescanned(), todos)
+    }
+
+    #[test]
+    fn rescans_only_changed_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        git(root, &["init", "-q"]);
+        fs::write(root.join("main.rs"), "//TODO bug: parse flags\n").unwrap();
+        fs::write(root.join("lib.rs"), "//TODO feature: retry\n").unwrap();
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "init"]);
+        let syntax = Syntax::default();
+
+        assert_eq!(rescan(root, &syntax).0, 2);
+        // Just written, so the modification times aren't trusted yet: the hashes still match.
+        assert_eq!(
+            rescan(root, &syntax),
+            (
+                0,
+                vec![
+                    "retry jolee18".to_string(),
+                    "parse flags jolee18".to_string()
+                ]
+            )
+        );
+
+        fs::write(root.join("lib.rs"), "//TODO feature: retry twice\n").unwrap();
+        let (rescanned, todos) = rescan(root, &syntax);
+        assert_eq!(rescanned, 1);
+        assert_eq!(todos[0], "retry twice Not Committed Yet");
+
+        // Committing changes the blame of the uncommitted ToDo without touching the file.
+        git(root, &["commit", "-q", "-am", "retry twice"]);
+        let (rescanned, todos) = rescan(root, &syntax);
+        assert_eq!(rescanned, 1);
+        assert_eq!(todos[0], "retry twice jolee18");
+
+        let mut other = syntax.clone();
+        other.keywords.push("fixme".to_string());
+        assert_eq!(rescan(root, &other).0, 2);
+        assert!(root.join(CACHE_DIR).join(".gitignore").exists());
+    }
+}
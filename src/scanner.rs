@@ -0,0 +1,218 @@
+//! Finds the ToDos in every file git tracks, with who wrote them and when.
+use std::{collections::BTreeMap, fs, path::Path, process::Command};
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    githandler,
+    scan_cache::{FileScan, ScanCache},
+    todofinder::{Submission, Syntax, ToDo},
+    walker::Walk,
+};
+
+/// ToDos found by `scan`, plus the comments that looked like ToDos but didn't parse.
+#[derive(Debug, Default)]
+pub struct ScanResult {
+    pub todos: Vec<ToDo>,
+    pub errors: Vec<ParseError>,
+}
+
+/// A comment mentioning a ToDo that `todofinder::parse_submission` rejected.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ParseError {
+    pub file_path: String,
+    pub line_number: usize,
+    pub line: String,
+    pub message: String,
+}
+
+/// Scans the files tracked in the git repository at `root` for ToDos in `syntax`. File paths
+/// are relative to `root`. Lines git can't blame, e.g. uncommitted ones, get "unknown" as issuer
+/// and no date.
+pub fn scan(root: &Path, syntax: &Syntax) -> Result<ScanResult> {
+    let mut result = ScanResult::default();
+    for file_path in tracked_files(root)? {
+        scan_into(root, &file_path, syntax, &mut result);
+    }
+    Ok(result)
+}
+
+/// Scans the single file `file_path`, relative to `root`, as it is in the working tree. A file
+/// that doesn't exist or isn't text has no ToDos.
+pub fn scan_file(root: &Path, file_path: &str, syntax: &Syntax) -> ScanResult {
+    let mut result = ScanResult::default();
+    scan_into(root, file_path, syntax, &mut result);
+    result
+}
+
+/// Scans the files `walk` streams from `root`, tracked by git or not, ordered by file and line.
+/// Files the walk couldn't read are left out; its progress counts them.
+pub fn scan_walk(root: &Path, walk: Walk, syntax: &Syntax) -> ScanResult {
+    let mut result = ScanResult::default();
+    for file in walk.flatten() {
+        let path = Path::new(&file.file_path);
+        let file_path = path
+            .strip_prefix(root)
+            .unwrap_or(path)
+            .display()
+            .to_string();
+        let lines = file.lines.into_iter().collect();
+        scan_lines(root, None, &file_path, lines, syntax, &mut result);
+    }
+    result.todos.sort_by(|a, b| {
+        (&a.submission.file_path, a.submission.line_number)
+            .cmp(&(&b.submission.file_path, b.submission.line_number))
+    });
+    result
+        .errors
+        .sort_by(|a, b| (&a.file_path, a.line_number).cmp(&(&b.file_path, b.line_number)));
+    result
+}
+
+/// `scan`, reusing what `cache` holds for files unchanged since it was filled and caching the
+/// rest. Files no longer tracked are dropped from the cache.
+pub fn scan_cached(root: &Path, syntax: &Syntax, cache: &mut ScanCache) -> Result<ScanResult> {
+    let mut result = ScanResult::default();
+    let file_paths = tracked_files(root)?;
+    for file_path in &file_paths {
+        let path = root.join(file_path);
+        let Ok(metadata) = fs::metadata(&path) else {
+            continue;
+        };
+        let scan = match cache.get(file_path, &metadata, None) {
+            Some(scan) => scan,
+            None => {
+                let Ok(source) = fs::read(&path) else {
+                    continue;
+                };
+                match cache.get(file_path, &metadata, Some(&source)) {
+                    Some(scan) => scan,
+                    None => {
+                        let mut file_result = ScanResult::default();
+                        let comments = match std::str::from_utf8(&source) {
+                            Ok(source) => {
+                                scan_source(root, file_path, source, syntax, &mut file_result)
+                            }
+                            Err(_) => vec![],
+                        };
+                        let scan = FileScan {
+                            comments,
+                            todos: file_result.todos,
+                            errors: file_result.errors,
+                        };
+                        cache.insert(file_path, &metadata, &source, scan.clone());
+                        scan
+                    }
+                }
+            }
+        };
+        result.todos.extend(scan.todos);
+        result.errors.extend(scan.errors);
+    }
+    cache.retain(&file_paths);
+    Ok(result)
+}
+
+fn scan_into(root: &Path, file_path: &str, syntax: &Syntax, result: &mut ScanResult) {
+    let Ok(source) = fs::read_to_string(root.join(file_path)) else {
+        return;
+    };
+    scan_source(root, file_path, &source, syntax, result);
+}
+
+fn scan_source(
+    root: &Path,
+    file_path: &str,
+    source: &str,
+    syntax: &Syntax,
+    result: &mut ScanResult,
+) -> Vec<(usize, String)> {
+    let lines = source
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.to_string()))
+        .collect();
+    scan_lines(root, None, file_path, lines, syntax, result)
+}
+
+/// Scans the tree of `revision`, e.g. a tag or commit hash, without checking it out.
+pub fn scan_revision(root: &Path, revision: &str, syntax: &Syntax) -> Result<ScanResult> {
+    let mut files: BTreeMap<String, Vec<(usize, String)>> = BTreeMap::new();
+    for (file_path, line_number, line) in
+        githandler::grep_todos_at(root, revision, &syntax.keywords)?
+    {
+        files
+            .entry(file_path)
+            .or_default()
+            .push((line_number, line));
+    }
+    let mut result = ScanResult::default();
+    for (file_path, lines) in files {
+        scan_lines(root, Some(revision), &file_path, lines, syntax, &mut result);
+    }
+    Ok(result)
+}
+
+/// Parses the comment `lines` of `file_path` that mention a ToDo, blaming them at `revision`.
+/// Returns those comments.
+fn scan_lines(
+    root: &Path,
+    revision: Option<&str>,
+    file_path: &str,
+    lines: Vec<(usize, String)>,
+    syntax: &Syntax,
+    result: &mut ScanResult,
+) -> Vec<(usize, String)> {
+    let mut lines: Vec<(usize, String)> = lines
+        .into_iter()
+        .filter(|(_, line)| syntax.is_comment(line) && syntax.is_to_do(line))
+        .collect();
+    if lines.is_empty() {
+        return lines;
+    }
+    lines.sort();
+    let blame = githandler::blame_file_at(root, revision, file_path).unwrap_or_default();
+    for (line_number, line) in lines.iter().cloned() {
+        let (issuer, date) = match blame.get(&line_number) {
+            Some(entry) => (entry.user.clone(), entry.date.clone()),
+            None => (String::from("unknown"), String::new()),
+        };
+        let submission = Submission {
+            line_number,
+            line: line.trim().to_string(),
+            file_path: file_path.to_string(),
+            issuer,
+            date,
+        };
+        match syntax.parse(submission.clone()) {
+            Ok(todo) => result.todos.push(todo),
+            Err(message) => result.errors.push(ParseError {
+                file_path: submission.file_path,
+                line_number,
+                line: submission.line,
+                message,
+            }),
+        }
+    }
+    lines
+}
+
+fn tracked_files(root: &Path) -> Result<Vec<String>> {
+    let output = Command::new("git")
+        .current_dir(root)
+        .args(["ls-files", "-z"])
+        .output()?;
+    if !output.status.success() {
+        return Err(color_eyre::Report::msg(format!(
+            "Couldn't list the files git tracks in {}: {}",
+            root.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .split('\0')
+        .filter(|file_path| !file_path.is_empty())
+        .map(String::from)
+        .collect())
+}
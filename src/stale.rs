@@ -0,0 +1,380 @@
+//! Flags ToDos that have been in the code too long or are past their due date, and escalates
+//! them on their boards. A ToDo's age comes from the blame date in its submission; its due date
+//! from a `due 2025-01-31` (or `due: 2025-01-31`) in the comment.
+use std::{collections::BTreeMap, path::Path};
+
+use chrono::{DateTime, NaiveDate};
+use color_eyre::eyre::Result;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    issue_repository::{IssueBoard, IssueUpdateRequest, Label},
+    sync::SyncFailure,
+    tissuebox_repository::{BoardLink, TissueBoxRepository},
+    todofinder::ToDo,
+};
+
+/// What happens to the issues of a ToDo a rule flags.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Escalation {
+    /// Adds a label such as `priority:high`.
+    AddLabel(String),
+    /// Comments on the issue, mentioning the assignee.
+    Comment(String),
+    /// Makes `tissue stale` exit with an error, as `--fail` does for any rule.
+    FailCi,
+}
+
+/// Flags ToDos older than `older_than_days`, or past their due date if `past_due` is set.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StaleRule {
+    pub name: String,
+    #[serde(default)]
+    pub older_than_days: Option<i64>,
+    #[serde(default)]
+    pub past_due: bool,
+    #[serde(default)]
+    pub escalations: Vec<Escalation>,
+}
+
+impl StaleRule {
+    pub fn older_than(name: &str, days: i64) -> StaleRule {
+        StaleRule {
+            name: name.to_string(),
+            older_than_days: Some(days),
+            past_due: false,
+            escalations: vec![],
+        }
+    }
+
+    pub fn past_due(name: &str) -> StaleRule {
+        StaleRule {
+            name: name.to_string(),
+            older_than_days: None,
+            past_due: true,
+            escalations: vec![],
+        }
+    }
+
+    pub fn with_escalation(mut self, escalation: Escalation) -> StaleRule {
+        self.escalations.push(escalation);
+        self
+    }
+
+    fn matches(&self, age_days: Option<i64>, overdue_days: Option<i64>) -> bool {
+        let too_old = self
+            .older_than_days
+            .zip(age_days)
+            .is_some_and(|(limit, age)| age > limit);
+        let overdue = self.past_due && overdue_days.is_some_and(|days| days > 0);
+        too_old || overdue
+    }
+}
+
+/// A ToDo flagged by the rule named `rule`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StaleFinding {
+    pub todo: ToDo,
+    pub rule: String,
+    /// Days since the ToDo was written, if git knows.
+    pub age_days: Option<i64>,
+    /// Days since the due date, negative if it is still ahead.
+    pub overdue_days: Option<i64>,
+}
+
+impl StaleFinding {
+    /// Who should act on the ToDo: its assignee, or else whoever wrote it.
+    pub fn owner(&self) -> &str {
+        self.todo
+            .assigned
+            .as_deref()
+            .unwrap_or(&self.todo.submission.issuer)
+    }
+
+    pub fn directory(&self) -> String {
+        let file_path = self.todo.submission.file_path.as_str();
+        let file_path = file_path.strip_prefix("./").unwrap_or(file_path);
+        match Path::new(file_path).parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.display().to_string(),
+            _ => String::from("."),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct EscalationReport {
+    pub labelled: Vec<BoardLink>,
+    pub commented: Vec<BoardLink>,
+    /// Flagged ToDos with no issue to escalate on.
+    pub unsynced: Vec<ToDo>,
+    pub failed: Vec<SyncFailure>,
+    /// Whether a rule with `Escalation::FailCi` flagged anything.
+    pub fail_ci: bool,
+}
+
+/// The date in a `due YYYY-MM-DD` part of the ToDo comment.
+pub fn due_date(todo: &ToDo) -> Option<NaiveDate> {
+    let due = Regex::new(r"(?i)\bdue:?\s+(\d{4}-\d{2}-\d{2})").unwrap();
+    let date = due.captures(&todo.submission.line)?.get(1)?.as_str();
+    NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()
+}
+
+/// Days between the blame date of the ToDo and `today`.
+pub fn age_days(todo: &ToDo, today: NaiveDate) -> Option<i64> {
+    let written = DateTime::parse_from_str(&todo.submission.date, "%Y-%m-%d %H:%M:%S %z").ok()?;
+    Some((today - written.date_naive()).num_days())
+}
+
+/// Checks every ToDo against every rule, one finding per rule that flags it.
+pub fn find_stale(todos: &[ToDo], rules: &[StaleRule], today: NaiveDate) -> Vec<StaleFinding> {
+    let mut findings = Vec::new();
+    for todo in todos {
+        let age_days = age_days(todo, today);
+        let overdue_days = due_date(todo).map(|due| (today - due).num_days());
+        for rule in rules.iter().filter(|r| r.matches(age_days, overdue_days)) {
+            findings.push(StaleFinding {
+                todo: todo.clone(),
+                rule: rule.name.clone(),
+                age_days,
+                overdue_days,
+            });
+        }
+    }
+    findings
+}
+
+/// Findings grouped by owner, then by the directory of the file.
+pub fn group_by_owner(findings: &[StaleFinding]) -> BTreeMap<(String, String), Vec<&StaleFinding>> {
+    let mut groups: BTreeMap<(String, String), Vec<&StaleFinding>> = BTreeMap::new();
+    for finding in findings {
+        groups
+            .entry((finding.owner().to_string(), finding.directory()))
+            .or_default()
+            .push(finding);
+    }
+    groups
+}
+
+/// Whether a rule with `Escalation::FailCi` flagged any of `findings`.
+pub fn fails_ci(rules: &[StaleRule], findings: &[StaleFinding]) -> bool {
+    findings.iter().any(|finding| {
+        rules
+            .iter()
+            .any(|rule| rule.name == finding.rule && rule.escalations.contains(&Escalation::FailCi))
+    })
+}
+
+/// Whether any of `rules` escalates on the boards, not just in CI.
+pub fn escalates_on_boards(rules: &[StaleRule]) -> bool {
+    rules
+        .iter()
+        .flat_map(|rule| &rule.escalations)
+        .any(|escalation| *escalation != Escalation::FailCi)
+}
+
+/// Applies the escalations of each finding's rule to every issue linked to its ToDo. A rule is
+/// escalated once per tissue; the tissue remembers it, so running this again does nothing new.
+pub async fn escalate(
+    tissue_box: &mut dyn TissueBoxRepository,
+    boards: &[&dyn IssueBoard],
+    rules: &[StaleRule],
+    findings: &[StaleFinding],
+) -> Result<EscalationReport> {
+    let mut report = EscalationReport {
+        fail_ci: fails_ci(rules, findings),
+        ..EscalationReport::default()
+    };
+
+    for finding in findings {
+        let Some(rule) = rules.iter().find(|rule| rule.name == finding.rule) else {
+            continue;
+        };
+        let board_escalations = rule
+            .escalations
+            .iter()
+            .any(|escalation| *escalation != Escalation::FailCi);
+        if !board_escalations {
+            continue;
+        }
+        let Some(mut tissue) = tissue_box.get_tissue_by_fingerprint(&finding.todo.fingerprint())?
+        else {
+            report.unsynced.push(finding.todo.clone());
+            continue;
+        };
+        if tissue.links.is_empty() {
+            report.unsynced.push(finding.todo.clone());
+            continue;
+        }
+        if tissue.escalations.contains(&rule.name) {
+            continue;
+        }
+
+        let mut succeeded = true;
+        for link in &mut tissue.links {
+            let Some(id) = link.id().cloned() else {
+                continue;
+            };
+            let Some(board) = boards
+                .iter()
+                .find(|board| board.get_repository_name() == link.board)
+            else {
+                continue;
+            };
+            for escalation in &rule.escalations {
+                let result = match escalation {
+                    Escalation::AddLabel(name) => {
+                        let update = IssueUpdateRequest::AddLabel(Label { name: name.clone() });
+                        let result = board.update_issue(&id, &update).await;
+                        if result.is_ok() {
+                            link.issue.apply(&update);
+                            report.labelled.push(link.clone());
+                        }
+                        result
+                    }
+                    Escalation::Comment(message) => {
+                        let body = match &tissue.todo.assigned {
+                            Some(assignee) => format!("@{} {}", assignee, message),
+                            None => message.clone(),
+                        };
+                        let result = board.add_comment(&id, &body).await;
+                        if result.is_ok() {
+                            report.commented.push(link.clone());
+                        }
+                        result
+                    }
+                    Escalation::FailCi => Ok(()),
+                };
+                if let Err(e) = result {
+                    succeeded = false;
+                    report.failed.push(SyncFailure {
+                        board: link.board.clone(),
+                        todo: tissue.todo.clone(),
+                        error: e.to_string(),
+                    });
+                }
+            }
+        }
+        if succeeded {
+            tissue.escalations.push(rule.name.clone());
+        }
+        // Saved right away, as another rule may flag the same tissue further on.
+        tissue_box.upsert_tissues(vec![tissue])?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        issue_repository::IssueId,
+        routing::Router,
+        sync::sync,
+        test_fixtures::{parsed_todo, FIXTURE_DATE},
+        testing::{InMemoryBoard, InMemoryTissueBox},
+    };
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 9, 1).unwrap()
+    }
+
+    #[test]
+    fn flags_old_and_overdue_todos() {
+        let todos = vec![
+            parsed_todo(
+                "//TODO bug: old; assigned jolee18",
+                "src/main.rs",
+                1,
+                FIXTURE_DATE,
+            ),
+            parsed_todo(
+                "//TODO feature: due 2024-08-01 ship it",
+                "src/lib.rs",
+                1,
+                "2024-08-30 10:00:00 +0000",
+            ),
+            parsed_todo(
+                "//TODO feature: fresh",
+                "main.rs",
+                1,
+                "2024-08-30 10:00:00 +0000",
+            ),
+        ];
+        let rules = [
+            StaleRule::older_than("old", 90),
+            StaleRule::past_due("overdue"),
+        ];
+
+        let findings = find_stale(&todos, &rules, today());
+
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].rule, "old");
+        assert_eq!(findings[0].age_days, Some(161));
+        assert_eq!(findings[1].rule, "overdue");
+        assert_eq!(findings[1].overdue_days, Some(31));
+
+        let groups = group_by_owner(&findings);
+        let keys: Vec<_> = groups.keys().cloned().collect();
+        assert_eq!(
+            keys,
+            [
+                ("OthelloEngineer".to_string(), "src".to_string()),
+                ("jolee18".to_string(), "src".to_string()),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn escalates_each_rule_once() {
+        let board = InMemoryBoard::new("memory");
+        let mut tissue_box = InMemoryTissueBox::new();
+        let todos = vec![parsed_todo(
+            "//TODO bug: old; assigned jolee18",
+            "src/main.rs",
+            1,
+            FIXTURE_DATE,
+        )];
+        sync(
+            &mut tissue_box,
+            &[&board],
+            &Router::single("memory"),
+            &todos,
+        )
+        .await
+        .unwrap();
+        let rules = [StaleRule::older_than("old", 90)
+            .with_escalation(Escalation::AddLabel("priority:high".to_string()))
+            .with_escalation(Escalation::Comment("is this still needed?".to_string()))
+            .with_escalation(Escalation::FailCi)];
+        let findings = find_stale(&todos, &rules, today());
+
+        let report = escalate(&mut tissue_box, &[&board], &rules, &findings)
+            .await
+            .unwrap();
+
+        assert!(report.fail_ci);
+        assert_eq!(report.labelled.len(), 1);
+        assert!(board.issues()[0]
+            .issue_type
+            .iter()
+            .any(|label| label.name == "priority:high"));
+        assert_eq!(
+            board.comments(),
+            [(
+                IssueId::Number(1),
+                "@jolee18 is this still needed?".to_string()
+            )]
+        );
+
+        let calls = board.calls().len();
+        let report = escalate(&mut tissue_box, &[&board], &rules, &findings)
+            .await
+            .unwrap();
+        assert!(report.fail_ci);
+        assert!(report.labelled.is_empty() && report.commented.is_empty());
+        assert_eq!(board.calls().len(), calls);
+    }
+}
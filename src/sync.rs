@@ -0,0 +1,590 @@
+//! Brings the Tissue Box and its boards in line with the ToDos currently in the code.
+//!
+//! New ToDos are filed on every board their file routes to, moved ToDos are updated in the box
+//! and ToDos that disappeared have their issues closed. Mirrors of the same ToDo are kept in
+//! line in state, title and type, except where someone changed one by hand: those are reported
+//! as diverged and left alone. A failing board is reported without stopping the others.
+use std::collections::HashSet;
+
+use color_eyre::eyre::Result;
+
+use crate::{
+    issue_repository::{
+        BoardError, Issue, IssueBoard, IssueId, IssueState, IssueTemplate, IssueUpdateRequest,
+    },
+    routing::Router,
+    tissuebox_repository::{BoardLink, Tissue, TissueBoxRepository},
+    todofinder::ToDo,
+};
+
+/// A ToDo whose issue could not be filed, updated or closed on `board`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SyncFailure {
+    pub board: String,
+    pub todo: ToDo,
+    pub error: String,
+}
+
+/// A mirror that changed on its board since tissue last synced it, e.g. one closed by hand.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Divergence {
+    pub board: String,
+    pub issue: IssueId,
+    pub todo: ToDo,
+    /// What changed, e.g. `state: open -> closed`.
+    pub differences: Vec<String>,
+}
+
+#[derive(Debug, Default, PartialEq)]
+pub struct SyncReport {
+    pub created: Vec<Tissue>,
+    pub moved: Vec<Tissue>,
+    pub closed: Vec<Tissue>,
+    /// Mirrors filed on a newly routed board or brought back in line with their ToDo.
+    pub mirrored: Vec<BoardLink>,
+    pub diverged: Vec<Divergence>,
+    /// ToDos in files no routing rule covers.
+    pub unrouted: Vec<ToDo>,
+    pub failed: Vec<SyncFailure>,
+}
+
+fn find_board<'a>(boards: &[&'a dyn IssueBoard], name: &str) -> Result<&'a dyn IssueBoard> {
+    boards
+        .iter()
+        .find(|board| board.get_repository_name() == name)
+        .copied()
+        .ok_or_else(|| color_eyre::Report::msg(format!("Board {} is not configured", name)))
+}
+
+fn is_closed(issue: &Issue) -> bool {
+    issue.state() == IssueState::Closed
+}
+
+/// How `to` differs from `from` in the fields tissue keeps in line across mirrors.
+fn differences(from: &Issue, to: &Issue) -> Vec<String> {
+    let mut differences = Vec::new();
+    if is_closed(from) != is_closed(to) {
+        differences.push(format!("state: {} -> {}", from.state, to.state));
+    }
+    if from.title != to.title {
+        differences.push(format!("title: '{}' -> '{}'", from.title, to.title));
+    }
+    if from.issue_type() != to.issue_type() {
+        differences.push(format!(
+            "type: {} -> {}",
+            from.issue_type().as_str(),
+            to.issue_type().as_str()
+        ));
+    }
+    differences
+}
+
+/// Updates that bring `issue` in line with `todo`.
+fn updates_for(issue: &Issue, todo: &ToDo) -> Vec<IssueUpdateRequest> {
+    let mut updates = Vec::new();
+    if is_closed(issue) {
+        updates.push(IssueUpdateRequest::State(IssueState::Reopened));
+    }
+    if issue.title != todo.description {
+        updates.push(IssueUpdateRequest::Title(todo.description.clone()));
+    }
+    if issue.issue_type() != todo.issue_type {
+        updates.push(IssueUpdateRequest::IssueType(todo.issue_type.clone()));
+    }
+    updates
+}
+
+async fn file(board: &dyn IssueBoard, todo: &ToDo, template: &IssueTemplate) -> Result<BoardLink> {
+    let mut issue = template.issue(todo);
+    issue.number = Some(board.add_issue(issue.clone()).await?);
+    Ok(BoardLink {
+        board: board.get_repository_name(),
+        issue,
+    })
+}
+
+/// Brings `link` in line with `todo` unless its issue was changed by hand. Returns whether the
+/// link changed.
+async fn reconcile(
+    boards: &[&dyn IssueBoard],
+    link: &mut BoardLink,
+    todo: &ToDo,
+    report: &mut SyncReport,
+) -> Result<bool> {
+    let Some(id) = link.id().cloned() else {
+        return Ok(false);
+    };
+    let board = find_board(boards, &link.board)?;
+    let current = match board.get_issue(&id).await {
+        Ok(issue) => issue,
+        Err(e) if matches!(e.downcast_ref(), Some(BoardError::NotFound(_))) => {
+            report.diverged.push(Divergence {
+                board: link.board.clone(),
+                issue: id,
+                todo: todo.clone(),
+                differences: vec![String::from("issue no longer exists")],
+            });
+            return Ok(false);
+        }
+        Err(e) => return Err(e),
+    };
+    let by_hand = differences(&link.issue, &current);
+    if !by_hand.is_empty() {
+        report.diverged.push(Divergence {
+            board: link.board.clone(),
+            issue: id,
+            todo: todo.clone(),
+            differences: by_hand,
+        });
+        return Ok(false);
+    }
+    let updates = updates_for(&current, todo);
+    if updates.is_empty() {
+        return Ok(false);
+    }
+    let mut updated = current;
+    for update in &updates {
+        board.update_issue(&id, update).await?;
+        updated.apply(update);
+    }
+    link.issue = updated;
+    report.mirrored.push(link.clone());
+    Ok(true)
+}
+
+/// Syncs `todos`, the ToDos currently in the code, to `boards` and records the result in
+/// `tissue_box`.
+pub async fn sync(
+    tissue_box: &mut dyn TissueBoxRepository,
+    boards: &[&dyn IssueBoard],
+    router: &Router,
+    todos: &[ToDo],
+) -> Result<SyncReport> {
+    sync_with_template(tissue_box, boards, router, &IssueTemplate::default(), todos).await
+}
+
+/// `sync`, filing new issues from `template`.
+pub async fn sync_with_template(
+    tissue_box: &mut dyn TissueBoxRepository,
+    boards: &[&dyn IssueBoard],
+    router: &Router,
+    template: &IssueTemplate,
+    todos: &[ToDo],
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let mut upserts = Vec::new();
+    // Unfiled tissues that moved or got filed, which upserting wouldn't replace.
+    let mut replaced = Vec::new();
+    let tissues = tissue_box.get_tissues()?;
+
+    for todo in todos {
+        let fingerprint = todo.fingerprint();
+        let routed = router.route(&todo.submission.file_path);
+        let existing = tissues
+            .iter()
+            .find(|tissue| tissue.todo.fingerprint() == fingerprint);
+        if existing.is_none() && routed.is_empty() {
+            report.unrouted.push(todo.clone());
+            continue;
+        }
+
+        let mut tissue = existing
+            .cloned()
+            .unwrap_or_else(|| Tissue::new(todo.clone()));
+        let mut changed = tissue.todo != *todo;
+        if changed && existing.is_some() {
+            tissue.todo = todo.clone();
+            report.moved.push(tissue.clone());
+        }
+
+        for link in &mut tissue.links {
+            match reconcile(boards, link, todo, &mut report).await {
+                Ok(link_changed) => changed |= link_changed,
+                Err(e) => report.failed.push(SyncFailure {
+                    board: link.board.clone(),
+                    todo: todo.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        for board_name in routed {
+            if tissue.link(board_name).is_some() {
+                continue;
+            }
+            let filed = match find_board(boards, board_name) {
+                Ok(board) => file(board, todo, template).await,
+                Err(e) => Err(e),
+            };
+            match filed {
+                Ok(link) => {
+                    if existing.is_some() {
+                        report.mirrored.push(link.clone());
+                    }
+                    tissue.links.push(link);
+                    changed = true;
+                }
+                Err(e) => report.failed.push(SyncFailure {
+                    board: board_name.clone(),
+                    todo: todo.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+
+        if existing.is_none() {
+            // Filed nowhere yet; try again on the next sync.
+            if tissue.links.is_empty() {
+                continue;
+            }
+            report.created.push(tissue.clone());
+        }
+        if changed {
+            if let Some(existing) = existing.filter(|existing| !existing.is_same(&tissue)) {
+                replaced.push(existing.clone());
+            }
+            upserts.push(tissue);
+        }
+    }
+
+    let fingerprints: HashSet<String> = todos.iter().map(ToDo::fingerprint).collect();
+    for tissue in tissues {
+        if fingerprints.contains(&tissue.todo.fingerprint()) {
+            continue;
+        }
+        let mut still_open = Vec::new();
+        for link in &tissue.links {
+            let Some(id) = link.id() else {
+                continue;
+            };
+            let closed = match find_board(boards, &link.board) {
+                Ok(board) => {
+                    board
+                        .update_issue(id, &IssueUpdateRequest::State(IssueState::Closed))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            if let Err(e) = closed {
+                report.failed.push(SyncFailure {
+                    board: link.board.clone(),
+                    todo: tissue.todo.clone(),
+                    error: e.to_string(),
+                });
+                still_open.push(link.clone());
+            }
+        }
+        if still_open.is_empty() {
+            report.closed.push(tissue);
+        } else {
+            // Keep the mirrors that failed to close, to retry them on the next sync.
+            upserts.push(Tissue {
+                links: still_open,
+                ..tissue
+            });
+        }
+    }
+
+    if !replaced.is_empty() {
+        tissue_box.remove_tissues(&replaced)?;
+    }
+    if !upserts.is_empty() {
+        tissue_box.upsert_tissues(upserts)?;
+    }
+    if !report.closed.is_empty() {
+        tissue_box.remove_tissues(&report.closed)?;
+    }
+    Ok(report)
+}
+
+/// Files those of `todos` the Tissue Box doesn't know yet from `template`, leaving every other
+/// tissue alone. Unlike `sync`, `todos` may be a subset of the ToDos in the code.
+pub async fn file_new(
+    tissue_box: &mut dyn TissueBoxRepository,
+    boards: &[&dyn IssueBoard],
+    router: &Router,
+    template: &IssueTemplate,
+    todos: &[ToDo],
+) -> Result<SyncReport> {
+    let mut report = SyncReport::default();
+    let known: HashSet<String> = tissue_box
+        .get_tissues()?
+        .iter()
+        .map(|tissue| tissue.todo.fingerprint())
+        .collect();
+    for todo in todos {
+        if known.contains(&todo.fingerprint()) {
+            continue;
+        }
+        let routed = router.route(&todo.submission.file_path);
+        if routed.is_empty() {
+            report.unrouted.push(todo.clone());
+            continue;
+        }
+        let mut tissue = Tissue::new(todo.clone());
+        for board_name in routed {
+            let filed = match find_board(boards, board_name) {
+                Ok(board) => file(board, todo, template).await,
+                Err(e) => Err(e),
+            };
+            match filed {
+                Ok(link) => tissue.links.push(link),
+                Err(e) => report.failed.push(SyncFailure {
+                    board: board_name.clone(),
+                    todo: todo.clone(),
+                    error: e.to_string(),
+                }),
+            }
+        }
+        if !tissue.links.is_empty() {
+            report.created.push(tissue);
+        }
+    }
+    if !report.created.is_empty() {
+        tissue_box.upsert_tissues(report.created.clone())?;
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        issue_repository::{BoardError, IssueId},
+        routing::RoutingRule,
+        test_fixtures::todo,
+        testing::{BoardCall, InMemoryBoard, InMemoryTissueBox},
+    };
+
+    fn mirroring_router() -> Router {
+        Router::new(&["default -> github:org/repo, jira:INT".parse().unwrap()]).unwrap()
+    }
+
+    fn router() -> Router {
+        let rules: Vec<RoutingRule> = [
+            "services/billing/** -> jira:BILL",
+            "default -> github:org/repo",
+        ]
+        .iter()
+        .map(|rule| rule.parse().unwrap())
+        .collect();
+        Router::new(&rules).unwrap()
+    }
+
+    #[tokio::test]
+    async fn files_todos_on_their_routed_board() {
+        let jira = InMemoryBoard::new("jira:BILL");
+        let github = InMemoryBoard::new("github:org/repo");
+        let mut tissue_box = InMemoryTissueBox::new();
+        let todos = vec![
+            todo("services/billing/invoice.rs", 3, "round totals"),
+            todo("src/main.rs", 7, "parse flags"),
+        ];
+
+        let report = sync(&mut tissue_box, &[&jira, &github], &router(), &todos)
+            .await
+            .unwrap();
+
+        assert_eq!(report.created.len(), 2);
+        assert_eq!(jira.issues()[0].title, "round totals");
+        assert_eq!(github.issues()[0].title, "parse flags");
+        let tissues = tissue_box.tissues();
+        assert_eq!(tissues[0].links[0].board, "jira:BILL");
+        assert_eq!(tissues[1].links[0].board, "github:org/repo");
+        assert_eq!(tissues[1].links[0].id(), Some(&IssueId::Number(1)));
+    }
+
+    #[tokio::test]
+    async fn updates_moved_and_closes_removed_todos() {
+        let github = InMemoryBoard::new("github:org/repo");
+        let mut tissue_box = InMemoryTissueBox::new();
+        let todos = vec![
+            todo("src/main.rs", 7, "parse flags"),
+            todo("src/main.rs", 9, "handle errors"),
+        ];
+        sync(&mut tissue_box, &[&github], &router(), &todos)
+            .await
+            .unwrap();
+
+        let report = sync(
+            &mut tissue_box,
+            &[&github],
+            &router(),
+            &[todo("src/main.rs", 12, "parse flags")],
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.moved.len(), 1);
+        assert_eq!(report.closed.len(), 1);
+        assert_eq!(github.issues()[1].state, "closed");
+        let tissues = tissue_box.tissues();
+        assert_eq!(tissues.len(), 1);
+        assert_eq!(tissues[0].todo.submission.line_number, 12);
+    }
+
+    #[tokio::test]
+    async fn replaces_unfiled_tissues_once_filed() {
+        let github = InMemoryBoard::new("github:org/repo");
+        let mut tissue_box = InMemoryTissueBox::new().with_tissues(vec![
+            Tissue::new(todo("src/main.rs", 7, "parse flags")),
+            Tissue::new(todo("src/main.rs", 9, "parse flags")),
+        ]);
+
+        sync(
+            &mut tissue_box,
+            &[&github],
+            &router(),
+            &[todo("src/main.rs", 8, "parse flags")],
+        )
+        .await
+        .unwrap();
+
+        let tissues = tissue_box.tissues();
+        assert_eq!(tissues.len(), 2);
+        assert_eq!(tissues[0].todo.submission.line_number, 9);
+        assert!(tissues[0].links.is_empty());
+        assert_eq!(tissues[1].todo.submission.line_number, 8);
+        assert_eq!(tissues[1].links.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn a_failing_board_does_not_stop_the_others() {
+        let jira = InMemoryBoard::new("jira:BILL");
+        jira.fail_next(BoardError::Timeout);
+        let github = InMemoryBoard::new("github:org/repo");
+        let mut tissue_box = InMemoryTissueBox::new();
+        let todos = vec![
+            todo("services/billing/invoice.rs", 3, "round totals"),
+            todo("src/main.rs", 7, "parse flags"),
+        ];
+
+        let report = sync(&mut tissue_box, &[&jira, &github], &router(), &todos)
+            .await
+            .unwrap();
+
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].board, "jira:BILL");
+        assert_eq!(report.created.len(), 1);
+        assert!(matches!(github.calls()[0], BoardCall::AddIssue(_)));
+
+        let report = sync(
+            &mut tissue_box,
+            &[&github],
+            &Router::new(&[]).unwrap(),
+            &todos,
+        )
+        .await
+        .unwrap();
+        assert_eq!(report.unrouted.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn mirrors_todos_and_adds_newly_routed_boards() {
+        let github = InMemoryBoard::new("github:org/repo");
+        let jira = InMemoryBoard::new("jira:INT");
+        let mut tissue_box = InMemoryTissueBox::new();
+        let todos = vec![todo("src/main.rs", 7, "parse flags")];
+        sync(&mut tissue_box, &[&github, &jira], &router(), &todos)
+            .await
+            .unwrap();
+
+        let report = sync(
+            &mut tissue_box,
+            &[&github, &jira],
+            &mirroring_router(),
+            &todos,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.mirrored.len(), 1);
+        assert_eq!(report.mirrored[0].board, "jira:INT");
+        let links = &tissue_box.tissues()[0].links;
+        assert_eq!(links.len(), 2);
+        assert_eq!(jira.issues()[0].title, "parse flags");
+
+        sync(&mut tissue_box, &[&github, &jira], &mirroring_router(), &[])
+            .await
+            .unwrap();
+        assert_eq!(github.issues()[0].state, "closed");
+        assert_eq!(jira.issues()[0].state, "closed");
+        assert!(tissue_box.tissues().is_empty());
+    }
+
+    #[tokio::test]
+    async fn reports_mirrors_changed_by_hand_instead_of_overwriting_them() {
+        let github = InMemoryBoard::new("github:org/repo");
+        let jira = InMemoryBoard::new("jira:INT");
+        let mut tissue_box = InMemoryTissueBox::new();
+        let todos = vec![todo("src/main.rs", 7, "parse flags")];
+        sync(
+            &mut tissue_box,
+            &[&github, &jira],
+            &mirroring_router(),
+            &todos,
+        )
+        .await
+        .unwrap();
+        jira.update_issue(
+            &IssueId::Number(1),
+            &IssueUpdateRequest::State(IssueState::Closed),
+        )
+        .await
+        .unwrap();
+
+        let report = sync(
+            &mut tissue_box,
+            &[&github, &jira],
+            &mirroring_router(),
+            &todos,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.diverged.len(), 1);
+        assert_eq!(report.diverged[0].board, "jira:INT");
+        assert_eq!(
+            report.diverged[0].differences,
+            vec!["state: open -> closed"]
+        );
+        assert_eq!(jira.issues()[0].state, "closed");
+        assert!(report.mirrored.is_empty());
+    }
+
+    #[tokio::test]
+    async fn files_only_new_todos_without_closing_the_rest() {
+        let github = InMemoryBoard::new("github:org/repo");
+        let mut tissue_box = InMemoryTissueBox::new();
+        sync(
+            &mut tissue_box,
+            &[&github],
+            &router(),
+            &[todo("src/main.rs", 7, "parse flags")],
+        )
+        .await
+        .unwrap();
+
+        let todos = vec![
+            todo("src/main.rs", 9, "parse flags"),
+            todo("src/lib.rs", 2, "retry requests"),
+            todo("services/billing/invoice.rs", 3, "round totals"),
+        ];
+        let report = file_new(
+            &mut tissue_box,
+            &[&github],
+            &router(),
+            &IssueTemplate::default(),
+            &todos,
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(report.created.len(), 1);
+        assert_eq!(report.created[0].todo.description, "retry requests");
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].board, "jira:BILL");
+        assert_eq!(github.issues().len(), 2);
+        assert_eq!(tissue_box.get_tissues().unwrap().len(), 2);
+    }
+}
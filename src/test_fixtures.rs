@@ -0,0 +1,83 @@
+//! ToDos, tissues and git repositories shared by the tests of several modules.
+use std::{path::Path, process::Command};
+
+use crate::{
+    issue_repository::{Issue, IssueId},
+    tissuebox_repository::{BoardLink, Tissue},
+    todofinder::{self, IssueType, Submission, ToDo},
+};
+
+/// When git blamed the fixture ToDos on their issuer, OthelloEngineer.
+pub const FIXTURE_DATE: &str = "2024-03-24 02:45:51 +0100";
+
+/// Parses the ToDo comment `line`, found on line `line_number` of `file_path` and blamed on
+/// OthelloEngineer at `date`. Panics if it isn't a ToDo.
+pub fn parsed_todo(line: &str, file_path: &str, line_number: usize, date: &str) -> ToDo {
+    todofinder::parse_submission(Submission {
+        line_number,
+        line: line.to_string(),
+        file_path: file_path.to_string(),
+        issuer: "OthelloEngineer".to_string(),
+        date: date.to_string(),
+    })
+    .unwrap()
+}
+
+/// An unassigned bug on line `line_number` of `file_path`, blamed on OthelloEngineer.
+pub fn todo(file_path: &str, line_number: usize, description: &str) -> ToDo {
+    ToDo {
+        submission: Submission {
+            line_number,
+            line: format!("// TODO bug: {}", description),
+            file_path: file_path.to_string(),
+            issuer: "OthelloEngineer".to_string(),
+            date: FIXTURE_DATE.to_string(),
+        },
+        description: description.to_string(),
+        assigned: None,
+        issue_type: IssueType::Bug,
+    }
+}
+
+/// The tissue of the bug on line 1 of `file_path`, linked to issue `number` on `local:issues`
+/// if there is one.
+pub fn tissue(number: Option<u32>, file_path: &str, description: &str) -> Tissue {
+    let todo = todo(file_path, 1, description);
+    let links = number
+        .map(|number| BoardLink {
+            board: "local:issues".to_string(),
+            issue: Issue {
+                number: Some(IssueId::Number(number)),
+                ..Issue::from(&todo)
+            },
+        })
+        .into_iter()
+        .collect();
+    Tissue {
+        links,
+        ..Tissue::new(todo)
+    }
+}
+
+/// Runs git with `args` in `directory`, committing as jolee18. Panics if git fails.
+pub fn git(directory: &Path, args: &[&str]) {
+    let status = Command::new("git")
+        .current_dir(directory)
+        .args([
+            "-c",
+            "user.name=jolee18",
+            "-c",
+            "user.email=jolee18@example.com",
+        ])
+        .args(args)
+        .status()
+        .unwrap();
+    assert!(status.success(), "git {} failed", args.join(" "));
+}
+
+/// Makes `directory` a git repository with an empty first commit, which a Tissue Box written
+/// there records as its last commit.
+pub fn init_repository(directory: &Path) {
+    git(directory, &["init", "-q"]);
+    git(directory, &["commit", "-q", "--allow-empty", "-m", "init"]);
+}
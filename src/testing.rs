@@ -0,0 +1,434 @@
+//! In-memory fakes of `IssueBoard` and `TissueBoxRepository` for testing code built on tissue,
+//! available with the `testing` feature.
+//!
+//! Both record every call they receive and can be told to fail the next call, so callers can
+//! exercise their error handling without a network or a tissue box on disk.
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use async_trait::async_trait;
+use color_eyre::eyre::Result;
+
+use crate::{
+    issue_repository::{BoardError, Issue, IssueBoard, IssueId, IssueUpdateRequest},
+    tissuebox_repository::{Tissue, TissueBoxRepository},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum BoardCall {
+    GetIssues,
+    GetIssue(IssueId),
+    AddIssue(Issue),
+    UpdateIssue(IssueId, IssueUpdateRequest),
+    AddComment(IssueId, String),
+}
+
+#[derive(Debug, Default)]
+struct BoardState {
+    issues: Vec<Issue>,
+    comments: Vec<(IssueId, String)>,
+    last_number: u32,
+    calls: Vec<BoardCall>,
+    failures: VecDeque<BoardError>,
+}
+
+/// `IssueBoard` that keeps its issues in memory and numbers new ones sequentially.
+#[derive(Debug)]
+pub struct InMemoryBoard {
+    name: String,
+    state: Mutex<BoardState>,
+}
+
+impl InMemoryBoard {
+    pub fn new(name: &str) -> InMemoryBoard {
+        InMemoryBoard {
+            name: name.to_string(),
+            state: Mutex::new(BoardState::default()),
+        }
+    }
+
+    /// Seeds the board. Issues without a number get the next free one.
+    pub fn with_issues(self, issues: Vec<Issue>) -> InMemoryBoard {
+        {
+            let mut state = self.state.lock().unwrap();
+            for issue in issues {
+                state.insert(issue);
+            }
+        }
+        self
+    }
+
+    /// Makes the next call fail with `error`. Queued failures are used up in order.
+    pub fn fail_next(&self, error: BoardError) {
+        self.state.lock().unwrap().failures.push_back(error);
+    }
+
+    pub fn calls(&self) -> Vec<BoardCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn issues(&self) -> Vec<Issue> {
+        self.state.lock().unwrap().issues.clone()
+    }
+
+    pub fn comments(&self) -> Vec<(IssueId, String)> {
+        self.state.lock().unwrap().comments.clone()
+    }
+
+    fn record(&self, call: BoardCall) -> Result<MutexGuard<'_, BoardState>> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+        match state.failures.pop_front() {
+            Some(error) => Err(error.into()),
+            None => Ok(state),
+        }
+    }
+}
+
+impl BoardState {
+    fn insert(&mut self, mut issue: Issue) -> IssueId {
+        let id = match &issue.number {
+            Some(id) => id.clone(),
+            None => IssueId::Number(self.last_number + 1),
+        };
+        if let IssueId::Number(number) = id {
+            self.last_number = self.last_number.max(number);
+        }
+        issue.number = Some(id.clone());
+        self.issues.push(issue);
+        id
+    }
+
+    fn find(&mut self, id: &IssueId) -> Result<&mut Issue> {
+        self.issues
+            .iter_mut()
+            .find(|issue| issue.number.as_ref() == Some(id))
+            .ok_or_else(|| BoardError::NotFound(id.clone()).into())
+    }
+}
+
+#[async_trait]
+impl IssueBoard for InMemoryBoard {
+    async fn get_issues(&self) -> Result<Vec<Issue>> {
+        let state = self.record(BoardCall::GetIssues)?;
+        Ok(state.issues.clone())
+    }
+
+    async fn get_issue(&self, id: &IssueId) -> Result<Issue> {
+        let mut state = self.record(BoardCall::GetIssue(id.clone()))?;
+        Ok(state.find(id)?.clone())
+    }
+
+    async fn add_issue(&self, issue: Issue) -> Result<IssueId> {
+        let mut state = self.record(BoardCall::AddIssue(issue.clone()))?;
+        Ok(state.insert(Issue {
+            number: None,
+            ..issue
+        }))
+    }
+
+    async fn update_issue(&self, id: &IssueId, update: &IssueUpdateRequest) -> Result<()> {
+        let mut state = self.record(BoardCall::UpdateIssue(id.clone(), update.clone()))?;
+        state.find(id)?.apply(update);
+        Ok(())
+    }
+
+    async fn add_comment(&self, id: &IssueId, body: &str) -> Result<()> {
+        let mut state = self.record(BoardCall::AddComment(id.clone(), body.to_string()))?;
+        state.find(id)?;
+        state.comments.push((id.clone(), body.to_string()));
+        Ok(())
+    }
+
+    fn get_repository_name(&self) -> String {
+        self.name.clone()
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TissueBoxCall {
+    GetTissues,
+    GetTissuesInFile(String),
+    GetTissueByIssue(String, IssueId),
+    GetTissueByFingerprint(String),
+    UpsertTissues(Vec<Tissue>),
+    RemoveTissues(Vec<Tissue>),
+    GetBoards,
+    AddBoard(String),
+    RemoveBoard(String),
+}
+
+#[derive(Debug, Default)]
+struct TissueBoxState {
+    tissues: Vec<Tissue>,
+    boards: Vec<String>,
+    calls: Vec<TissueBoxCall>,
+    failures: VecDeque<String>,
+}
+
+/// `TissueBoxRepository` kept in memory. Clones share their state, so keep one handle around to
+/// inspect what the code under test did with the others.
+#[derive(Clone, Debug, Default)]
+pub struct InMemoryTissueBox {
+    state: Arc<Mutex<TissueBoxState>>,
+}
+
+impl InMemoryTissueBox {
+    pub fn new() -> InMemoryTissueBox {
+        InMemoryTissueBox::default()
+    }
+
+    pub fn with_tissues(self, tissues: Vec<Tissue>) -> InMemoryTissueBox {
+        self.state.lock().unwrap().tissues.extend(tissues);
+        self
+    }
+
+    /// Makes the next call fail with `message`. Queued failures are used up in order.
+    pub fn fail_next(&self, message: &str) {
+        self.state
+            .lock()
+            .unwrap()
+            .failures
+            .push_back(message.to_string());
+    }
+
+    pub fn calls(&self) -> Vec<TissueBoxCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn tissues(&self) -> Vec<Tissue> {
+        self.state.lock().unwrap().tissues.clone()
+    }
+
+    pub fn boards(&self) -> Vec<String> {
+        self.state.lock().unwrap().boards.clone()
+    }
+
+    fn record(&self, call: TissueBoxCall) -> Result<MutexGuard<'_, TissueBoxState>> {
+        let mut state = self.state.lock().unwrap();
+        state.calls.push(call);
+        match state.failures.pop_front() {
+            Some(message) => Err(color_eyre::Report::msg(message)),
+            None => Ok(state),
+        }
+    }
+}
+
+impl TissueBoxRepository for InMemoryTissueBox {
+    fn get_tissues(&self) -> Result<Vec<Tissue>> {
+        let state = self.record(TissueBoxCall::GetTissues)?;
+        Ok(state.tissues.clone())
+    }
+
+    fn get_tissues_in_file(&self, file_path: &str) -> Result<Vec<Tissue>> {
+        let state = self.record(TissueBoxCall::GetTissuesInFile(file_path.to_string()))?;
+        Ok(state
+            .tissues
+            .iter()
+            .filter(|tissue| tissue.todo.submission.file_path == file_path)
+            .cloned()
+            .collect())
+    }
+
+    fn get_tissue_by_issue(&self, board: &str, number: &IssueId) -> Result<Option<Tissue>> {
+        let state = self.record(TissueBoxCall::GetTissueByIssue(
+            board.to_string(),
+            number.clone(),
+        ))?;
+        Ok(state
+            .tissues
+            .iter()
+            .find(|tissue| tissue.is_linked_to(board, number))
+            .cloned())
+    }
+
+    fn get_tissue_by_fingerprint(&self, fingerprint: &str) -> Result<Option<Tissue>> {
+        let state = self.record(TissueBoxCall::GetTissueByFingerprint(
+            fingerprint.to_string(),
+        ))?;
+        Ok(state
+            .tissues
+            .iter()
+            .find(|tissue| tissue.todo.fingerprint() == fingerprint)
+            .cloned())
+    }
+
+    fn upsert_tissues(&mut self, tissues: Vec<Tissue>) -> Result<()> {
+        let mut state = self.record(TissueBoxCall::UpsertTissues(tissues.clone()))?;
+        for tissue in tissues {
+            match state.tissues.iter_mut().find(|t| t.is_same(&tissue)) {
+                Some(existing) => *existing = tissue,
+                None => state.tissues.push(tissue),
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_tissues(&mut self, tissues: &[Tissue]) -> Result<Vec<Tissue>> {
+        let mut state = self.record(TissueBoxCall::RemoveTissues(tissues.to_vec()))?;
+        let (removed, kept) = state
+            .tissues
+            .drain(..)
+            .partition(|t| tissues.iter().any(|tissue| t.is_same(tissue)));
+        state.tissues = kept;
+        Ok(removed)
+    }
+
+    fn get_boards(&self) -> Result<Vec<String>> {
+        let state = self.record(TissueBoxCall::GetBoards)?;
+        Ok(state.boards.clone())
+    }
+
+    fn add_board(&mut self, issue_board: &dyn IssueBoard) -> Result<()> {
+        let name = issue_board.get_repository_name();
+        let mut state = self.record(TissueBoxCall::AddBoard(name.clone()))?;
+        if !state.boards.contains(&name) {
+            state.boards.push(name);
+        }
+        Ok(())
+    }
+
+    fn remove_board(&mut self, board_name: &str) -> Result<String> {
+        let mut state = self.record(TissueBoxCall::RemoveBoard(board_name.to_string()))?;
+        let idx = state
+            .boards
+            .iter()
+            .position(|name| name == board_name)
+            .ok_or_else(|| color_eyre::Report::msg(format!("Board {} not found", board_name)))?;
+        Ok(state.boards.remove(idx))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        issue_repository::{IssueState, Label, User},
+        test_fixtures::tissue,
+    };
+
+    fn issue(title: &str) -> Issue {
+        Issue {
+            number: None,
+            title: title.to_string(),
+            author: User {
+                name: "OthelloEngineer".to_string(),
+            },
+            assignee: None,
+            issue_type: vec![Label {
+                name: "bug".to_string(),
+            }],
+            state: "open".to_string(),
+            body: None,
+            source: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn board_numbers_issues_and_logs_calls() {
+        let board = InMemoryBoard::new("memory").with_issues(vec![issue("seeded")]);
+        let id = board.add_issue(issue("added")).await.unwrap();
+        board
+            .update_issue(&id, &IssueUpdateRequest::State(IssueState::Closed))
+            .await
+            .unwrap();
+
+        assert_eq!(id, IssueId::Number(2));
+        assert_eq!(board.get_issue(&id).await.unwrap().state, "closed");
+        assert_eq!(
+            board.calls(),
+            vec![
+                BoardCall::AddIssue(issue("added")),
+                BoardCall::UpdateIssue(id.clone(), IssueUpdateRequest::State(IssueState::Closed)),
+                BoardCall::GetIssue(id),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn board_injects_failures() {
+        let board = InMemoryBoard::new("memory");
+        board.fail_next(BoardError::RateLimited);
+        board.fail_next(BoardError::Timeout);
+
+        let first = board.get_issues().await.unwrap_err();
+        let second = board.get_issues().await.unwrap_err();
+        let missing = board.get_issue(&IssueId::from(404)).await.unwrap_err();
+
+        assert_eq!(first.downcast_ref(), Some(&BoardError::RateLimited));
+        assert_eq!(second.downcast_ref(), Some(&BoardError::Timeout));
+        assert_eq!(
+            missing.downcast_ref(),
+            Some(&BoardError::NotFound(IssueId::from(404)))
+        );
+        assert!(board.get_issues().await.is_ok());
+    }
+
+    #[test]
+    fn tissue_box_shares_state_between_handles() {
+        let tissue_box = InMemoryTissueBox::new().with_tissues(vec![tissue(
+            Some(1),
+            "src/main.rs",
+            "implement this function",
+        )]);
+        let mut handle = tissue_box.clone();
+        handle
+            .upsert_tissues(vec![tissue(
+                Some(2),
+                "src/main.rs",
+                "implement this function",
+            )])
+            .unwrap();
+        let removed = handle
+            .remove_tissues(&[tissue(Some(1), "src/main.rs", "implement this function")])
+            .unwrap();
+        let fingerprint = removed[0].todo.fingerprint();
+        let found = handle.get_tissue_by_fingerprint(&fingerprint).unwrap();
+
+        assert_eq!(
+            removed,
+            vec![tissue(Some(1), "src/main.rs", "implement this function")]
+        );
+        assert_eq!(
+            tissue_box.tissues(),
+            vec![tissue(Some(2), "src/main.rs", "implement this function")]
+        );
+        assert_eq!(found, tissue_box.tissues().pop());
+        assert_eq!(
+            tissue_box.calls(),
+            vec![
+                TissueBoxCall::UpsertTissues(vec![tissue(
+                    Some(2),
+                    "src/main.rs",
+                    "implement this function"
+                )]),
+                TissueBoxCall::RemoveTissues(vec![tissue(
+                    Some(1),
+                    "src/main.rs",
+                    "implement this function"
+                )]),
+                TissueBoxCall::GetTissueByFingerprint(fingerprint),
+            ]
+        );
+    }
+
+    #[test]
+    fn tissue_box_injects_failures() {
+        let mut tissue_box = InMemoryTissueBox::new();
+        tissue_box.fail_next("disk full");
+
+        let error = tissue_box
+            .upsert_tissues(vec![tissue(
+                Some(1),
+                "src/main.rs",
+                "implement this function",
+            )])
+            .unwrap_err();
+
+        assert_eq!(error.to_string(), "disk full");
+        assert!(tissue_box.tissues().is_empty());
+        assert!(tissue_box.get_tissues().unwrap().is_empty());
+    }
+}
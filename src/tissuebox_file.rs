@@ -1,117 +1,682 @@
-use core::fmt;
-use std::{fmt::Formatter, fs};
+use std::{
+    fs,
+    io::{ErrorKind, Write},
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant},
+};
 
 use crate::{
     githandler,
     issue_repository::IssueBoard,
-    tissuebox_repository::{Tissue, TissueBoxRepository},
+    routing::{Router, RoutingRule},
+    tissuebox_migration::{self, MigrationContext, MigrationReport, CURRENT_SCHEMA_VERSION},
+    tissuebox_repository::{Tissue, TissueBoxError, TissueBoxRepository},
 };
-use async_trait::async_trait;
 use color_eyre::eyre::Result;
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize)]
+const DEFAULT_LOCK_TIMEOUT: Duration = Duration::from_secs(10);
+const LOCK_RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct TissueBoxFile {
+    #[serde(default)]
+    pub schema_version: u64,
     pub path: String,
     pub last_commit: String,
+    /// Bumped on every write, so a writer holding a stale copy can detect that it lost a race.
+    #[serde(default)]
+    pub version: u64,
     pub boards: Vec<String>,
+    /// Which board each ToDo is filed on. See `routing::Router`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub routes: Vec<RoutingRule>,
     pub tissues: Vec<Tissue>,
+    #[serde(skip, default = "default_lock_timeout")]
+    lock_timeout: Duration,
+}
+
+fn default_lock_timeout() -> Duration {
+    DEFAULT_LOCK_TIMEOUT
+}
+
+/// Flushes the directory entry of `file_path` to disk, so a rename into it survives a crash.
+/// Directories can't be opened for that outside Unix, where the rename is left to the OS.
+fn sync_directory(file_path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        let directory = match file_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        fs::File::open(directory)?.sync_all()?;
+    }
+    #[cfg(not(unix))]
+    let _ = file_path;
+    Ok(())
+}
+
+/// Advisory lock held while a process reads, modifies and writes the box. Removed on drop.
+struct TissueBoxLock {
+    path: PathBuf,
+}
+
+impl TissueBoxLock {
+    fn acquire(path: PathBuf, timeout: Duration) -> Result<TissueBoxLock> {
+        let started = Instant::now();
+        loop {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    // Only informational, to help whoever finds a stale lock.
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(TissueBoxLock { path });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    if started.elapsed() >= timeout {
+                        return Err(TissueBoxError::Locked {
+                            lock_path: path.display().to_string(),
+                            waited: timeout,
+                        }
+                        .into());
+                    }
+                    thread::sleep(LOCK_RETRY_INTERVAL);
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+}
+
+impl Drop for TissueBoxLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
 }
 
 impl TissueBoxFile {
-    pub fn new(path: String, boards: Vec<Box<dyn IssueBoard>>) -> TissueBoxFile {
-        let last_commit = String::from("None");
-        let boards = boards
-            .iter()
-            .map(|board| board.get_repository_name())
-            .collect();
-        let tissue_box = TissueBoxFile {
-            path: path.clone(),
-            last_commit,
-            boards,
+    /// Creates an empty box in the directory `path`, creating the directory if needed. Fails if a
+    /// box already exists there.
+    pub fn init(path: &str, boards: &[&dyn IssueBoard]) -> Result<TissueBoxFile> {
+        fs::create_dir_all(path).map_err(|e| {
+            color_eyre::Report::msg(format!(
+                "Can't create the Tissue Box directory {}: {}",
+                path, e
+            ))
+        })?;
+        let mut tissue_box = TissueBoxFile {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            path: path.to_string(),
+            last_commit: String::from("None"),
+            version: 0,
+            boards: boards
+                .iter()
+                .map(|board| board.get_repository_name())
+                .collect(),
+            routes: vec![],
             tissues: vec![],
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
         };
+        let _lock = TissueBoxLock::acquire(
+            PathBuf::from(format!("{}.lock", tissue_box.file_path())),
+            DEFAULT_LOCK_TIMEOUT,
+        )?;
+        if PathBuf::from(tissue_box.file_path()).exists() {
+            return Err(color_eyre::Report::msg(format!(
+                "A Tissue Box already exists at {}",
+                tissue_box.file_path()
+            )));
+        }
+        tissue_box.save()?;
+        Ok(tissue_box)
+    }
+
+    /// Opens the box in the directory `path`, failing with `TissueBoxError::NotFound` if there
+    /// is none.
+    pub fn open(path: &str) -> Result<TissueBoxFile> {
+        let file_path = format!("{}/tissue_box.json", path);
+        if !PathBuf::from(&file_path).exists() {
+            return Err(TissueBoxError::NotFound { path: file_path }.into());
+        }
+        let (mut tissue_box, _) = Self::read(&file_path)?;
+        tissue_box.path = path.to_string();
+        Ok(tissue_box)
+    }
+
+    /// How long a write waits for another process to release the box's lock.
+    pub fn with_lock_timeout(mut self, lock_timeout: Duration) -> TissueBoxFile {
+        self.lock_timeout = lock_timeout;
+        self
+    }
+
+    /// Re-reads the box from disk, e.g. after a `TissueBoxError::Conflict`.
+    pub fn reload(&mut self) -> Result<()> {
+        *self = self.load()?;
+        Ok(())
+    }
+
+    fn file_path(&self) -> String {
+        format!("{}/tissue_box.json", &self.path)
+    }
 
-        if fs::metadata(&path).is_err() {
-            //TODO improvement: This dialoguer should be moved to UI-layer
-            let user_input = dialoguer::Confirm::new()
-                .with_prompt("No Tissue Box was found. \n Do you want to create a new Tissue Box?")
-                .interact()
-                .unwrap();
-            if !user_input {
-                println!("Exiting due to user input");
-                std::process::exit(0);
+    /// Upgrades the box in `path` to the current schema and writes it back if anything changed.
+    pub fn migrate(path: &str) -> Result<MigrationReport> {
+        let file_path = format!("{}/tissue_box.json", path);
+        let _lock = TissueBoxLock::acquire(
+            PathBuf::from(format!("{}.lock", file_path)),
+            DEFAULT_LOCK_TIMEOUT,
+        )?;
+        let (mut tissue_box, report) = Self::read(&file_path)?;
+        if !report.is_empty() {
+            tissue_box.path = path.to_string();
+            tissue_box.save()?;
+        }
+        Ok(report)
+    }
+
+    /// Adds `rule`, replacing any rule for the same pattern.
+    pub fn add_route(&mut self, rule: RoutingRule) -> Result<()> {
+        Router::new(std::slice::from_ref(&rule))?;
+        self.modify(|tissue_box| {
+            tissue_box
+                .routes
+                .retain(|route| route.pattern != rule.pattern);
+            tissue_box.routes.push(rule);
+            Ok(())
+        })
+    }
+
+    /// Router for this box's rules. A box with a single board and no rules sends everything to
+    /// that board.
+    pub fn router(&self) -> Result<Router> {
+        match (self.routes.is_empty(), self.boards.as_slice()) {
+            (true, [board]) => Ok(Router::single(board)),
+            _ => Router::new(&self.routes),
+        }
+    }
+
+    /// Reads the box stored at `file_path`, which need not be named `tissue_box.json`, e.g. one
+    /// of the versions git hands to the merge driver.
+    pub(crate) fn read(file_path: &str) -> Result<(TissueBoxFile, MigrationReport)> {
+        let file = fs::File::open(file_path)?;
+        let mut value: serde_json::Value = serde_json::from_reader(file)?;
+        let directory = Path::new(file_path)
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let context = MigrationContext::for_directory(directory);
+        let report = tissuebox_migration::migrate(&mut value, &context)?;
+        Ok((serde_json::from_value(value)?, report))
+    }
+
+    /// Reads the box as it currently is on disk, migrated to the current schema.
+    fn load(&self) -> Result<TissueBoxFile> {
+        let (mut tissue_box, _) = Self::read(&self.file_path())?;
+        tissue_box.path = self.path.clone();
+        tissue_box.lock_timeout = self.lock_timeout;
+        Ok(tissue_box)
+    }
+
+    /// Writes to a temporary file next to the box, flushes it to disk and renames it into place,
+    /// so a crash never leaves a half-written box behind or loses the rename.
+    fn save(&mut self) -> Result<()> {
+        let file_path = self.file_path();
+        let temp_path = format!("{}.{}.tmp", file_path, std::process::id());
+        let contents = self.serialise()?;
+        let written = fs::File::create(&temp_path)
+            .and_then(|mut file| {
+                file.write_all(contents.as_bytes())?;
+                file.sync_all()
+            })
+            .and_then(|_| fs::rename(&temp_path, &file_path));
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path);
+            return Err(e.into());
+        }
+        sync_directory(Path::new(&file_path))?;
+        Ok(())
+    }
+
+    /// Sorts boards and tissues and pretty-prints the box, one field per line, so that branches
+    /// adding different ToDos touch different lines and merge cleanly.
+    pub(crate) fn serialise(&mut self) -> Result<String> {
+        self.boards.sort();
+        self.boards.dedup();
+        self.tissues.sort_by(|a, b| {
+            let key = |t: &Tissue| {
+                (
+                    t.todo.submission.file_path.clone(),
+                    t.todo.submission.line_number,
+                    t.todo.description.clone(),
+                )
+            };
+            key(a).cmp(&key(b))
+        });
+        Ok(serde_json::to_string_pretty(self)? + "\n")
+    }
+
+    /// Loads the box under its lock, applies `change` and writes it back, keeping `self` in sync
+    /// with disk. Fails with `TissueBoxError::Conflict` if the box changed since `self` was read.
+    fn modify<T>(&mut self, change: impl FnOnce(&mut TissueBoxFile) -> Result<T>) -> Result<T> {
+        let _lock = TissueBoxLock::acquire(
+            PathBuf::from(format!("{}.lock", self.file_path())),
+            self.lock_timeout,
+        )?;
+        let mut tissue_box = self.load()?;
+        if tissue_box.version != self.version {
+            return Err(TissueBoxError::Conflict {
+                expected: self.version,
+                found: tissue_box.version,
             }
-            fs::write(
-                format!("{}/tissue_box.json", &path),
-                serde_json::to_string(&tissue_box)
-                    .expect("couldn't convert TissueBox Struct to json :/"),
-            )
-            .expect("Failed to create file");
+            .into());
         }
-        tissue_box
+        let result = change(&mut tissue_box)?;
+        tissue_box.last_commit = githandler::head_commit(Path::new(&tissue_box.path))?;
+        tissue_box.version += 1;
+        tissue_box.save()?;
+        *self = tissue_box;
+        Ok(result)
     }
 }
 
-#[async_trait]
 impl TissueBoxRepository for TissueBoxFile {
-    fn add_tissues(self, tissues: Vec<&Tissue>) -> Result<()> {
-        let file_path = format!("{}/tissue_box.json", &self.path);
-        let file = fs::OpenOptions::new()
-            .write(true)
-            .read(true)
-            .open(file_path.clone())
-            .expect("Failed to open file");
-
-        let mut tissue_box: TissueBoxFile = serde_json::from_reader(&file).expect("Failed to read");
-        tissue_box.tissues.extend(tissues);
-        tissue_box.last_commit =
-            githandler::get_last_commit_hash().expect("Failed to get commit hash");
-
-        fs::write(
-            file_path,
-            serde_json::to_string(&tissue_box).expect("Failed to convert to json"),
-        )
-        .expect("Failed to update Tissue Box file");
-        Ok(())
+    fn get_tissues(&self) -> Result<Vec<Tissue>> {
+        Ok(self.load()?.tissues)
     }
-    fn remove_tissues(self, tissues: Vec<Tissue>) -> Result<Vec<Tissue>> {
-        let file_path = format!("{}/tissue_box.json", &self.path);
-        let file = fs::OpenOptions::new()
-            .write(true)
-            .read(true)
-            .open(file_path.clone())
-            .expect("Failed to open file");
-
-        let mut tissue_box: TissueBoxFile = serde_json::from_reader(&file).expect("Failed to read");
-        let mut removed_tissues = Vec::new();
-        for tissue in tissues {
-            let idx = tissue_box
+
+    fn upsert_tissues(&mut self, tissues: Vec<Tissue>) -> Result<()> {
+        self.modify(|tissue_box| {
+            for tissue in tissues {
+                match tissue_box.tissues.iter_mut().find(|t| t.is_same(&tissue)) {
+                    Some(existing) => *existing = tissue,
+                    None => tissue_box.tissues.push(tissue),
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_tissues(&mut self, tissues: &[Tissue]) -> Result<Vec<Tissue>> {
+        self.modify(|tissue_box| {
+            let (removed, kept) = tissue_box
                 .tissues
+                .drain(..)
+                .partition(|t| tissues.iter().any(|tissue| t.is_same(tissue)));
+            tissue_box.tissues = kept;
+            Ok(removed)
+        })
+    }
+
+    fn get_boards(&self) -> Result<Vec<String>> {
+        Ok(self.load()?.boards)
+    }
+
+    fn add_board(&mut self, issue_board: &dyn IssueBoard) -> Result<()> {
+        let board_name = issue_board.get_repository_name();
+        self.modify(|tissue_box| {
+            if !tissue_box.boards.contains(&board_name) {
+                tissue_box.boards.push(board_name);
+            }
+            Ok(())
+        })
+    }
+
+    fn remove_board(&mut self, board_name: &str) -> Result<String> {
+        self.modify(|tissue_box| {
+            let idx = tissue_box
+                .boards
                 .iter()
-                .position(|t| t.issue.number == tissue.issue.number)
-                .expect("Tissue not found");
-            removed_tissues.push(tissue_box.tissues.remove(idx));
-        }
+                .position(|name| name == board_name)
+                .ok_or_else(|| {
+                    color_eyre::Report::msg(format!(
+                        "Board {} is not in the Tissue Box",
+                        board_name
+                    ))
+                })?;
+            Ok(tissue_box.boards.remove(idx))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        issue_repository::IssueId,
+        local_board::LocalBoard,
+        test_fixtures::{git, init_repository, tissue},
+    };
+
+    fn tissue_box(directory: &tempfile::TempDir) -> TissueBoxFile {
+        init_repository(directory.path());
+        let mut tissue_box = TissueBoxFile {
+            schema_version: CURRENT_SCHEMA_VERSION,
+            path: directory.path().to_str().unwrap().to_string(),
+            last_commit: String::from("None"),
+            version: 0,
+            boards: vec![],
+            routes: vec![],
+            tissues: vec![],
+            lock_timeout: DEFAULT_LOCK_TIMEOUT,
+        };
+        tissue_box.save().unwrap();
+        tissue_box
+    }
+
+    #[test]
+    fn upserts_and_queries_tissues() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+
+        tissue_box
+            .upsert_tissues(vec![
+                tissue(Some(1), "src/main.rs", "first draft"),
+                tissue(Some(2), "src/lib.rs", "second"),
+            ])
+            .unwrap();
+        // Same board and issue, reworded: replaces the first tissue.
+        tissue_box
+            .upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+
+        let tissues = tissue_box.get_tissues().unwrap();
+        assert_eq!(tissues.len(), 2);
+        assert_eq!(tissue_box.tissues, tissues);
+        assert_eq!(
+            tissue_box.get_tissues_in_file("src/lib.rs").unwrap().len(),
+            1
+        );
+        assert_eq!(
+            tissue_box
+                .get_tissue_by_issue("local:issues", &IssueId::from(1))
+                .unwrap()
+                .unwrap()
+                .todo
+                .description,
+            "first"
+        );
+        assert!(tissue_box
+            .get_tissue_by_issue("local:issues", &IssueId::from(3))
+            .unwrap()
+            .is_none());
+        // The same number on another board is another issue.
+        assert!(tissue_box
+            .get_tissue_by_issue("jira:OPS", &IssueId::from(1))
+            .unwrap()
+            .is_none());
+    }
 
-        fs::write(
-            file_path,
-            serde_json::to_string(&tissue_box).expect("Failed to convert to json"),
-        )
-        .expect("Failed to update Tissue Box file");
-        Ok(removed_tissues)
+    #[test]
+    fn removes_tissues() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        tissue_box
+            .upsert_tissues(vec![
+                tissue(Some(1), "src/main.rs", "first"),
+                tissue(Some(2), "src/main.rs", "second"),
+            ])
+            .unwrap();
+
+        let removed = tissue_box
+            .remove_tissues(&[
+                tissue(Some(1), "src/main.rs", "first"),
+                tissue(Some(3), "src/main.rs", "missing"),
+            ])
+            .unwrap();
+
+        assert_eq!(removed, vec![tissue(Some(1), "src/main.rs", "first")]);
+        assert_eq!(
+            tissue_box.get_tissues().unwrap(),
+            vec![tissue(Some(2), "src/main.rs", "second")]
+        );
+    }
+
+    #[test]
+    fn keeps_identical_todos_in_one_file_apart() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        let mut copy = tissue(None, "src/main.rs", "first");
+        copy.todo.submission.line_number = 5;
+        tissue_box
+            .upsert_tissues(vec![tissue(None, "src/main.rs", "first"), copy.clone()])
+            .unwrap();
+        assert_eq!(tissue_box.get_tissues().unwrap().len(), 2);
+
+        tissue_box
+            .upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+        let removed = tissue_box
+            .remove_tissues(&[tissue(None, "src/main.rs", "first"), copy])
+            .unwrap();
+
+        assert_eq!(removed.len(), 2);
+        assert_eq!(
+            tissue_box.get_tissues().unwrap(),
+            vec![tissue(Some(1), "src/main.rs", "first")]
+        );
     }
-    fn update_tissues(self, tissues: Vec<Tissue>) -> Result<()> {
-        self.removed_tissues(tissues.clone())?;
-        self.add_tissues(tissues)?;
+
+    #[test]
+    fn manages_boards() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        let board = LocalBoard::new("issues");
+
+        tissue_box.add_board(&board).unwrap();
+        tissue_box.add_board(&board).unwrap();
+        assert_eq!(tissue_box.get_boards().unwrap(), vec!["local:issues"]);
+
+        assert_eq!(
+            tissue_box.remove_board("local:issues").unwrap(),
+            "local:issues"
+        );
+        assert!(tissue_box.get_boards().unwrap().is_empty());
+        assert!(tissue_box.remove_board("local:issues").is_err());
     }
-    fn get_tissues() -> Result<Vec<Tissue>> {
-        Ok(vec![])
+
+    #[test]
+    fn stale_writer_gets_a_conflict() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut first = tissue_box(&directory);
+        let mut second = first.load().unwrap();
+
+        first
+            .upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+        let error = second
+            .upsert_tissues(vec![tissue(Some(2), "src/main.rs", "second")])
+            .unwrap_err();
+
+        assert_eq!(
+            error.downcast_ref(),
+            Some(&TissueBoxError::Conflict {
+                expected: 0,
+                found: 1
+            })
+        );
+        second.reload().unwrap();
+        second
+            .upsert_tissues(vec![tissue(Some(2), "src/main.rs", "second")])
+            .unwrap();
+        assert_eq!(first.get_tissues().unwrap().len(), 2);
     }
-    fn add_board(issue_board: &dyn IssueBoard) -> Result<()> {
-        Ok(())
+
+    #[test]
+    fn waits_for_the_lock_and_times_out() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory).with_lock_timeout(Duration::from_millis(100));
+        let lock_path = format!("{}.lock", tissue_box.file_path());
+        fs::write(&lock_path, "4242").unwrap();
+
+        let error = tissue_box
+            .add_board(&LocalBoard::new("issues"))
+            .unwrap_err();
+
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(TissueBoxError::Locked { .. })
+        ));
+        fs::remove_file(&lock_path).unwrap();
+        tissue_box.add_board(&LocalBoard::new("issues")).unwrap();
+        assert!(!PathBuf::from(lock_path).exists());
+    }
+
+    #[test]
+    fn writes_leave_no_temporary_files() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        tissue_box
+            .upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+
+        let files: Vec<_> = fs::read_dir(directory.path())
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name())
+            .filter(|name| name != ".git")
+            .collect();
+        assert_eq!(files, vec!["tissue_box.json"]);
+        assert_eq!(tissue_box.version, 1);
+    }
+
+    #[test]
+    fn records_the_last_commit_of_its_own_repository() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        tissue_box.add_board(&LocalBoard::new("issues")).unwrap();
+        assert_eq!(
+            tissue_box.last_commit,
+            githandler::head_commit(directory.path()).unwrap()
+        );
+
+        fs::remove_dir_all(directory.path().join(".git")).unwrap();
+        assert!(tissue_box.add_board(&LocalBoard::new("other")).is_err());
+        assert_eq!(tissue_box.get_boards().unwrap(), vec!["local:issues"]);
+    }
+
+    #[test]
+    fn init_creates_and_open_finds_the_box() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().to_str().unwrap();
+
+        let error = TissueBoxFile::open(path).unwrap_err();
+        assert!(matches!(
+            error.downcast_ref(),
+            Some(TissueBoxError::NotFound { .. })
+        ));
+
+        let board = LocalBoard::new("issues");
+        TissueBoxFile::init(path, &[&board]).unwrap();
+        assert!(TissueBoxFile::init(path, &[]).is_err());
+        let tissue_box = TissueBoxFile::open(path).unwrap();
+        assert_eq!(tissue_box.boards, vec!["local:issues"]);
+        assert_eq!(tissue_box.path, path);
+    }
+
+    #[test]
+    fn init_creates_missing_directories() {
+        let directory = tempfile::tempdir().unwrap();
+        let nested = directory.path().join("tissue/box");
+        let path = nested.to_str().unwrap();
+
+        TissueBoxFile::init(path, &[]).unwrap();
+        assert!(TissueBoxFile::open(path).is_ok());
+
+        let file = directory.path().join("file");
+        fs::write(&file, "").unwrap();
+        let error = TissueBoxFile::init(file.join("box").to_str().unwrap(), &[]).unwrap_err();
+        assert!(error.to_string().contains("file/box"));
+    }
+
+    #[test]
+    fn routes_to_the_only_board_or_by_rule() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        tissue_box.add_board(&LocalBoard::new("issues")).unwrap();
+        assert_eq!(
+            tissue_box.router().unwrap().route("src/main.rs"),
+            ["local:issues"]
+        );
+
+        tissue_box
+            .add_route("infra/** -> gitlab:ops/infra".parse().unwrap())
+            .unwrap();
+        tissue_box
+            .add_route("infra/** -> jira:OPS".parse().unwrap())
+            .unwrap();
+        assert!(tissue_box
+            .add_route("[ -> jira:OPS".parse().unwrap())
+            .is_err());
+
+        let router = tissue_box.load().unwrap().router().unwrap();
+        assert_eq!(router.route("infra/main.tf"), ["jira:OPS"]);
+        assert!(router.route("src/main.rs").is_empty());
+    }
+
+    #[test]
+    fn writes_sorted_pretty_json() {
+        let directory = tempfile::tempdir().unwrap();
+        let mut tissue_box = tissue_box(&directory);
+        tissue_box
+            .upsert_tissues(vec![
+                tissue(Some(2), "src/main.rs", "second"),
+                tissue(Some(1), "src/lib.rs", "first"),
+            ])
+            .unwrap();
+
+        let json = fs::read_to_string(tissue_box.file_path()).unwrap();
+        assert!(json.lines().count() > 1);
+        assert!(json.find("src/lib.rs").unwrap() < json.find("src/main.rs").unwrap());
+        assert_eq!(tissue_box.tissues[0].todo.description, "first");
     }
-    fn remove_board(board_name: &str) -> Result<&dyn IssueBoard> {
-        todo!()
+
+    #[test]
+    fn migrates_old_boxes_on_load_and_on_demand() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().to_str().unwrap().to_string();
+        let file_path = format!("{}/tissue_box.json", path);
+        let todo = serde_json::to_value(tissue(None, "src/main.rs", "first").todo).unwrap();
+        let old = serde_json::json!({
+            "path": path,
+            "last_commit": "None",
+            "boards": ["GitHub"],
+            "tissues": [{
+                "issue": {
+                    "number": 1, "title": "first", "user": {"login": "OthelloEngineer"},
+                    "assignee": null, "labels": [{"name": "bug"}], "state": "open", "body": null
+                },
+                "todo": todo
+            }]
+        });
+        fs::write(&file_path, old.to_string()).unwrap();
+        git(Path::new(&path), &["init", "-q"]);
+        git(
+            Path::new(&path),
+            &[
+                "remote",
+                "add",
+                "origin",
+                "git@github.com:OthelloEngineer/tissue.git",
+            ],
+        );
+
+        let (loaded, report) = TissueBoxFile::read(&file_path).unwrap();
+        assert_eq!(loaded.schema_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(
+            loaded.tissues[0].links[0].issue.author.name,
+            "OthelloEngineer"
+        );
+        assert_eq!(
+            loaded.tissues[0].links[0].board,
+            "github:OthelloEngineer/tissue"
+        );
+        assert_eq!(report.from_version, 0);
+
+        let report = TissueBoxFile::migrate(&path).unwrap();
+        assert_eq!(report.changes.len(), 5);
+        assert!(TissueBoxFile::migrate(&path).unwrap().is_empty());
     }
 }
@@ -0,0 +1,292 @@
+//! Three-way merge of `tissue_box.json`, used as a git merge driver.
+//!
+//! Tissues are matched with `Tissue::is_same`, so branches that each add or sync different ToDos
+//! merge without conflicts. Only tissues both sides changed differently are reported.
+use std::{fmt, fs};
+
+use color_eyre::eyre::Result;
+
+use crate::{tissuebox_file::TissueBoxFile, tissuebox_repository::Tissue};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum MergeConflict {
+    /// Both sides changed the same tissue, differently.
+    BothChanged { ours: Tissue, theirs: Tissue },
+    /// One side changed a tissue the other side removed.
+    ChangedAndRemoved { changed: Tissue },
+    /// Both sides filed the same ToDo, as different issues.
+    DuplicateIssue { ours: Tissue, theirs: Tissue },
+}
+
+fn describe(tissue: &Tissue) -> String {
+    let links = if tissue.links.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", tissue.links_to_string())
+    };
+    format!(
+        "\"{}\" in {}{}",
+        tissue.todo.description, tissue.todo.submission.file_path, links
+    )
+}
+
+impl fmt::Display for MergeConflict {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MergeConflict::BothChanged { ours, .. } => {
+                write!(f, "{} was changed on both sides", describe(ours))
+            }
+            MergeConflict::ChangedAndRemoved { changed } => write!(
+                f,
+                "{} was changed on one side and removed on the other",
+                describe(changed)
+            ),
+            MergeConflict::DuplicateIssue { ours, theirs } => write!(
+                f,
+                "{} was filed as {} on the other side",
+                describe(ours),
+                theirs.links_to_string()
+            ),
+        }
+    }
+}
+
+pub struct MergeOutcome {
+    pub tissue_box: TissueBoxFile,
+    pub conflicts: Vec<MergeConflict>,
+}
+
+fn find<'a>(tissues: &'a [Tissue], tissue: &Tissue) -> Option<&'a Tissue> {
+    tissues.iter().find(|t| t.is_same(tissue))
+}
+
+/// Merges `ours` and `theirs`, which both descend from `base`. Conflicting tissues keep our
+/// side and are listed in the outcome.
+pub fn merge(base: TissueBoxFile, ours: TissueBoxFile, theirs: TissueBoxFile) -> MergeOutcome {
+    let mut tissues = Vec::new();
+    let mut conflicts = Vec::new();
+
+    for our_tissue in &ours.tissues {
+        let base_tissue = find(&base.tissues, our_tissue);
+        match (find(&theirs.tissues, our_tissue), base_tissue) {
+            (Some(their_tissue), _) if their_tissue == our_tissue => {
+                tissues.push(our_tissue.clone())
+            }
+            (Some(their_tissue), Some(base_tissue)) if base_tissue == our_tissue => {
+                tissues.push(their_tissue.clone())
+            }
+            (Some(their_tissue), Some(base_tissue)) if base_tissue == their_tissue => {
+                tissues.push(our_tissue.clone())
+            }
+            (Some(their_tissue), _) => {
+                conflicts.push(MergeConflict::BothChanged {
+                    ours: our_tissue.clone(),
+                    theirs: their_tissue.clone(),
+                });
+                tissues.push(our_tissue.clone());
+            }
+            (None, None) => tissues.push(our_tissue.clone()),
+            // Removed on their side.
+            (None, Some(base_tissue)) if base_tissue == our_tissue => {}
+            (None, Some(_)) => {
+                conflicts.push(MergeConflict::ChangedAndRemoved {
+                    changed: our_tissue.clone(),
+                });
+                tissues.push(our_tissue.clone());
+            }
+        }
+    }
+
+    for their_tissue in &theirs.tissues {
+        if find(&ours.tissues, their_tissue).is_some() {
+            continue;
+        }
+        match find(&base.tissues, their_tissue) {
+            None => {
+                let fingerprint = their_tissue.todo.fingerprint();
+                if let Some(our_tissue) =
+                    tissues.iter().find(|t| t.todo.fingerprint() == fingerprint)
+                {
+                    conflicts.push(MergeConflict::DuplicateIssue {
+                        ours: our_tissue.clone(),
+                        theirs: their_tissue.clone(),
+                    });
+                }
+                tissues.push(their_tissue.clone());
+            }
+            // Removed on our side.
+            Some(base_tissue) if base_tissue == their_tissue => {}
+            Some(_) => {
+                conflicts.push(MergeConflict::ChangedAndRemoved {
+                    changed: their_tissue.clone(),
+                });
+                tissues.push(their_tissue.clone());
+            }
+        }
+    }
+
+    let mut boards: Vec<String> = ours
+        .boards
+        .iter()
+        .filter(|board| theirs.boards.contains(board) || !base.boards.contains(board))
+        .cloned()
+        .collect();
+    boards.extend(
+        theirs
+            .boards
+            .iter()
+            .filter(|board| !ours.boards.contains(board) && !base.boards.contains(board))
+            .cloned(),
+    );
+
+    let version = ours.version.max(theirs.version) + 1;
+    let mut tissue_box = ours;
+    tissue_box.version = version;
+    tissue_box.boards = boards;
+    tissue_box.tissues = tissues;
+    MergeOutcome {
+        tissue_box,
+        conflicts,
+    }
+}
+
+/// Runs the merge driver: merges the boxes at the three paths git passes as `%O %A %B` and
+/// writes the result over `ours`, as git expects.
+pub fn merge_files(base: &str, ours: &str, theirs: &str) -> Result<Vec<MergeConflict>> {
+    let (base_box, _) = TissueBoxFile::read(base)?;
+    let (our_box, _) = TissueBoxFile::read(ours)?;
+    let (their_box, _) = TissueBoxFile::read(theirs)?;
+    let mut outcome = merge(base_box, our_box, their_box);
+    fs::write(ours, outcome.tissue_box.serialise()?)?;
+    Ok(outcome.conflicts)
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::test_fixtures::tissue;
+
+    fn tissue_box(boards: &[&str], tissues: Vec<Tissue>) -> TissueBoxFile {
+        serde_json::from_value(json!({
+            "schema_version": 1,
+            "path": ".",
+            "last_commit": "None",
+            "version": 1,
+            "boards": boards,
+            "tissues": tissues,
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn unions_tissues_added_and_synced_on_both_sides() {
+        let base = tissue_box(&["local:issues"], vec![tissue(None, "src/a.rs", "shared")]);
+        let ours = tissue_box(
+            &["local:issues"],
+            vec![
+                tissue(Some(1), "src/a.rs", "shared"),
+                tissue(None, "src/b.rs", "ours"),
+            ],
+        );
+        let theirs = tissue_box(
+            &["local:issues", "jira:BILL"],
+            vec![
+                tissue(None, "src/a.rs", "shared"),
+                tissue(None, "src/c.rs", "theirs"),
+            ],
+        );
+
+        let outcome = merge(base, ours, theirs);
+
+        assert!(outcome.conflicts.is_empty());
+        assert_eq!(
+            outcome.tissue_box.tissues,
+            vec![
+                tissue(Some(1), "src/a.rs", "shared"),
+                tissue(None, "src/b.rs", "ours"),
+                tissue(None, "src/c.rs", "theirs"),
+            ]
+        );
+        assert_eq!(outcome.tissue_box.boards, vec!["local:issues", "jira:BILL"]);
+        assert_eq!(outcome.tissue_box.version, 2);
+    }
+
+    #[test]
+    fn honours_removals_and_flags_real_conflicts() {
+        let base = tissue_box(
+            &["local:issues"],
+            vec![
+                tissue(Some(1), "src/a.rs", "removed"),
+                tissue(Some(2), "src/a.rs", "edited"),
+            ],
+        );
+        let mut edited = tissue(Some(2), "src/a.rs", "edited");
+        edited.links[0].issue.state = "closed".to_string();
+        let ours = tissue_box(&[], vec![tissue(Some(2), "src/a.rs", "edited")]);
+        let theirs = tissue_box(
+            &["local:issues"],
+            vec![
+                edited.clone(),
+                tissue(Some(1), "src/a.rs", "removed"),
+                tissue(Some(3), "src/b.rs", "new"),
+            ],
+        );
+
+        let outcome = merge(base, ours, theirs);
+
+        assert!(outcome.conflicts.is_empty());
+        assert!(outcome.tissue_box.boards.is_empty());
+        assert_eq!(
+            outcome.tissue_box.tissues,
+            vec![edited.clone(), tissue(Some(3), "src/b.rs", "new")]
+        );
+
+        let base = tissue_box(&[], vec![tissue(Some(2), "src/a.rs", "edited")]);
+        let mut also_edited = tissue(Some(2), "src/a.rs", "edited");
+        also_edited.links[0].issue.title = "renamed".to_string();
+        let ours = tissue_box(
+            &[],
+            vec![also_edited.clone(), tissue(Some(4), "src/b.rs", "new")],
+        );
+        let theirs = tissue_box(&[], vec![edited, tissue(Some(5), "src/b.rs", "new")]);
+
+        let outcome = merge(base, ours, theirs);
+
+        assert_eq!(outcome.conflicts.len(), 2);
+        assert!(matches!(
+            outcome.conflicts[0],
+            MergeConflict::BothChanged { .. }
+        ));
+        assert!(matches!(
+            outcome.conflicts[1],
+            MergeConflict::DuplicateIssue { .. }
+        ));
+        assert_eq!(outcome.tissue_box.tissues[0], also_edited);
+    }
+
+    #[test]
+    fn merge_driver_writes_over_ours() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = |name: &str| directory.path().join(name).to_str().unwrap().to_string();
+        let write = |name: &str, tissue_box: TissueBoxFile| {
+            fs::write(path(name), serde_json::to_string(&tissue_box).unwrap()).unwrap()
+        };
+        write("base", tissue_box(&[], vec![]));
+        write(
+            "ours",
+            tissue_box(&[], vec![tissue(None, "src/a.rs", "ours")]),
+        );
+        write(
+            "theirs",
+            tissue_box(&[], vec![tissue(None, "src/b.rs", "theirs")]),
+        );
+
+        let conflicts = merge_files(&path("base"), &path("ours"), &path("theirs")).unwrap();
+
+        assert!(conflicts.is_empty());
+        let (merged, _) = TissueBoxFile::read(&path("ours")).unwrap();
+        assert_eq!(merged.tissues.len(), 2);
+    }
+}
@@ -0,0 +1,434 @@
+//! Upgrades serialised Tissue Boxes to the current schema.
+//!
+//! Each migration takes the raw JSON of one schema version to the next, so boxes written by any
+//! older tissue can be loaded. Files without a `schema_version` are version 0.
+use std::{
+    cell::OnceCell,
+    path::{Path, PathBuf},
+};
+
+use color_eyre::eyre::Result;
+use serde_json::{json, Map, Value};
+
+use crate::githandler;
+
+pub const CURRENT_SCHEMA_VERSION: u64 = 3;
+
+struct Migration {
+    from: u64,
+    apply: fn(&mut Map<String, Value>, &MigrationContext) -> Result<Vec<String>>,
+}
+
+/// What migrations may need to know beyond the box itself, looked up only if one needs it.
+#[derive(Debug, Default)]
+pub struct MigrationContext {
+    directory: Option<PathBuf>,
+    github_repository: OnceCell<Option<String>>,
+}
+
+impl MigrationContext {
+    /// Context for a box kept in the git repository at `directory`.
+    pub fn for_directory(directory: &Path) -> MigrationContext {
+        MigrationContext {
+            directory: Some(directory.to_path_buf()),
+            github_repository: OnceCell::new(),
+        }
+    }
+
+    /// The `org/repo` that boxes from before board names were qualified meant by "GitHub".
+    /// Found from the git remotes otherwise.
+    pub fn with_github_repository(self, github_repository: &str) -> MigrationContext {
+        MigrationContext {
+            github_repository: OnceCell::from(Some(github_repository.to_string())),
+            ..self
+        }
+    }
+
+    fn github_repository(&self) -> Option<&str> {
+        self.github_repository
+            .get_or_init(|| {
+                let directory = self.directory.as_deref()?;
+                githandler::github_repository(directory).ok().flatten()
+            })
+            .as_deref()
+    }
+}
+
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        apply: board_neutral_issues,
+    },
+    Migration {
+        from: 1,
+        apply: board_links,
+    },
+    Migration {
+        from: 2,
+        apply: qualified_github_board,
+    },
+];
+
+#[derive(Debug, PartialEq)]
+pub struct MigrationReport {
+    pub from_version: u64,
+    pub to_version: u64,
+    pub changes: Vec<String>,
+}
+
+impl MigrationReport {
+    pub fn is_empty(&self) -> bool {
+        self.from_version == self.to_version
+    }
+}
+
+/// Migrates `tissue_box` in place to `CURRENT_SCHEMA_VERSION`.
+pub fn migrate(tissue_box: &mut Value, context: &MigrationContext) -> Result<MigrationReport> {
+    let object = tissue_box
+        .as_object_mut()
+        .ok_or_else(|| color_eyre::Report::msg("Tissue Box is not a JSON object"))?;
+    let from_version = object
+        .get("schema_version")
+        .and_then(Value::as_u64)
+        .unwrap_or(0);
+    if from_version > CURRENT_SCHEMA_VERSION {
+        return Err(color_eyre::Report::msg(format!(
+            "Tissue Box uses schema version {}, but this tissue only understands up to {}. \
+             Please upgrade tissue",
+            from_version, CURRENT_SCHEMA_VERSION
+        )));
+    }
+
+    let mut changes = Vec::new();
+    for migration in MIGRATIONS.iter().filter(|m| m.from >= from_version) {
+        for change in (migration.apply)(object, context)? {
+            changes.push(format!(
+                "v{} -> v{}: {}",
+                migration.from,
+                migration.from + 1,
+                change
+            ));
+        }
+        object.insert("schema_version".into(), Value::from(migration.from + 1));
+    }
+    Ok(MigrationReport {
+        from_version,
+        to_version: CURRENT_SCHEMA_VERSION,
+        changes,
+    })
+}
+
+fn tissues_mut(tissue_box: &mut Map<String, Value>) -> impl Iterator<Item = &mut Value> {
+    tissue_box
+        .get_mut("tissues")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+}
+
+fn rename_key(object: &mut Map<String, Value>, from: &str, to: &str) -> bool {
+    match object.remove(from) {
+        Some(value) => {
+            object.insert(to.to_string(), value);
+            true
+        }
+        None => false,
+    }
+}
+
+/// v0 stored issues with GitHub's field names (`user.login`, `labels`).
+fn board_neutral_issues(
+    tissue_box: &mut Map<String, Value>,
+    _: &MigrationContext,
+) -> Result<Vec<String>> {
+    let mut renamed_authors = 0;
+    let mut renamed_labels = 0;
+    let mut renamed_logins = 0;
+    for tissue in tissues_mut(tissue_box) {
+        let Some(issue) = tissue.get_mut("issue").and_then(Value::as_object_mut) else {
+            continue;
+        };
+        if rename_key(issue, "user", "author") {
+            renamed_authors += 1;
+        }
+        if rename_key(issue, "labels", "issue_type") {
+            renamed_labels += 1;
+        }
+        for user in ["author", "assignee"] {
+            if let Some(user) = issue.get_mut(user).and_then(Value::as_object_mut) {
+                if rename_key(user, "login", "name") {
+                    renamed_logins += 1;
+                }
+            }
+        }
+    }
+    let mut changes = Vec::new();
+    if renamed_authors > 0 {
+        changes.push(format!(
+            "renamed issue field `user` to `author` in {} tissue(s)",
+            renamed_authors
+        ));
+    }
+    if renamed_labels > 0 {
+        changes.push(format!(
+            "renamed issue field `labels` to `issue_type` in {} tissue(s)",
+            renamed_labels
+        ));
+    }
+    if renamed_logins > 0 {
+        changes.push(format!(
+            "renamed user field `login` to `name` in {} user(s)",
+            renamed_logins
+        ));
+    }
+    Ok(changes)
+}
+
+/// v1 stored a single `issue` and `board` per tissue and a single `board` per routing rule.
+fn board_links(tissue_box: &mut Map<String, Value>, _: &MigrationContext) -> Result<Vec<String>> {
+    // Boxes from before routing only ever had their first board.
+    let fallback_board = tissue_box
+        .get("boards")
+        .and_then(|boards| boards.get(0))
+        .cloned()
+        .unwrap_or_else(|| Value::from("unknown"));
+    let mut linked = 0;
+    let mut unfiled = 0;
+    for tissue in tissues_mut(tissue_box) {
+        let Some(tissue) = tissue.as_object_mut() else {
+            continue;
+        };
+        let board = tissue.remove("board").filter(|board| !board.is_null());
+        let Some(issue) = tissue.remove("issue") else {
+            continue;
+        };
+        let links = if issue.get("number").is_some_and(|number| !number.is_null()) {
+            linked += 1;
+            json!([{"board": board.unwrap_or_else(|| fallback_board.clone()), "issue": issue}])
+        } else {
+            unfiled += 1;
+            json!([])
+        };
+        tissue.insert("links".into(), links);
+    }
+    let mut rules = 0;
+    for rule in tissue_box
+        .get_mut("routes")
+        .and_then(Value::as_array_mut)
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_object_mut)
+    {
+        if let Some(board) = rule.remove("board") {
+            rule.insert("boards".into(), json!([board]));
+            rules += 1;
+        }
+    }
+    let mut changes = Vec::new();
+    if linked > 0 {
+        changes.push(format!(
+            "moved `issue` and `board` into `links` in {} tissue(s)",
+            linked
+        ));
+    }
+    if unfiled > 0 {
+        changes.push(format!(
+            "dropped the placeholder `issue` of {} unfiled tissue(s)",
+            unfiled
+        ));
+    }
+    if rules > 0 {
+        changes.push(format!(
+            "turned `board` into `boards` in {} routing rule(s)",
+            rules
+        ));
+    }
+    Ok(changes)
+}
+
+/// v2 could still name the board of boxes from before routing "GitHub", which no board
+/// connected today is called. It becomes `github:org/repo` of the repository's GitHub remote.
+fn qualified_github_board(
+    tissue_box: &mut Map<String, Value>,
+    context: &MigrationContext,
+) -> Result<Vec<String>> {
+    let mut legacy: Vec<&mut Value> = board_names_mut(tissue_box)
+        .into_iter()
+        .filter(|name| *name == "GitHub")
+        .collect();
+    if legacy.is_empty() {
+        return Ok(vec![]);
+    }
+    let github_repository = context.github_repository().ok_or_else(|| {
+        color_eyre::Report::msg(
+            "The Tissue Box names its board \"GitHub\", but no GitHub remote says which \
+             repository that is. Add one, e.g. `git remote add origin \
+             git@github.com:org/repo.git`, and try again",
+        )
+    })?;
+    let board = format!("github:{}", github_repository);
+    for name in &mut legacy {
+        **name = Value::from(board.as_str());
+    }
+    Ok(vec![format!(
+        "renamed board \"GitHub\" to \"{}\" in {} place(s)",
+        board,
+        legacy.len()
+    )])
+}
+
+/// Every board name in the box: the board list, links and routing rules.
+fn board_names_mut(tissue_box: &mut Map<String, Value>) -> Vec<&mut Value> {
+    let mut names = Vec::new();
+    for (key, value) in tissue_box.iter_mut() {
+        let Some(entries) = value.as_array_mut() else {
+            continue;
+        };
+        match key.as_str() {
+            "boards" => names.extend(entries.iter_mut()),
+            "tissues" => names.extend(
+                entries
+                    .iter_mut()
+                    .filter_map(|tissue| tissue.get_mut("links"))
+                    .filter_map(Value::as_array_mut)
+                    .flatten()
+                    .filter_map(|link| link.get_mut("board")),
+            ),
+            "routes" => names.extend(
+                entries
+                    .iter_mut()
+                    .filter_map(|rule| rule.get_mut("boards"))
+                    .filter_map(Value::as_array_mut)
+                    .flatten(),
+            ),
+            _ => {}
+        }
+    }
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrates_github_shaped_issues() {
+        let mut tissue_box = json!({
+            "path": ".",
+            "last_commit": "None",
+            "boards": ["GitHub"],
+            "tissues": [{
+                "issue": {
+                    "number": 1,
+                    "title": "test issue",
+                    "user": {"login": "OthelloEngineer"},
+                    "assignee": {"login": "jolee18"},
+                    "labels": [{"name": "bug"}],
+                    "state": "open",
+                    "body": null
+                },
+                "todo": {}
+            }]
+        });
+
+        let context = MigrationContext::default().with_github_repository("OthelloEngineer/tissue");
+        let report = migrate(&mut tissue_box, &context).unwrap();
+
+        assert_eq!(report.from_version, 0);
+        assert_eq!(report.to_version, CURRENT_SCHEMA_VERSION);
+        assert_eq!(report.changes.len(), 5);
+        assert_eq!(tissue_box["schema_version"], json!(CURRENT_SCHEMA_VERSION));
+        assert_eq!(
+            tissue_box["boards"],
+            json!(["github:OthelloEngineer/tissue"])
+        );
+        let link = &tissue_box["tissues"][0]["links"][0];
+        assert_eq!(link["board"], json!("github:OthelloEngineer/tissue"));
+        let issue = &link["issue"];
+        assert_eq!(issue["author"], json!({"name": "OthelloEngineer"}));
+        assert_eq!(issue["assignee"], json!({"name": "jolee18"}));
+        assert_eq!(issue["issue_type"], json!([{"name": "bug"}]));
+        assert!(issue.get("user").is_none());
+    }
+
+    #[test]
+    fn moves_issues_into_board_links() {
+        let mut tissue_box = json!({
+            "schema_version": 1,
+            "boards": ["github:org/repo", "jira:BILL"],
+            "routes": [{"pattern": "billing/**", "board": "jira:BILL"}],
+            "tissues": [
+                {"issue": {"number": 4, "title": "routed"}, "board": "jira:BILL", "todo": {}},
+                {"issue": {"number": 5, "title": "old"}, "todo": {}},
+                {"issue": {"number": null, "title": "unfiled"}, "todo": {}}
+            ]
+        });
+
+        let report = migrate(&mut tissue_box, &MigrationContext::default()).unwrap();
+
+        assert_eq!(report.from_version, 1);
+        assert_eq!(report.changes.len(), 3);
+        let tissues = &tissue_box["tissues"];
+        assert_eq!(tissues[0]["links"][0]["board"], json!("jira:BILL"));
+        assert_eq!(tissues[0]["links"][0]["issue"]["number"], json!(4));
+        assert_eq!(tissues[1]["links"][0]["board"], json!("github:org/repo"));
+        assert_eq!(tissues[2]["links"], json!([]));
+        assert!(tissues[0].get("issue").is_none());
+        assert_eq!(tissue_box["routes"][0]["boards"], json!(["jira:BILL"]));
+    }
+
+    #[test]
+    fn qualifies_the_legacy_github_board() {
+        let legacy = json!({
+            "schema_version": 2,
+            "boards": ["GitHub", "jira:BILL"],
+            "routes": [{"pattern": "default", "boards": ["GitHub"]}],
+            "tissues": [{
+                "links": [{"board": "GitHub", "issue": {"number": 1, "title": "GitHub"}}],
+                "todo": {}
+            }]
+        });
+
+        let mut tissue_box = legacy.clone();
+        assert!(migrate(&mut tissue_box, &MigrationContext::default()).is_err());
+
+        let context = MigrationContext::default().with_github_repository("org/repo");
+        let report = migrate(&mut tissue_box, &context).unwrap();
+        assert_eq!(
+            report.changes,
+            ["v2 -> v3: renamed board \"GitHub\" to \"github:org/repo\" in 3 place(s)"]
+        );
+        assert_eq!(
+            tissue_box["boards"],
+            json!(["github:org/repo", "jira:BILL"])
+        );
+        assert_eq!(
+            tissue_box["routes"][0]["boards"],
+            json!(["github:org/repo"])
+        );
+        let link = &tissue_box["tissues"][0]["links"][0];
+        assert_eq!(link["board"], json!("github:org/repo"));
+        assert_eq!(link["issue"]["title"], json!("GitHub"));
+    }
+
+    #[test]
+    fn current_boxes_are_untouched() {
+        let mut tissue_box = json!({
+            "schema_version": CURRENT_SCHEMA_VERSION,
+            "tissues": [{"links": [], "todo": {}}]
+        });
+        let before = tissue_box.clone();
+
+        let report = migrate(&mut tissue_box, &MigrationContext::default()).unwrap();
+
+        assert!(report.is_empty());
+        assert!(report.changes.is_empty());
+        assert_eq!(tissue_box, before);
+    }
+
+    #[test]
+    fn refuses_boxes_from_newer_versions() {
+        let mut tissue_box = json!({"schema_version": CURRENT_SCHEMA_VERSION + 1});
+        assert!(migrate(&mut tissue_box, &MigrationContext::default()).is_err());
+    }
+}
@@ -1,58 +1,169 @@
-// Create tissuebox.json if doesn't exist
-//
-// Pull ToDo's from files
-//
-// Diff with tissuebox.json
-//
-// Apply difference to github repository. Save ID of tissues to track them on the repository
-//
-//
-//diff --git a/src/integration-test.rs b/src/integration-test.rs
-//index 31d7315..1aee8f7 100644
-//--- a/src/integration-test.rs
-//+++ b/src/integration-test.rs
-//@@ -1,6 +1,9 @@
-// #[cfg(test)]
-// mod tests {
-//-    use crate::{filereader, githandler, todofinder::{self, Submission}};
-//+    use crate::{
-//+        filereader, githandler,
-//+        todofinder::{self, Submission},
-//+    };
-//
-//     use super::*;
-//     use std::fs;
-//@@ -10,7 +13,7 @@ mod tests {
-//             Ok(files) => files,
-//             Err(e) => panic!("Error: {}", e),
-//         };
-//-        let mut submissions : Vec<Submission> = Vec::new();
-//+        let mut submissions: Vec<Submission> = Vec::new();
-//         for file in files {
-//             for line in file.lines {
-//                 let issuer = githandler::blame_user_from_line(&file.file_path, line.0).unwrap();
-
-use async_trait::async_trait;
+use std::{fmt, time::Duration};
+
 use color_eyre::eyre::Result;
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    issue_repository::{Issue, IssueBoard},
+    issue_repository::{Issue, IssueBoard, IssueId},
     todofinder::ToDo,
 };
 
-#[derive(Debug, Serialize, Deserialize)]
+/// A ToDo and the issues filed for it, one per board it is mirrored to.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Tissue {
-    pub issue: Issue,
     pub todo: ToDo,
+    #[serde(default)]
+    pub links: Vec<BoardLink>,
+    /// Names of the stale rules already escalated, so each fires only once.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub escalations: Vec<String>,
+}
+
+/// An issue filed on `board`, as tissue last saw it. Comparing this with the board's current
+/// issue shows whether someone changed the mirror by hand.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct BoardLink {
+    pub board: String,
+    pub issue: Issue,
+}
+
+impl BoardLink {
+    pub fn id(&self) -> Option<&IssueId> {
+        self.issue.number.as_ref()
+    }
+}
+
+impl fmt::Display for BoardLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.id() {
+            Some(id) => write!(f, "{}#{}", self.board, id),
+            None => write!(f, "{}", self.board),
+        }
+    }
+}
+
+impl Tissue {
+    /// A tissue for `todo` that is not filed on any board yet.
+    pub fn new(todo: ToDo) -> Tissue {
+        Tissue {
+            todo,
+            links: vec![],
+            escalations: vec![],
+        }
+    }
+
+    pub fn link(&self, board: &str) -> Option<&BoardLink> {
+        self.links.iter().find(|link| link.board == board)
+    }
+
+    /// The tissue's links, e.g. `github:org/repo#4, jira:INT#INT-7`.
+    pub fn links_to_string(&self) -> String {
+        self.links
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    /// Whether the tissue links to issue `id` on `board`.
+    pub fn is_linked_to(&self, board: &str, id: &IssueId) -> bool {
+        self.link(board).is_some_and(|link| link.id() == Some(id))
+    }
+
+    /// Whether `other` tracks the same ToDo. Tissues are matched by a shared board and issue
+    /// once both are filed, and by fingerprint and line while neither is, so identical ToDos in
+    /// one file stay apart. A filed tissue is never the same as an unfiled one.
+    pub fn is_same(&self, other: &Tissue) -> bool {
+        match (self.links.is_empty(), other.links.is_empty()) {
+            (true, true) => {
+                self.todo.fingerprint() == other.todo.fingerprint()
+                    && self.todo.submission.line_number == other.todo.submission.line_number
+            }
+            (false, false) => self.links.iter().any(|link| {
+                other
+                    .link(&link.board)
+                    .is_some_and(|other_link| other_link.id() == link.id())
+            }),
+            _ => false,
+        }
+    }
+}
+
+/// Failures of a tissue box that callers may want to handle, e.g. by retrying or re-syncing.
+/// Returned wrapped in a `color_eyre::Report`, so use `downcast_ref` to inspect it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TissueBoxError {
+    /// There is no box at `path` yet; create one with `TissueBoxFile::init`.
+    NotFound { path: String },
+    /// Another process held the box's lock for longer than we were willing to wait.
+    Locked { lock_path: String, waited: Duration },
+    /// The box was written by someone else since it was loaded.
+    Conflict { expected: u64, found: u64 },
 }
 
-#[async_trait]
+impl fmt::Display for TissueBoxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TissueBoxError::NotFound { path } => write!(
+                f,
+                "No Tissue Box was found at {}. Create one with `tissue box init`",
+                path
+            ),
+            TissueBoxError::Locked { lock_path, waited } => write!(
+                f,
+                "Tissue Box is locked by another process (waited {:?}). \
+                 If no other tissue is running, delete {}",
+                waited, lock_path
+            ),
+            TissueBoxError::Conflict { expected, found } => write!(
+                f,
+                "Tissue Box was changed by another process (expected version {}, found {}). \
+                 Reload it and try again",
+                expected, found
+            ),
+        }
+    }
+}
+
+impl std::error::Error for TissueBoxError {}
+
 pub trait TissueBoxRepository {
-    fn add_tissues(self, tissues: Vec<Tissue>) -> Result<()>;
-    fn remove_tissues(self, tissues: Vec<&Tissue>) -> Result<Vec<Tissue>>;
-    fn update_tissues(self, tissues: Vec<Tissue>) -> Result<()>;
-    fn get_tissues(self) -> Result<Vec<Tissue>>;
-    fn add_board(self, issue_board: &dyn IssueBoard) -> Result<()>;
-    fn remove_board(self, board_name: &str) -> Result<&dyn IssueBoard>;
+    fn get_tissues(&self) -> Result<Vec<Tissue>>;
+
+    fn get_tissues_in_file(&self, file_path: &str) -> Result<Vec<Tissue>> {
+        Ok(self
+            .get_tissues()?
+            .into_iter()
+            .filter(|tissue| tissue.todo.submission.file_path == file_path)
+            .collect())
+    }
+
+    /// Finds the tissue linked to issue `number` on `board`; numbers are only unique per board.
+    fn get_tissue_by_issue(&self, board: &str, number: &IssueId) -> Result<Option<Tissue>> {
+        Ok(self
+            .get_tissues()?
+            .into_iter()
+            .find(|tissue| tissue.is_linked_to(board, number)))
+    }
+
+    /// Finds the tissue tracking the ToDo with this `ToDo::fingerprint`.
+    fn get_tissue_by_fingerprint(&self, fingerprint: &str) -> Result<Option<Tissue>> {
+        Ok(self
+            .get_tissues()?
+            .into_iter()
+            .find(|tissue| tissue.todo.fingerprint() == fingerprint))
+    }
+
+    /// Replaces tissues that are already in the box and adds the rest.
+    fn upsert_tissues(&mut self, tissues: Vec<Tissue>) -> Result<()>;
+
+    /// Removes the given tissues and returns the ones that were in the box.
+    fn remove_tissues(&mut self, tissues: &[Tissue]) -> Result<Vec<Tissue>>;
+
+    fn get_boards(&self) -> Result<Vec<String>>;
+
+    fn add_board(&mut self, issue_board: &dyn IssueBoard) -> Result<()>;
+
+    /// Removes the board and returns its name.
+    fn remove_board(&mut self, board_name: &str) -> Result<String>;
 }
@@ -0,0 +1,564 @@
+use std::path::Path;
+
+use color_eyre::eyre::Result;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+
+use crate::{
+    githandler,
+    issue_repository::{IssueBoard, IssueId},
+    tissuebox_migration::{self, MigrationContext, CURRENT_SCHEMA_VERSION},
+    tissuebox_repository::{Tissue, TissueBoxRepository},
+};
+
+/// Kept equal to the JSON schema version of the tissues in `data`, so old databases are
+/// upgraded with the same migrations as `tissue_box.json`.
+const SCHEMA_VERSION: i64 = CURRENT_SCHEMA_VERSION as i64;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS tissues (
+        id INTEGER PRIMARY KEY,
+        fingerprint TEXT NOT NULL,
+        file_path TEXT NOT NULL,
+        data TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS tissues_by_fingerprint ON tissues (fingerprint);
+    CREATE INDEX IF NOT EXISTS tissues_by_file_path ON tissues (file_path);
+    CREATE TABLE IF NOT EXISTS links (
+        tissue_id INTEGER NOT NULL,
+        board TEXT NOT NULL,
+        issue_number TEXT NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS links_by_tissue ON links (tissue_id);
+    CREATE INDEX IF NOT EXISTS links_by_issue_number ON links (issue_number, board);
+    CREATE TABLE IF NOT EXISTS boards (name TEXT PRIMARY KEY);
+    CREATE TABLE IF NOT EXISTS meta (key TEXT PRIMARY KEY, value TEXT NOT NULL);
+";
+
+/// `TissueBoxRepository` stored in SQLite, for repositories with too many ToDos to rewrite a
+/// JSON file on every change.
+///
+/// Each tissue is kept as its JSON in `data`, next to indexed columns for the lookups the
+/// repository offers. Wrap a whole sync in `transaction` to write it all or nothing.
+pub struct TissueBoxSqlite {
+    connection: Connection,
+    in_transaction: bool,
+}
+
+impl TissueBoxSqlite {
+    pub fn open(path: impl AsRef<Path>) -> Result<TissueBoxSqlite> {
+        let path = path.as_ref();
+        let directory = path
+            .parent()
+            .filter(|directory| !directory.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        Self::init(
+            Connection::open(path)?,
+            &MigrationContext::for_directory(directory),
+        )
+    }
+
+    pub fn open_in_memory() -> Result<TissueBoxSqlite> {
+        Self::init(Connection::open_in_memory()?, &MigrationContext::default())
+    }
+
+    fn init(connection: Connection, context: &MigrationContext) -> Result<TissueBoxSqlite> {
+        let version: i64 = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+        if version > SCHEMA_VERSION {
+            return Err(color_eyre::Report::msg(format!(
+                "Tissue Box database uses schema version {}, but this tissue only understands up \
+                 to {}. Please upgrade tissue",
+                version, SCHEMA_VERSION
+            )));
+        }
+        let mut tissue_box = TissueBoxSqlite {
+            connection,
+            in_transaction: false,
+        };
+        tissue_box.transaction(|tissue_box| {
+            if version == 1 {
+                // v1 kept a single issue per tissue in an `issue_number` column.
+                tissue_box.connection.execute_batch(
+                    "DROP INDEX tissues_by_issue_number;
+                     ALTER TABLE tissues DROP COLUMN issue_number;",
+                )?;
+            }
+            tissue_box.connection.execute_batch(SCHEMA)?;
+            if version > 0 && version < SCHEMA_VERSION {
+                tissue_box.migrate_rows(version, context)?;
+            }
+            tissue_box
+                .connection
+                .pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            Ok(())
+        })?;
+        Ok(tissue_box)
+    }
+
+    /// Runs the JSON migrations over every stored tissue and rebuilds the index columns.
+    fn migrate_rows(&mut self, version: i64, context: &MigrationContext) -> Result<()> {
+        let mut statement = self.connection.prepare("SELECT id, data FROM tissues")?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+            })?
+            .collect::<rusqlite::Result<Vec<_>>>()?;
+        drop(statement);
+        let mut tissues = Vec::new();
+        for (_, data) in &rows {
+            tissues.push(serde_json::from_str::<serde_json::Value>(data)?);
+        }
+        let mut tissue_box = serde_json::json!({
+            "schema_version": version,
+            "boards": self.get_boards()?,
+            "tissues": tissues,
+        });
+        tissuebox_migration::migrate(&mut tissue_box, context)?;
+        let boards: Vec<String> = serde_json::from_value(tissue_box["boards"].take())?;
+        if boards != self.get_boards()? {
+            self.connection.execute("DELETE FROM boards", [])?;
+            for board in &boards {
+                self.connection
+                    .execute("INSERT OR IGNORE INTO boards (name) VALUES (?1)", [board])?;
+            }
+        }
+        let tissues: Vec<Tissue> = serde_json::from_value(tissue_box["tissues"].take())?;
+        for ((id, _), tissue) in rows.into_iter().zip(tissues) {
+            self.write_row(Some(id), &tissue)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `work` in a single transaction, rolling everything back if it fails.
+    pub fn transaction<T>(
+        &mut self,
+        work: impl FnOnce(&mut TissueBoxSqlite) -> Result<T>,
+    ) -> Result<T> {
+        if self.in_transaction {
+            return work(self);
+        }
+        self.connection.execute_batch("BEGIN IMMEDIATE")?;
+        self.in_transaction = true;
+        let result = work(self);
+        self.in_transaction = false;
+        match result {
+            Ok(value) => {
+                self.connection.execute_batch("COMMIT")?;
+                Ok(value)
+            }
+            Err(e) => {
+                self.connection.execute_batch("ROLLBACK")?;
+                Err(e)
+            }
+        }
+    }
+
+    pub fn last_commit(&self) -> Result<Option<String>> {
+        Ok(self
+            .connection
+            .query_row(
+                "SELECT value FROM meta WHERE key = 'last_commit'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?)
+    }
+
+    /// Copies every board and tissue from `source`, e.g. an existing `tissue_box.json`.
+    /// Returns the number of tissues imported.
+    pub fn import(&mut self, source: &dyn TissueBoxRepository) -> Result<usize> {
+        let boards = source.get_boards()?;
+        let tissues = source.get_tissues()?;
+        let count = tissues.len();
+        self.transaction(|tissue_box| {
+            for board in boards {
+                tissue_box.insert_board(&board)?;
+            }
+            tissue_box.upsert_tissues(tissues)
+        })?;
+        Ok(count)
+    }
+
+    /// Copies every board and tissue into `target`, e.g. a `tissue_box.json` to commit.
+    /// Returns the number of tissues exported.
+    pub fn export(&self, target: &mut dyn TissueBoxRepository) -> Result<usize> {
+        let tissues = self.get_tissues()?;
+        let count = tissues.len();
+        let existing = target.get_boards()?;
+        for board in self.get_boards()? {
+            if !existing.contains(&board) {
+                target.add_board(&NamedBoard(board))?;
+            }
+        }
+        target.upsert_tissues(tissues)?;
+        Ok(count)
+    }
+
+    fn query_tissues(&self, sql: &str, params: &[&str]) -> Result<Vec<Tissue>> {
+        let mut statement = self.connection.prepare(sql)?;
+        let rows = statement.query_map(params_from_iter(params), |row| row.get::<_, String>(0))?;
+        let mut tissues = Vec::new();
+        for data in rows {
+            tissues.push(serde_json::from_str(&data?)?);
+        }
+        Ok(tissues)
+    }
+
+    /// Row ID of the stored tissue that `Tissue::is_same` as `tissue`.
+    fn find_row(&self, tissue: &Tissue) -> Result<Option<i64>> {
+        for link in &tissue.links {
+            let Some(number) = link.id() else {
+                continue;
+            };
+            let row = self
+                .connection
+                .query_row(
+                    "SELECT tissue_id FROM links WHERE issue_number = ?1 AND board = ?2",
+                    [number.to_string(), link.board.clone()],
+                    |row| row.get(0),
+                )
+                .optional()?;
+            if row.is_some() {
+                return Ok(row);
+            }
+        }
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, data FROM tissues WHERE file_path = ?1")?;
+        let rows = statement.query_map([&tissue.todo.submission.file_path], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        })?;
+        for row in rows {
+            let (id, data) = row?;
+            let stored: Tissue = serde_json::from_str(&data)?;
+            if stored.is_same(tissue) {
+                return Ok(Some(id));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Writes `tissue` to row `id`, or to a new row, along with its links.
+    fn write_row(&self, id: Option<i64>, tissue: &Tissue) -> Result<()> {
+        let data = serde_json::to_string(tissue)?;
+        let fingerprint = tissue.todo.fingerprint();
+        let file_path = &tissue.todo.submission.file_path;
+        let id = match id {
+            Some(id) => {
+                self.connection.execute(
+                    "UPDATE tissues SET fingerprint = ?1, file_path = ?2, data = ?3 WHERE id = ?4",
+                    params![fingerprint, file_path, data, id],
+                )?;
+                self.connection
+                    .execute("DELETE FROM links WHERE tissue_id = ?1", [id])?;
+                id
+            }
+            None => {
+                self.connection.execute(
+                    "INSERT INTO tissues (fingerprint, file_path, data) VALUES (?1, ?2, ?3)",
+                    params![fingerprint, file_path, data],
+                )?;
+                self.connection.last_insert_rowid()
+            }
+        };
+        for link in &tissue.links {
+            if let Some(number) = link.id() {
+                self.connection.execute(
+                    "INSERT INTO links (tissue_id, board, issue_number) VALUES (?1, ?2, ?3)",
+                    params![id, link.board, number.to_string()],
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn insert_board(&self, name: &str) -> Result<()> {
+        self.connection
+            .execute("INSERT OR IGNORE INTO boards (name) VALUES (?1)", [name])?;
+        Ok(())
+    }
+
+    fn touch(&self) -> Result<()> {
+        self.connection.execute(
+            "INSERT OR REPLACE INTO meta (key, value) VALUES ('last_commit', ?1)",
+            [githandler::get_last_commit_hash()?],
+        )?;
+        Ok(())
+    }
+}
+
+/// Stands in for a board when only its name is known, e.g. when exporting.
+struct NamedBoard(String);
+
+#[async_trait::async_trait]
+impl IssueBoard for NamedBoard {
+    async fn get_issues(&self) -> Result<Vec<crate::issue_repository::Issue>> {
+        Err(color_eyre::Report::msg("not a real board"))
+    }
+    async fn get_issue(&self, _id: &IssueId) -> Result<crate::issue_repository::Issue> {
+        Err(color_eyre::Report::msg("not a real board"))
+    }
+    async fn add_issue(&self, _issue: crate::issue_repository::Issue) -> Result<IssueId> {
+        Err(color_eyre::Report::msg("not a real board"))
+    }
+    async fn update_issue(
+        &self,
+        _id: &IssueId,
+        _update: &crate::issue_repository::IssueUpdateRequest,
+    ) -> Result<()> {
+        Err(color_eyre::Report::msg("not a real board"))
+    }
+    async fn add_comment(&self, _id: &IssueId, _body: &str) -> Result<()> {
+        Err(color_eyre::Report::msg("not a real board"))
+    }
+    fn get_repository_name(&self) -> String {
+        self.0.clone()
+    }
+}
+
+impl TissueBoxRepository for TissueBoxSqlite {
+    fn get_tissues(&self) -> Result<Vec<Tissue>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT data FROM tissues ORDER BY id")?;
+        let rows = statement.query_map([], |row| row.get::<_, String>(0))?;
+        let mut tissues = Vec::new();
+        for data in rows {
+            tissues.push(serde_json::from_str(&data?)?);
+        }
+        Ok(tissues)
+    }
+
+    fn get_tissues_in_file(&self, file_path: &str) -> Result<Vec<Tissue>> {
+        self.query_tissues(
+            "SELECT data FROM tissues WHERE file_path = ?1 ORDER BY id",
+            &[file_path],
+        )
+    }
+
+    fn get_tissue_by_issue(&self, board: &str, number: &IssueId) -> Result<Option<Tissue>> {
+        Ok(self
+            .query_tissues(
+                "SELECT data FROM tissues JOIN links ON links.tissue_id = tissues.id \
+                 WHERE links.board = ?1 AND links.issue_number = ?2 LIMIT 1",
+                &[board, &number.to_string()],
+            )?
+            .pop())
+    }
+
+    fn get_tissue_by_fingerprint(&self, fingerprint: &str) -> Result<Option<Tissue>> {
+        Ok(self
+            .query_tissues(
+                "SELECT data FROM tissues WHERE fingerprint = ?1 LIMIT 1",
+                &[fingerprint],
+            )?
+            .pop())
+    }
+
+    fn upsert_tissues(&mut self, tissues: Vec<Tissue>) -> Result<()> {
+        self.transaction(|tissue_box| {
+            for tissue in tissues {
+                tissue_box.write_row(tissue_box.find_row(&tissue)?, &tissue)?;
+            }
+            tissue_box.touch()
+        })
+    }
+
+    fn remove_tissues(&mut self, tissues: &[Tissue]) -> Result<Vec<Tissue>> {
+        self.transaction(|tissue_box| {
+            let mut removed = Vec::new();
+            for tissue in tissues {
+                if let Some(id) = tissue_box.find_row(tissue)? {
+                    let data: String = tissue_box.connection.query_row(
+                        "DELETE FROM tissues WHERE id = ?1 RETURNING data",
+                        [id],
+                        |row| row.get(0),
+                    )?;
+                    tissue_box
+                        .connection
+                        .execute("DELETE FROM links WHERE tissue_id = ?1", [id])?;
+                    removed.push(serde_json::from_str(&data)?);
+                }
+            }
+            tissue_box.touch()?;
+            Ok(removed)
+        })
+    }
+
+    fn get_boards(&self) -> Result<Vec<String>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT name FROM boards ORDER BY rowid")?;
+        let names = statement.query_map([], |row| row.get(0))?;
+        Ok(names.collect::<rusqlite::Result<Vec<String>>>()?)
+    }
+
+    fn add_board(&mut self, issue_board: &dyn IssueBoard) -> Result<()> {
+        self.insert_board(&issue_board.get_repository_name())
+    }
+
+    fn remove_board(&mut self, board_name: &str) -> Result<String> {
+        let removed = self
+            .connection
+            .execute("DELETE FROM boards WHERE name = ?1", [board_name])?;
+        if removed == 0 {
+            return Err(color_eyre::Report::msg(format!(
+                "Board {} is not in the Tissue Box",
+                board_name
+            )));
+        }
+        Ok(board_name.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{local_board::LocalBoard, test_fixtures::tissue, testing::InMemoryTissueBox};
+
+    #[test]
+    fn upserts_and_looks_up_tissues() {
+        let mut tissue_box = TissueBoxSqlite::open_in_memory().unwrap();
+        tissue_box
+            .upsert_tissues(vec![
+                tissue(Some(1), "src/main.rs", "first draft"),
+                tissue(Some(2), "src/lib.rs", "second"),
+            ])
+            .unwrap();
+        tissue_box
+            .upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+
+        assert_eq!(tissue_box.get_tissues().unwrap().len(), 2);
+        assert_eq!(
+            tissue_box.get_tissues_in_file("src/main.rs").unwrap(),
+            vec![tissue(Some(1), "src/main.rs", "first")]
+        );
+        assert_eq!(
+            tissue_box
+                .get_tissue_by_issue("local:issues", &IssueId::from(2))
+                .unwrap()
+                .unwrap()
+                .todo
+                .description,
+            "second"
+        );
+        let fingerprint = tissue(None, "src/lib.rs", "second").todo.fingerprint();
+        assert!(tissue_box
+            .get_tissue_by_fingerprint(&fingerprint)
+            .unwrap()
+            .is_some());
+        assert!(tissue_box.last_commit().unwrap().is_some());
+    }
+
+    #[test]
+    fn removes_tissues_and_boards() {
+        let mut tissue_box = TissueBoxSqlite::open_in_memory().unwrap();
+        tissue_box
+            .upsert_tissues(vec![
+                tissue(Some(1), "src/main.rs", "first"),
+                tissue(Some(2), "src/main.rs", "second"),
+            ])
+            .unwrap();
+        tissue_box.add_board(&LocalBoard::new("issues")).unwrap();
+
+        let removed = tissue_box
+            .remove_tissues(&[tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+
+        assert_eq!(removed, vec![tissue(Some(1), "src/main.rs", "first")]);
+        assert_eq!(tissue_box.get_tissues().unwrap().len(), 1);
+        assert_eq!(tissue_box.get_boards().unwrap(), vec!["local:issues"]);
+        tissue_box.remove_board("local:issues").unwrap();
+        assert!(tissue_box.remove_board("local:issues").is_err());
+    }
+
+    #[test]
+    fn failed_transactions_roll_back() {
+        let mut tissue_box = TissueBoxSqlite::open_in_memory().unwrap();
+        let result: Result<()> = tissue_box.transaction(|tissue_box| {
+            tissue_box.upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])?;
+            Err(color_eyre::Report::msg("board unreachable"))
+        });
+
+        assert!(result.is_err());
+        assert!(tissue_box.get_tissues().unwrap().is_empty());
+    }
+
+    #[test]
+    fn imports_and_exports_other_tissue_boxes() {
+        let source = InMemoryTissueBox::new().with_tissues(vec![
+            tissue(Some(1), "src/main.rs", "first"),
+            tissue(Some(2), "src/lib.rs", "second"),
+        ]);
+        let mut tissue_box = TissueBoxSqlite::open_in_memory().unwrap();
+        tissue_box.add_board(&LocalBoard::new("issues")).unwrap();
+
+        assert_eq!(tissue_box.import(&source).unwrap(), 2);
+        let mut target = InMemoryTissueBox::new();
+        assert_eq!(tissue_box.export(&mut target).unwrap(), 2);
+
+        assert_eq!(target.tissues(), source.tissues());
+        assert_eq!(target.boards(), vec!["local:issues"]);
+    }
+
+    #[test]
+    fn upgrades_single_issue_databases() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tissue_box.sqlite");
+        let todo = serde_json::to_value(tissue(None, "src/main.rs", "first").todo).unwrap();
+        let data = serde_json::json!({
+            "issue": {
+                "number": 4, "title": "first", "author": {"name": "OthelloEngineer"},
+                "assignee": null, "issue_type": [], "state": "open", "body": null
+            },
+            "board": "jira:BILL",
+            "todo": todo
+        });
+        let connection = Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE tissues (id INTEGER PRIMARY KEY, fingerprint TEXT NOT NULL, \
+                 file_path TEXT NOT NULL, issue_number TEXT, data TEXT NOT NULL);
+                 CREATE INDEX tissues_by_issue_number ON tissues (issue_number);
+                 PRAGMA user_version = 1;",
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO tissues (fingerprint, file_path, issue_number, data) \
+                 VALUES ('', 'src/main.rs', '4', ?1)",
+                [data.to_string()],
+            )
+            .unwrap();
+        drop(connection);
+
+        let tissue_box = TissueBoxSqlite::open(&path).unwrap();
+
+        let tissue = tissue_box
+            .get_tissue_by_issue("jira:BILL", &IssueId::from(4))
+            .unwrap()
+            .unwrap();
+        assert!(tissue_box
+            .get_tissue_by_issue("local:issues", &IssueId::from(4))
+            .unwrap()
+            .is_none());
+        assert_eq!(tissue.links[0].board, "jira:BILL");
+        assert!(tissue_box
+            .get_tissue_by_fingerprint(&tissue.todo.fingerprint())
+            .unwrap()
+            .is_some());
+    }
+
+    #[test]
+    fn persists_to_disk() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("tissue_box.sqlite");
+        TissueBoxSqlite::open(&path)
+            .unwrap()
+            .upsert_tissues(vec![tissue(Some(1), "src/main.rs", "first")])
+            .unwrap();
+
+        let reopened = TissueBoxSqlite::open(&path).unwrap();
+        assert_eq!(reopened.get_tissues().unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,395 @@
+//! The ToDos a pull request adds, changes or removes, from the `git diff` between its base and
+//! head, summarised in Markdown for a comment on the pull request.
+use color_eyre::eyre::Result;
+
+use crate::{
+    githandler,
+    issue_repository::{IssueBoard, IssueId},
+    tissuebox_repository::{BoardLink, Tissue},
+    todofinder::{Submission, Syntax, ToDo},
+};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ToDoChangeKind {
+    Added,
+    /// Edited in place, e.g. reworded or reassigned. Holds the ToDo as it was.
+    Changed(Box<ToDo>),
+    Removed,
+}
+
+impl ToDoChangeKind {
+    fn title(&self) -> &str {
+        match self {
+            ToDoChangeKind::Added => "Added",
+            ToDoChangeKind::Changed(_) => "Changed",
+            ToDoChangeKind::Removed => "Removed",
+        }
+    }
+}
+
+/// A ToDo as it is after the change, or as it was if it was removed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ToDoChange {
+    pub kind: ToDoChangeKind,
+    pub todo: ToDo,
+    /// Issues the Tissue Box has for the ToDo, which removing it should close.
+    pub links: Vec<BoardLink>,
+}
+
+#[derive(Debug, Default)]
+pub struct ToDoDiff {
+    pub changes: Vec<ToDoChange>,
+}
+
+/// A ToDo line on one side of a diff, and the hunk it is in.
+struct DiffedToDo {
+    todo: ToDo,
+    file: usize,
+    hunk: usize,
+}
+
+impl ToDoDiff {
+    /// Classifies the ToDo lines of a unified diff. A removed and an added line with the same
+    /// fingerprint, or in the same hunk, are one changed ToDo; lines that only moved are left out.
+    pub fn from_diff(diff: &str, tissues: &[Tissue], syntax: &Syntax) -> ToDoDiff {
+        let (removed, added) = diffed_todos(diff, syntax);
+        let mut removed: Vec<Option<DiffedToDo>> = removed.into_iter().map(Some).collect();
+        let mut added: Vec<Option<DiffedToDo>> = added.into_iter().map(Some).collect();
+        let mut changes = Vec::new();
+
+        let pairings: [fn(&DiffedToDo, &DiffedToDo) -> bool; 2] = [
+            |before, after| before.todo.fingerprint() == after.todo.fingerprint(),
+            |before, after| before.file == after.file && before.hunk == after.hunk,
+        ];
+        for same_todo in pairings {
+            for before in removed.iter_mut() {
+                let Some(a) = before.as_ref().and_then(|before| {
+                    added
+                        .iter()
+                        .position(|after| after.as_ref().is_some_and(|a| same_todo(before, a)))
+                }) else {
+                    continue;
+                };
+                let before = before.take().unwrap().todo;
+                let after = added[a].take().unwrap().todo;
+                if before.submission.line != after.submission.line {
+                    changes.push(changed(before, after, tissues));
+                }
+            }
+        }
+
+        for before in removed.into_iter().flatten() {
+            changes.push(ToDoChange {
+                kind: ToDoChangeKind::Removed,
+                links: links_of(&before.todo, tissues),
+                todo: before.todo,
+            });
+        }
+        for after in added.into_iter().flatten() {
+            changes.push(ToDoChange {
+                kind: ToDoChangeKind::Added,
+                links: links_of(&after.todo, tissues),
+                todo: after.todo,
+            });
+        }
+        changes.sort_by(|a, b| {
+            (&a.todo.submission.file_path, a.todo.submission.line_number)
+                .cmp(&(&b.todo.submission.file_path, b.todo.submission.line_number))
+        });
+        ToDoDiff { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// A summary for a pull request comment, with the issues removed ToDos were filed as.
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("### ToDos in this change\n\n");
+        if self.is_empty() {
+            markdown.push_str("No ToDos were added, changed or removed.\n");
+            return markdown;
+        }
+        for title in ["Added", "Changed", "Removed"] {
+            let changes: Vec<&ToDoChange> = self
+                .changes
+                .iter()
+                .filter(|change| change.kind.title() == title)
+                .collect();
+            if changes.is_empty() {
+                continue;
+            }
+            markdown.push_str(&format!("**{} ({})**\n\n", title, changes.len()));
+            for change in changes {
+                markdown.push_str(&format!("- {}\n", markdown_item(change)));
+            }
+            markdown.push('\n');
+        }
+        markdown
+    }
+
+    /// Posts the summary as a comment on pull request `pull_request`, which the board treats as
+    /// an issue.
+    pub async fn post(&self, board: &dyn IssueBoard, pull_request: &IssueId) -> Result<()> {
+        board.add_comment(pull_request, &self.to_markdown()).await
+    }
+}
+
+/// Runs `git diff` of `range`, e.g. `main..feature`, in `directory` and classifies it.
+pub fn diff_range(
+    directory: &std::path::Path,
+    range: &str,
+    tissues: &[Tissue],
+    syntax: &Syntax,
+) -> Result<ToDoDiff> {
+    let diff = githandler::diff(directory, range)?;
+    Ok(ToDoDiff::from_diff(&diff, tissues, syntax))
+}
+
+fn changed(before: ToDo, after: ToDo, tissues: &[Tissue]) -> ToDoChange {
+    let mut links = links_of(&after, tissues);
+    if links.is_empty() {
+        links = links_of(&before, tissues);
+    }
+    ToDoChange {
+        kind: ToDoChangeKind::Changed(Box::new(before)),
+        todo: after,
+        links,
+    }
+}
+
+fn links_of(todo: &ToDo, tissues: &[Tissue]) -> Vec<BoardLink> {
+    let fingerprint = todo.fingerprint();
+    tissues
+        .iter()
+        .find(|tissue| tissue.todo.fingerprint() == fingerprint)
+        .map(|tissue| tissue.links.clone())
+        .unwrap_or_default()
+}
+
+fn markdown_item(change: &ToDoChange) -> String {
+    let todo = &change.todo;
+    let mut item = format!(
+        "`{}:{}` {}: {}",
+        todo.submission.file_path,
+        todo.submission.line_number,
+        todo.issue_type.as_str(),
+        todo.description
+    );
+    if let Some(assigned) = &todo.assigned {
+        item.push_str(&format!(" (assigned {})", assigned));
+    }
+    if let ToDoChangeKind::Changed(before) = &change.kind {
+        item.push_str(&format!(", was `{}`", before.submission.line));
+    }
+    if !change.links.is_empty() {
+        let links: Vec<String> = change.links.iter().map(issue_reference).collect();
+        let verb = match change.kind {
+            ToDoChangeKind::Removed => "can close",
+            _ => "tracked in",
+        };
+        item.push_str(&format!(" — {} {}", verb, links.join(", ")));
+    }
+    item
+}
+
+/// `org/repo#12` for GitHub issues, which GitHub turns into a link; `board#id` otherwise.
+fn issue_reference(link: &BoardLink) -> String {
+    match (link.board.strip_prefix("github:"), link.id()) {
+        (Some(repository), Some(id)) => format!("{}#{}", repository, id),
+        _ => link.to_string(),
+    }
+}
+
+/// The ToDo lines removed and added by `diff`, with their line numbers on each side.
+fn diffed_todos(diff: &str, syntax: &Syntax) -> (Vec<DiffedToDo>, Vec<DiffedToDo>) {
+    let mut removed = Vec::new();
+    let mut added = Vec::new();
+    for (file, diffed_file) in githandler::changed_lines_per_diffed_file(diff)
+        .into_iter()
+        .enumerate()
+    {
+        let mut old_path = diffed_file.file_path.clone();
+        let mut new_path = diffed_file.file_path.clone();
+        let (mut old_line, mut new_line, mut hunk) = (0, 0, 0);
+        for line in &diffed_file.changed_lines {
+            // Inside a hunk, `--- ` may just be a removed `-- ` comment.
+            let in_header = hunk == 0;
+            if let Some(path) = line.strip_prefix("--- ").filter(|_| in_header) {
+                old_path = path.strip_prefix("a/").unwrap_or(path).to_string();
+            } else if let Some(path) = line.strip_prefix("+++ ").filter(|_| in_header) {
+                new_path = path.strip_prefix("b/").unwrap_or(path).to_string();
+            } else if line.starts_with("@@") {
+                (old_line, new_line) = hunk_starts(line);
+                hunk += 1;
+            } else if let Some(text) = line.strip_prefix('-') {
+                if let Some(todo) = parse_todo(syntax, text, &old_path, old_line) {
+                    removed.push(DiffedToDo { todo, file, hunk });
+                }
+                old_line += 1;
+            } else if let Some(text) = line.strip_prefix('+') {
+                if let Some(todo) = parse_todo(syntax, text, &new_path, new_line) {
+                    added.push(DiffedToDo { todo, file, hunk });
+                }
+                new_line += 1;
+            } else if line.starts_with(' ') {
+                old_line += 1;
+                new_line += 1;
+            }
+        }
+    }
+    (removed, added)
+}
+
+/// The first old and new line numbers of a `@@ -12,3 +14,4 @@` hunk header.
+fn hunk_starts(header: &str) -> (usize, usize) {
+    let start = |prefix: char| {
+        header
+            .split_whitespace()
+            .find_map(|range| range.strip_prefix(prefix))
+            .and_then(|range| range.split(',').next())
+            .and_then(|line| line.parse().ok())
+            .unwrap_or(0)
+    };
+    (start('-'), start('+'))
+}
+
+fn parse_todo(syntax: &Syntax, text: &str, file_path: &str, line_number: usize) -> Option<ToDo> {
+    if !syntax.is_comment(text) || !syntax.is_to_do(text) {
+        return None;
+    }
+    syntax
+        .parse(Submission {
+            line_number,
+            line: text.trim().to_string(),
+            file_path: file_path.to_string(),
+            issuer: String::from("unknown"),
+            date: String::new(),
+        })
+        .ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{issue_repository::Issue, testing::InMemoryBoard};
+
+    const DIFF: &str = "\
+diff --git a/src/main.rs b/src/main.rs
+index 3d3ea22..8c82be5 100644
+--- a/src/main.rs
++++ b/src/main.rs
+@@ -2 +2 @@
+-    //TODO bug: parse flags; assigned OthelloEngineer
++    //TODO bug: parse flags; assigned jolee18
+@@ -10,0 +11,2 @@ fn main() {
++    //TODO feature: retry payments
++    let retries = 3;
+@@ -20 +22 @@ fn run() {
+-    //TODO improvement: cache lookups
++    //TODO improvement: cache lookups per user
+diff --git a/src/data/old.rs b/src/data/old.rs
+deleted file mode 100644
+index 3d3ea22..0000000
+--- a/src/data/old.rs
++++ /dev/null
+@@ -1,2 +0,0 @@
+-//TODO bug: crash on empty input
+-fn old() {}
+";
+
+    fn removed_tissue() -> Tissue {
+        let (removed, _) = diffed_todos(DIFF, &Syntax::default());
+        let todo = removed.last().unwrap().todo.clone();
+        let mut tissue = Tissue::new(todo.clone());
+        tissue.links.push(BoardLink {
+            board: "github:org/repo".to_string(),
+            issue: Issue {
+                number: Some(IssueId::Number(12)),
+                ..Issue::from(&todo)
+            },
+        });
+        tissue
+    }
+
+    #[test]
+    fn classifies_todo_changes() {
+        let diff = ToDoDiff::from_diff(DIFF, &[removed_tissue()], &Syntax::default());
+        let summary: Vec<(&str, usize, &str)> = diff
+            .changes
+            .iter()
+            .map(|change| {
+                let kind = match change.kind {
+                    ToDoChangeKind::Added => "added",
+                    ToDoChangeKind::Changed(_) => "changed",
+                    ToDoChangeKind::Removed => "removed",
+                };
+                let submission = &change.todo.submission;
+                (kind, submission.line_number, submission.file_path.as_str())
+            })
+            .collect();
+
+        assert_eq!(
+            summary,
+            [
+                ("removed", 1, "src/data/old.rs"),
+                ("changed", 2, "src/main.rs"),
+                ("added", 11, "src/main.rs"),
+                ("changed", 22, "src/main.rs"),
+            ]
+        );
+        assert_eq!(diff.changes[0].links.len(), 1);
+    }
+
+    #[test]
+    fn reads_removed_dash_comments_inside_hunks() {
+        let diff = "\
+diff --git a/db/schema.sql b/db/schema.sql
+index 3d3ea22..8c82be5 100644
+--- a/db/schema.sql
++++ b/db/schema.sql
+@@ -4,3 +4,2 @@
+--- TODO bug: x
+---TODO bug: drop the legacy table
++--TODO feature: index users by email
+ CREATE TABLE users (id INTEGER);
+";
+        // The removed `-- TODO bug: x` reads like a file header but is a line of the hunk.
+        let (removed, added) = diffed_todos(diff, &Syntax::default());
+
+        let located = |diffed: &[DiffedToDo]| {
+            let submission = &diffed[0].todo.submission;
+            (submission.file_path.clone(), submission.line_number)
+        };
+        assert_eq!(removed.len(), 1);
+        assert_eq!(removed[0].todo.description, "drop the legacy table");
+        assert_eq!(located(&removed), ("db/schema.sql".to_string(), 5));
+        assert_eq!(added.len(), 1);
+        assert_eq!(located(&added), ("db/schema.sql".to_string(), 4));
+    }
+
+    #[tokio::test]
+    async fn posts_a_markdown_summary() {
+        let diff = ToDoDiff::from_diff(DIFF, &[removed_tissue()], &Syntax::default());
+        let markdown = diff.to_markdown();
+
+        assert!(markdown.contains("**Added (1)**\n\n- `src/main.rs:11` feature: retry payments\n"));
+        assert!(markdown.contains(
+            "- `src/main.rs:2` bug: parse flags (assigned jolee18), \
+             was `//TODO bug: parse flags; assigned OthelloEngineer`\n"
+        ));
+        assert!(markdown
+            .contains("- `src/data/old.rs:1` bug: crash on empty input — can close org/repo#12\n"));
+
+        let board = InMemoryBoard::new("memory");
+        let pull_request = board
+            .add_issue(Issue::from(&diff.changes[0].todo))
+            .await
+            .unwrap();
+        diff.post(&board, &pull_request).await.unwrap();
+        assert_eq!(board.comments(), [(pull_request, markdown)]);
+        assert!(ToDoDiff::from_diff("", &[], &Syntax::default())
+            .to_markdown()
+            .contains("No ToDos"));
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Eq, PartialEq)]
+#[derive(Clone, Debug, Deserialize, Serialize, Eq, Hash, PartialEq)]
 pub enum IssueType {
     Bug,
     Feature,
@@ -8,6 +8,13 @@ pub enum IssueType {
     Other,
 }
 impl IssueType {
+    pub const ALL: [IssueType; 4] = [
+        IssueType::Bug,
+        IssueType::Feature,
+        IssueType::Improvement,
+        IssueType::Other,
+    ];
+
     pub fn as_str(&self) -> &str {
         match self {
             IssueType::Bug => "bug",
@@ -26,7 +33,7 @@ pub fn parse_issue_type(issue_type: &str) -> IssueType {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ToDo {
     pub submission: Submission,
     pub description: String,
@@ -34,6 +41,28 @@ pub struct ToDo {
     pub issue_type: IssueType,
 }
 
+impl ToDo {
+    /// Stable identifier for the ToDo that survives it moving to another line. Hex-encoded
+    /// 64-bit FNV-1a over the file, issue type and description.
+    pub fn fingerprint(&self) -> String {
+        let key = format!(
+            "{}\0{}\0{}",
+            self.submission.file_path,
+            self.issue_type.as_str(),
+            self.description
+        );
+        format!("{:016x}", fnv1a(key.as_bytes()))
+    }
+}
+
+/// 64-bit FNV-1a. Used instead of `DefaultHasher`, whose output may change between Rust
+/// releases, because fingerprints are persisted.
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq)]
 pub struct Submission {
     pub line_number: usize,
@@ -43,65 +72,131 @@ pub struct Submission {
     pub date: String,
 }
 
-pub fn is_to_do(line: &str) -> bool {
-    line.to_lowercase().contains("todo")
+/// How ToDo comments are recognised: the markers that start a comment, the keywords that make
+/// it a ToDo and the words accepted as each issue type. The default is tissue's own
+/// `//TODO bug: description; assigned NAME`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Syntax {
+    pub comment_markers: Vec<String>,
+    pub keywords: Vec<String>,
+    /// Words, without the trailing `:`, accepted as each issue type. Others parse as `Other`.
+    pub issue_types: Vec<(IssueType, Vec<String>)>,
 }
 
-// TODO bug: this function is not implemented; Assigned OthelloEngineer.
-// TODO feature: implement this function; Assigned OthelloEngineer.
-pub fn parse_submission(submission: Submission) -> Result<ToDo, String> {
-    let lower_line = submission.line.to_lowercase();
-    let original_words: Vec<&str> = submission.line.split_whitespace().collect();
-    let words: Vec<&str> = lower_line.split_whitespace().collect();
-    let issue_type = find_issue_type(words[1])?;
-    let mut assigned = None;
-    let mut description = String::from("");
-
-    let mut description_finished = false;
-    let next_word = 3;
-    for (i, word) in words[2..].iter().enumerate() {
-        if description_finished {
-            // To allow ; in description. If description is finished, then the rest word must be assigned.
-            assigned = Some(original_words[i + next_word..].join(" "));
-            break;
+impl Default for Syntax {
+    fn default() -> Syntax {
+        let strings = |strings: &[&str]| strings.iter().map(|s| s.to_string()).collect();
+        Syntax {
+            comment_markers: strings(&["//", "#", ";", "--"]),
+            keywords: strings(&["todo"]),
+            issue_types: [IssueType::Bug, IssueType::Feature, IssueType::Improvement]
+                .into_iter()
+                .map(|issue_type| (issue_type.clone(), vec![issue_type.as_str().to_string()]))
+                .collect(),
+        }
+    }
+}
+
+impl Syntax {
+    pub fn is_comment(&self, line: &str) -> bool {
+        let line = line.trim();
+        self.comment_markers
+            .iter()
+            .any(|marker| line.starts_with(marker.as_str()))
+    }
+
+    pub fn is_to_do(&self, line: &str) -> bool {
+        let line = line.to_lowercase();
+        self.keywords
+            .iter()
+            .any(|keyword| line.contains(&keyword.to_lowercase()))
+    }
+
+    // TODO bug: this function is not implemented; Assigned OthelloEngineer.
+    // TODO feature: implement this function; Assigned OthelloEngineer.
+    pub fn parse(&self, submission: Submission) -> Result<ToDo, String> {
+        let lower_line = submission.line.to_lowercase();
+        let original_words: Vec<&str> = submission.line.split_whitespace().collect();
+        let words: Vec<&str> = lower_line.split_whitespace().collect();
+        let Some(issue_type) = words.get(1) else {
+            return Err(format!(
+                "No issue type found at line nr. {} in {}",
+                submission.line_number, submission.file_path
+            ));
+        };
+        let issue_type = self.issue_type(issue_type)?;
+        let mut assigned = None;
+        let mut description = String::from("");
+
+        let mut description_finished = false;
+        let next_word = 3;
+        for (i, word) in words[2..].iter().enumerate() {
+            if description_finished {
+                // To allow ; in description. If description is finished, then the rest word must be assigned.
+                assigned = Some(original_words[i + next_word..].join(" "));
+                break;
+            }
+
+            if word.ends_with(";") && words.get(i + 3) == Some(&"assigned") {
+                description_finished = true;
+                let no_semicolon_word = word.trim_end_matches(";");
+                description.push_str(no_semicolon_word);
+                continue;
+            }
+            description.push_str(format!("{} ", word).as_str());
         }
 
-        if word.ends_with(";") && words[i + 3].eq("assigned") {
-            description_finished = true;
-            let no_semicolon_word = word.trim_end_matches(";");
-            description.push_str(format!("{}", no_semicolon_word).as_str());
-            continue;
+        if description.is_empty() {
+            return Err(format!(
+                "No description found after {} {} at line nr. {} in {} \n comment: '{}'",
+                words[0], words[1], submission.line_number, submission.file_path, submission.line
+            ));
         }
-        description.push_str(format!("{} ", word).as_str());
+
+        Ok(ToDo {
+            submission,
+            description: description.trim().to_string(),
+            assigned,
+            issue_type,
+        })
     }
 
-    if description.is_empty() {
-        return Err(format!(
-            "No description found after {} {} at line nr. {} in {} \n comment: '{}'",
-            words[0], words[1], submission.line_number, submission.file_path, submission.line
-        ));
+    fn issue_type(&self, word: &str) -> Result<IssueType, String> {
+        let Some(name) = word.strip_suffix(':') else {
+            return Err(format!(
+                "Cannot confirm issue type. Does not end with ':'. Found issue type {}",
+                word
+            ));
+        };
+        let issue_type = self
+            .issue_types
+            .iter()
+            .find(|(_, names)| names.iter().any(|n| n.eq_ignore_ascii_case(name)))
+            .map_or(IssueType::Other, |(issue_type, _)| issue_type.clone());
+        Ok(issue_type)
     }
+}
 
-    Ok(ToDo {
-        submission: submission,
-        description: description.trim().to_string(),
-        assigned: assigned,
-        issue_type: issue_type,
-    })
+pub fn is_to_do(line: &str) -> bool {
+    Syntax::default().is_to_do(line)
 }
 
-fn find_issue_type(line: &str) -> Result<IssueType, String> {
-    if !line.ends_with(":") {
-        return Err(format!(
-            "Cannot confirm issue type. Does not end with ':'. Found issue type {}",
-            line
-        ));
-    }
-    match line {
-        "bug:" => Ok(IssueType::Bug),
-        "feature:" => Ok(IssueType::Feature),
-        "improvement:" => Ok(IssueType::Improvement),
-        _ => Ok(IssueType::Other),
+/// Parses a ToDo in the default syntax.
+pub fn parse_submission(submission: Submission) -> Result<ToDo, String> {
+    Syntax::default().parse(submission)
+}
+
+/// Rewrites the `; assigned NAME` part of a ToDo comment `line`, removing it if `assignee` is
+/// `None`.
+pub fn with_assignee(line: &str, assignee: Option<&str>) -> String {
+    let line = line.trim_end();
+    let without = match line.to_ascii_lowercase().find("; assigned") {
+        Some(index) => &line[..index],
+        None => line,
+    };
+    match assignee {
+        Some(assignee) => format!("{}; assigned {}", without, assignee),
+        None => without.to_string(),
     }
 }
 
@@ -111,20 +206,26 @@ mod tests {
 
     #[test]
     fn test_is_to_do() {
-        assert_eq!(is_to_do("TODO: implement this function"), true);
-        assert_eq!(is_to_do("todo: implement this function"), true);
-        assert_eq!(is_to_do("to do: implement this function"), false);
-        assert_eq!(is_to_do("to do implement this function"), false);
+        assert!(is_to_do("TODO: implement this function"));
+        assert!(is_to_do("todo: implement this function"));
+        assert!(!is_to_do("to do: implement this function"));
+        assert!(!is_to_do("to do implement this function"));
     }
 
     #[test]
     fn test_find_issue_type() {
-        assert_eq!(find_issue_type("bug:"), Ok(IssueType::Bug));
-        assert_eq!(find_issue_type("feature:"), Ok(IssueType::Feature));
-        assert_eq!(find_issue_type("improvement:"), Ok(IssueType::Improvement));
-        assert_eq!(find_issue_type("other:"), Ok(IssueType::Other));
+        assert_eq!(Syntax::default().issue_type("bug:"), Ok(IssueType::Bug));
+        assert_eq!(
+            Syntax::default().issue_type("feature:"),
+            Ok(IssueType::Feature)
+        );
         assert_eq!(
-            find_issue_type("other"),
+            Syntax::default().issue_type("improvement:"),
+            Ok(IssueType::Improvement)
+        );
+        assert_eq!(Syntax::default().issue_type("other:"), Ok(IssueType::Other));
+        assert_eq!(
+            Syntax::default().issue_type("other"),
             Err(
                 "Cannot confirm issue type. Does not end with ':'. Found issue type other"
                     .to_string()
@@ -132,6 +233,42 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_fingerprint_ignores_line_number() {
+        let submission = Submission {
+            line_number: 1,
+            line: String::from("TODO bug: implement this function"),
+            file_path: String::from("src/todofinder.rs"),
+            issuer: String::from("OthelloEngineer"),
+            date: String::from("2021-09-01"),
+        };
+        let moved = Submission {
+            line_number: 42,
+            ..submission.clone()
+        };
+        let to_do = parse_submission(submission).unwrap();
+        assert_eq!(
+            to_do.fingerprint(),
+            parse_submission(moved).unwrap().fingerprint()
+        );
+        assert_eq!(to_do.fingerprint().len(), 16);
+        assert_eq!(fnv1a(b""), 0xcbf29ce484222325);
+    }
+
+    #[test]
+    fn test_with_assignee() {
+        let line = "    // TODO bug: fix this; Assigned OthelloEngineer";
+        assert_eq!(
+            with_assignee(line, Some("jolee18")),
+            "    // TODO bug: fix this; assigned jolee18"
+        );
+        assert_eq!(with_assignee(line, None), "    // TODO bug: fix this");
+        assert_eq!(
+            with_assignee("// TODO bug: fix this", Some("jolee18")),
+            "// TODO bug: fix this; assigned jolee18"
+        );
+    }
+
     #[test]
     fn test_parse_to_do_line() {
         let submission = Submission {
@@ -178,4 +315,18 @@ mod tests {
         assert_eq!(to_do.assigned, Some(String::from("OthelloEngineer")));
         assert_eq!(to_do.issue_type, IssueType::Feature);
     }
+
+    #[test]
+    fn test_parse_incomplete_lines_without_panicking() {
+        let submission = |line: &str| Submission {
+            line_number: 1,
+            line: String::from(line),
+            file_path: String::from("src/todofinder.rs"),
+            issuer: String::from("OthelloEngineer"),
+            date: String::from("2021-09-01"),
+        };
+        assert!(parse_submission(submission("TODO")).is_err());
+        let to_do = parse_submission(submission("TODO bug: trailing;")).unwrap();
+        assert_eq!(to_do.description, "trailing;");
+    }
 }
@@ -0,0 +1,556 @@
+//! A parallel directory walk for large trees. Worker threads share a bounded queue of
+//! directories and read each file as they come across it; the comment lines stream out through a
+//! bounded channel, so memory stays flat however many files there are.
+use std::{
+    collections::{HashSet, VecDeque},
+    fmt, fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender},
+        Arc, Condvar, Mutex,
+    },
+    thread,
+};
+
+use crate::{
+    filereader::FileLines,
+    todofinder::{Submission, Syntax},
+};
+
+/// Settings for a walk; `walk` starts it.
+#[derive(Clone, Debug)]
+pub struct Walker {
+    root: PathBuf,
+    threads: usize,
+    queue_size: usize,
+    max_depth: Option<usize>,
+    follow_symlinks: bool,
+    syntax: Syntax,
+    /// Off for `walk_paths`, which only lists the files.
+    read_files: bool,
+}
+
+impl Walker {
+    /// Walks `root` on a thread per core, following symlinks and skipping `.git` directories.
+    pub fn new(root: &Path) -> Walker {
+        Walker {
+            root: root.to_path_buf(),
+            threads: thread::available_parallelism().map_or(4, |n| n.get()),
+            queue_size: 256,
+            max_depth: None,
+            follow_symlinks: true,
+            syntax: Syntax::default(),
+            read_files: true,
+        }
+    }
+
+    pub fn with_threads(mut self, threads: usize) -> Walker {
+        self.threads = threads.max(1);
+        self
+    }
+
+    /// How many read files may wait for the consumer before the workers block, and how many
+    /// directories may wait for a worker before the one that found them reads them itself.
+    pub fn with_queue_size(mut self, queue_size: usize) -> Walker {
+        self.queue_size = queue_size.max(1);
+        self
+    }
+
+    /// Leaves out directories nested deeper than `max_depth` below the root.
+    pub fn with_max_depth(mut self, max_depth: usize) -> Walker {
+        self.max_depth = Some(max_depth);
+        self
+    }
+
+    pub fn with_follow_symlinks(mut self, follow_symlinks: bool) -> Walker {
+        self.follow_symlinks = follow_symlinks;
+        self
+    }
+
+    /// Which lines count as comments, and as ToDos for `Walk::submissions`.
+    pub fn with_syntax(mut self, syntax: Syntax) -> Walker {
+        self.syntax = syntax;
+        self
+    }
+
+    /// The paths of every file under the root, text or not, without reading them.
+    pub fn walk_paths(mut self) -> impl Iterator<Item = Result<String, String>> {
+        self.read_files = false;
+        self.walk().map(|file| file.map(|file| file.file_path))
+    }
+
+    pub fn walk(self) -> Walk {
+        let (sender, receiver) = mpsc::sync_channel(self.queue_size);
+        let progress = Arc::new(Progress::default());
+        progress.running.store(self.threads, Ordering::SeqCst);
+        let root = fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone());
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue {
+                directories: VecDeque::from([(self.root.clone(), root.clone(), 0)]),
+                pending: 1,
+            }),
+            ready: Condvar::new(),
+            visited: Mutex::new(HashSet::from([root.clone()])),
+            files: Mutex::new(HashSet::new()),
+            root,
+            cancelled: AtomicBool::new(false),
+        });
+        let syntax = self.syntax.clone();
+        let walker = Arc::new(self);
+        for _ in 0..walker.threads {
+            let (walker, shared, sender, progress) = (
+                walker.clone(),
+                shared.clone(),
+                sender.clone(),
+                progress.clone(),
+            );
+            thread::spawn(move || {
+                work(&walker, &shared, &sender, &progress);
+                progress.running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        Walk {
+            receiver,
+            progress,
+            syntax,
+        }
+    }
+}
+
+/// Counts of what a walk has done so far, updated by its workers as they go.
+#[derive(Debug, Default)]
+pub struct Progress {
+    directories: AtomicUsize,
+    files: AtomicUsize,
+    /// Files that aren't UTF-8 text, and symlinks that lead nowhere, out of the root or back
+    /// into the walk.
+    skipped: AtomicUsize,
+    failed: AtomicUsize,
+    running: AtomicUsize,
+}
+
+impl Progress {
+    pub fn directories(&self) -> usize {
+        self.directories.load(Ordering::Relaxed)
+    }
+
+    pub fn files(&self) -> usize {
+        self.files.load(Ordering::Relaxed)
+    }
+
+    pub fn skipped(&self) -> usize {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> usize {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    /// Whether every worker has stopped.
+    pub fn is_finished(&self) -> bool {
+        self.running.load(Ordering::SeqCst) == 0
+    }
+}
+
+impl fmt::Display for Progress {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} files in {} directories, {} skipped, {} unreadable",
+            self.files(),
+            self.directories(),
+            self.skipped(),
+            self.failed()
+        )
+    }
+}
+
+/// A running walk: the comment lines of each file read, in no particular order, or an error for
+/// each file or directory that couldn't be read. Dropping it stops the workers.
+pub struct Walk {
+    receiver: Receiver<Result<FileLines, String>>,
+    progress: Arc<Progress>,
+    syntax: Syntax,
+}
+
+impl Walk {
+    pub fn progress(&self) -> Arc<Progress> {
+        self.progress.clone()
+    }
+
+    /// The ToDo lines of the walk as submissions, without blame: the issuer is "unknown".
+    pub fn submissions(self) -> impl Iterator<Item = Result<Submission, String>> {
+        let syntax = self.syntax.clone();
+        self.flat_map(move |file| {
+            let file = match file {
+                Ok(file) => file,
+                Err(e) => return vec![Err(e)],
+            };
+            let mut lines: Vec<(usize, String)> = file
+                .lines
+                .into_iter()
+                .filter(|(_, line)| syntax.is_to_do(line))
+                .collect();
+            lines.sort();
+            lines
+                .into_iter()
+                .map(|(line_number, line)| {
+                    Ok(Submission {
+                        line_number,
+                        line: line.trim().to_string(),
+                        file_path: file.file_path.clone(),
+                        issuer: String::from("unknown"),
+                        date: String::new(),
+                    })
+                })
+                .collect()
+        })
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<FileLines, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+}
+
+struct Queue {
+    /// Directories with their canonical paths and depths, at most `Walker::queue_size`.
+    directories: VecDeque<(PathBuf, PathBuf, usize)>,
+    /// Directories queued or being read; the walk is over when none are left.
+    pending: usize,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    ready: Condvar,
+    /// Canonical paths of the directories entered, so symlink loops are only followed once.
+    visited: Mutex<HashSet<PathBuf>>,
+    /// Canonical paths of the files read, so a file linked to twice is only read once.
+    files: Mutex<HashSet<PathBuf>>,
+    /// The canonical root, which symlinks may not lead out of.
+    root: PathBuf,
+    cancelled: AtomicBool,
+}
+
+type Sender = SyncSender<Result<FileLines, String>>;
+
+fn work(walker: &Walker, shared: &Shared, sender: &Sender, progress: &Progress) {
+    loop {
+        let (directory, canonical, depth) = {
+            let mut queue = shared.queue.lock().unwrap();
+            loop {
+                if shared.cancelled.load(Ordering::SeqCst) {
+                    return;
+                }
+                if let Some(next) = queue.directories.pop_front() {
+                    break next;
+                }
+                if queue.pending == 0 {
+                    return;
+                }
+                queue = shared.ready.wait(queue).unwrap();
+            }
+        };
+        // Subdirectories that didn't fit in the queue, read here instead of recursing so deep
+        // trees don't grow the stack.
+        let mut overflow = vec![(directory, canonical, depth)];
+        let mut read = Ok(());
+        while let Some(next) = overflow.pop() {
+            read = read_directory(walker, shared, sender, progress, next, &mut overflow);
+            if read.is_err() {
+                break;
+            }
+        }
+        if read.is_err() {
+            // The consumer is gone.
+            shared.cancelled.store(true, Ordering::SeqCst);
+        }
+        let mut queue = shared.queue.lock().unwrap();
+        queue.pending -= 1;
+        if queue.pending == 0 || read.is_err() {
+            shared.ready.notify_all();
+        }
+    }
+}
+
+/// Reads the files of `directory`, whose canonical path is `canonical`, and queues its
+/// subdirectories, or adds them to `overflow` if the queue is full. Fails only if the consumer
+/// hung up.
+fn read_directory(
+    walker: &Walker,
+    shared: &Shared,
+    sender: &Sender,
+    progress: &Progress,
+    (directory, canonical, depth): (PathBuf, PathBuf, usize),
+    overflow: &mut Vec<(PathBuf, PathBuf, usize)>,
+) -> Result<(), ()> {
+    let fail = |message: String| {
+        progress.failed.fetch_add(1, Ordering::Relaxed);
+        sender.send(Err(message)).map_err(|_| ())
+    };
+    let entries = match fs::read_dir(&directory) {
+        Ok(entries) => entries,
+        Err(e) => return fail(format!("{}: {}", directory.display(), e)),
+    };
+    progress.directories.fetch_add(1, Ordering::Relaxed);
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                fail(format!("{}: {}", directory.display(), e))?;
+                continue;
+            }
+        };
+        let path = entry.path();
+        let Ok(file_type) = entry.file_type() else {
+            fail(format!("{}: unknown file type", path.display()))?;
+            continue;
+        };
+        let (is_dir, is_file, target) = if file_type.is_symlink() {
+            let target = fs::canonicalize(&path)
+                .ok()
+                .filter(|target| target.starts_with(&shared.root));
+            match (target, fs::metadata(&path)) {
+                (Some(target), Ok(metadata)) if walker.follow_symlinks => {
+                    (metadata.is_dir(), metadata.is_file(), target)
+                }
+                _ => {
+                    progress.skipped.fetch_add(1, Ordering::Relaxed);
+                    continue;
+                }
+            }
+        } else {
+            let target = canonical.join(entry.file_name());
+            (file_type.is_dir(), file_type.is_file(), target)
+        };
+
+        if is_dir {
+            if entry.file_name() == ".git" || walker.max_depth.is_some_and(|max| depth >= max) {
+                continue;
+            }
+            if !shared.visited.lock().unwrap().insert(target.clone()) {
+                progress.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            let mut queue = shared.queue.lock().unwrap();
+            if queue.directories.len() < walker.queue_size {
+                queue.directories.push_back((path, target, depth + 1));
+                queue.pending += 1;
+                shared.ready.notify_one();
+            } else {
+                overflow.push((path, target, depth + 1));
+            }
+        } else if is_file {
+            if !shared.files.lock().unwrap().insert(target) {
+                progress.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            }
+            if !walker.read_files {
+                progress.files.fetch_add(1, Ordering::Relaxed);
+                sender
+                    .send(Ok(FileLines {
+                        file_path: path.display().to_string(),
+                        lines: Default::default(),
+                    }))
+                    .map_err(|_| ())?;
+                continue;
+            }
+            let source = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    fail(format!("{}: {}", path.display(), e))?;
+                    continue;
+                }
+            };
+            let Ok(source) = String::from_utf8(source) else {
+                progress.skipped.fetch_add(1, Ordering::Relaxed);
+                continue;
+            };
+            let lines = source
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| walker.syntax.is_comment(line))
+                .map(|(index, line)| (index + 1, line.to_string()))
+                .collect();
+            progress.files.fetch_add(1, Ordering::Relaxed);
+            sender
+                .send(Ok(FileLines {
+                    file_path: path.display().to_string(),
+                    lines,
+                }))
+                .map_err(|_| ())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> tempfile::TempDir {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        fs::create_dir_all(root.join("src/deep/deeper")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(
+            root.join("main.rs"),
+            "//TODO bug: parse flags\nfn main() {}\n",
+        )
+        .unwrap();
+        fs::write(
+            root.join("src/lib.rs"),
+            "// a comment\n# TODO feature: retry\n",
+        )
+        .unwrap();
+        fs::write(root.join("src/deep/deeper/x.py"), "# TODO bug: deep\n").unwrap();
+        fs::write(root.join("src/logo.png"), [0x89, 0x50, 0xff, 0xfe]).unwrap();
+        fs::write(root.join(".git/HEAD"), "// TODO bug: not ours\n").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("..", root.join("src/deep/parent")).unwrap();
+        directory
+    }
+
+    fn relative_paths(root: &Path, walk: Walk) -> Vec<String> {
+        let mut paths: Vec<String> = walk
+            .map(|file| file.unwrap().file_path)
+            .map(|path| {
+                Path::new(&path)
+                    .strip_prefix(root)
+                    .unwrap()
+                    .display()
+                    .to_string()
+            })
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[test]
+    fn walks_every_text_file_once() {
+        let directory = tree();
+        let root = directory.path();
+        let walk = Walker::new(root).with_threads(3).with_queue_size(1).walk();
+        let progress = walk.progress();
+
+        assert_eq!(
+            relative_paths(root, walk),
+            ["main.rs", "src/deep/deeper/x.py", "src/lib.rs"]
+        );
+        assert_eq!(progress.files(), 3);
+        assert_eq!(progress.skipped(), if cfg!(unix) { 2 } else { 1 });
+        assert_eq!(progress.failed(), 0);
+
+        let shallow = Walker::new(root).with_max_depth(1).walk();
+        assert_eq!(relative_paths(root, shallow), ["main.rs", "src/lib.rs"]);
+    }
+
+    #[test]
+    fn lists_paths_without_reading_files() {
+        let directory = tree();
+        let root = directory.path();
+        let mut paths: Vec<String> = Walker::new(root)
+            .walk_paths()
+            .map(|path| path.unwrap())
+            .collect();
+        paths.sort();
+
+        let expected: Vec<String> = [
+            "main.rs",
+            "src/deep/deeper/x.py",
+            "src/lib.rs",
+            "src/logo.png",
+        ]
+        .iter()
+        .map(|path| root.join(path).display().to_string())
+        .collect();
+        assert_eq!(paths, expected);
+    }
+
+    #[test]
+    fn walks_deep_trees_with_a_full_queue() {
+        let directory = tempfile::tempdir().unwrap();
+        let deepest = (0..500).fold(directory.path().to_path_buf(), |path, depth| {
+            path.join(depth.to_string())
+        });
+        fs::create_dir_all(&deepest).unwrap();
+        fs::write(deepest.join("x.py"), "# TODO bug: deep\n").unwrap();
+
+        let walk = Walker::new(directory.path())
+            .with_threads(1)
+            .with_queue_size(1)
+            .walk();
+        let progress = walk.progress();
+
+        assert_eq!(walk.count(), 1);
+        assert_eq!(progress.directories(), 501);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stays_in_the_root_and_reads_linked_files_once() {
+        use std::os::unix::fs::symlink;
+
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path().join("repo");
+        for name in ["a", "b", "c"] {
+            fs::create_dir_all(root.join(name)).unwrap();
+            fs::write(root.join(name).join("mod.rs"), "// TODO bug: x\n").unwrap();
+        }
+        fs::create_dir_all(directory.path().join("outside")).unwrap();
+        fs::write(
+            directory.path().join("outside/secret.rs"),
+            "// TODO bug: x\n",
+        )
+        .unwrap();
+        fs::write(root.join("main.rs"), "// TODO bug: x\n").unwrap();
+        symlink("../main.rs", root.join("a/alias.rs")).unwrap();
+        symlink("../outside", root.join("outside")).unwrap();
+        symlink("../outside/secret.rs", root.join("secret.rs")).unwrap();
+
+        // With room for one queued directory, the worker reads the others as it finds them.
+        let walk = Walker::new(&root).with_threads(1).with_queue_size(1).walk();
+        let progress = walk.progress();
+
+        let mut paths = relative_paths(&root, walk);
+        // Whichever of main.rs and its alias comes first is read.
+        paths.retain(|path| path != "main.rs" && path != "a/alias.rs");
+        assert_eq!(paths, ["a/mod.rs", "b/mod.rs", "c/mod.rs"]);
+        assert_eq!(progress.files(), 4);
+        assert_eq!(progress.skipped(), 3);
+    }
+
+    #[test]
+    fn streams_submissions_and_stops_when_dropped() {
+        let directory = tree();
+        let root = directory.path();
+
+        let mut submissions: Vec<Submission> = Walker::new(root)
+            .walk()
+            .submissions()
+            .map(Result::unwrap)
+            .collect();
+        submissions.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+        let lines: Vec<&str> = submissions.iter().map(|s| s.line.as_str()).collect();
+        assert_eq!(
+            lines,
+            [
+                "//TODO bug: parse flags",
+                "# TODO bug: deep",
+                "# TODO feature: retry"
+            ]
+        );
+
+        let mut walk = Walker::new(root).with_threads(1).with_queue_size(1).walk();
+        let progress = walk.progress();
+        assert!(walk.next().is_some());
+        drop(walk);
+        while !progress.is_finished() {
+            thread::yield_now();
+        }
+    }
+}
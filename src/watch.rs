@@ -0,0 +1,249 @@
+//! `tissue watch`: keeps an index of the ToDos in a working tree up to date from filesystem
+//! notifications, re-parsing only the files that changed.
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    fmt,
+    path::{Path, PathBuf},
+    sync::mpsc::{Receiver, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+
+use color_eyre::eyre::Result;
+use notify::{Event, EventKind};
+use serde::Serialize;
+
+use crate::{
+    githandler, scanner,
+    todofinder::{Syntax, ToDo},
+};
+
+/// How long to wait for a burst of notifications, e.g. an editor's save, to settle.
+const DEBOUNCE: Duration = Duration::from_millis(200);
+
+/// A change to the ToDos in the working tree. A ToDo that only moved to another line is
+/// neither added nor removed.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+#[serde(tag = "event", content = "todo", rename_all = "snake_case")]
+pub enum WatchEvent {
+    Added(ToDo),
+    Removed(ToDo),
+}
+
+impl fmt::Display for WatchEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (sign, todo) = match self {
+            WatchEvent::Added(todo) => ('+', todo),
+            WatchEvent::Removed(todo) => ('-', todo),
+        };
+        write!(
+            f,
+            "{} {}:{} {}: {}",
+            sign,
+            todo.submission.file_path,
+            todo.submission.line_number,
+            todo.issue_type.as_str(),
+            todo.description
+        )
+    }
+}
+
+/// The ToDos in the working tree at `root`, by file, and when each was first seen.
+pub struct WatchIndex {
+    root: PathBuf,
+    syntax: Syntax,
+    files: BTreeMap<String, Vec<ToDo>>,
+    first_seen: HashMap<String, Instant>,
+}
+
+impl WatchIndex {
+    /// Indexes the files git tracks in `root`. Their ToDos count as first seen now.
+    pub fn new(root: &Path, syntax: Syntax) -> Result<WatchIndex> {
+        let now = Instant::now();
+        let mut index = WatchIndex {
+            root: root.to_path_buf(),
+            syntax,
+            files: BTreeMap::new(),
+            first_seen: HashMap::new(),
+        };
+        for todo in scanner::scan(root, &index.syntax)?.todos {
+            index.first_seen.insert(todo.fingerprint(), now);
+            index
+                .files
+                .entry(todo.submission.file_path.clone())
+                .or_default()
+                .push(todo);
+        }
+        Ok(index)
+    }
+
+    pub fn todos(&self) -> impl Iterator<Item = &ToDo> {
+        self.files.values().flatten()
+    }
+
+    /// Re-parses `file_path`, relative to the root, and returns how its ToDos changed.
+    pub fn update(&mut self, file_path: &str, now: Instant) -> Vec<WatchEvent> {
+        let todos = scanner::scan_file(&self.root, file_path, &self.syntax).todos;
+        let old = self.files.remove(file_path).unwrap_or_default();
+        let fingerprints =
+            |todos: &[ToDo]| -> BTreeSet<String> { todos.iter().map(ToDo::fingerprint).collect() };
+        let (old_fingerprints, new_fingerprints) = (fingerprints(&old), fingerprints(&todos));
+
+        let mut events = Vec::new();
+        for todo in old {
+            let fingerprint = todo.fingerprint();
+            if !new_fingerprints.contains(&fingerprint) {
+                self.first_seen.remove(&fingerprint);
+                events.push(WatchEvent::Removed(todo));
+            }
+        }
+        for todo in &todos {
+            let fingerprint = todo.fingerprint();
+            if !old_fingerprints.contains(&fingerprint) {
+                self.first_seen.entry(fingerprint).or_insert(now);
+                events.push(WatchEvent::Added(todo.clone()));
+            }
+        }
+        if !todos.is_empty() {
+            self.files.insert(file_path.to_string(), todos);
+        }
+        events
+    }
+
+    /// The ToDos that have been in the tree unchanged for at least `stable_for`.
+    pub fn stable(&self, stable_for: Duration, now: Instant) -> Vec<&ToDo> {
+        self.todos()
+            .filter(|todo| {
+                self.first_seen
+                    .get(&todo.fingerprint())
+                    .is_some_and(|seen| now.saturating_duration_since(*seen) >= stable_for)
+            })
+            .collect()
+    }
+
+    /// Waits up to `timeout` for notifications from a watcher on the root, then updates the
+    /// files they name once they settle. Returns no events if nothing changed in time, and an
+    /// error once the watcher is gone.
+    pub fn next_changes(
+        &mut self,
+        receiver: &Receiver<notify::Result<Event>>,
+        timeout: Duration,
+    ) -> Result<Vec<WatchEvent>> {
+        let mut changed = BTreeSet::new();
+        let mut wait = timeout;
+        loop {
+            match receiver.recv_timeout(wait) {
+                Ok(event) => {
+                    changed.extend(changed_files(&self.root, &event?));
+                    wait = DEBOUNCE;
+                }
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err(color_eyre::Report::msg("The file watcher stopped"))
+                }
+            }
+        }
+        let now = Instant::now();
+        let mut events = Vec::new();
+        for file_path in changed {
+            if githandler::is_ignored(&self.root, &file_path)? {
+                continue;
+            }
+            events.extend(self.update(&file_path, now));
+        }
+        Ok(events)
+    }
+}
+
+/// The files under `root` that `event` reports changed, relative to `root`. Git's own files and
+/// tissue's are left out.
+pub fn changed_files(root: &Path, event: &Event) -> Vec<String> {
+    if matches!(event.kind, EventKind::Access(_)) {
+        return vec![];
+    }
+    event
+        .paths
+        .iter()
+        .filter(|path| !path.is_dir())
+        .filter_map(|path| path.strip_prefix(root).ok())
+        .filter(|path| {
+            !path.starts_with(".git")
+                && !path.starts_with(".tissue")
+                && !path.starts_with("tissue_box.json")
+        })
+        .map(|path| path.display().to_string())
+        .filter(|path| !path.is_empty())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use notify::event::{AccessKind, CreateKind, ModifyKind};
+
+    use super::*;
+    use crate::test_fixtures::git;
+
+    fn descriptions(events: &[WatchEvent]) -> Vec<String> {
+        events.iter().map(|event| event.to_string()).collect()
+    }
+
+    #[test]
+    fn updates_changed_files_and_tracks_stable_todos() {
+        let directory = tempfile::tempdir().unwrap();
+        let root = directory.path();
+        git(root, &["init", "-q"]);
+        fs::write(root.join("main.rs"), "//TODO bug: parse flags\n").unwrap();
+        git(root, &["add", "."]);
+        git(root, &["commit", "-q", "-m", "init"]);
+        let mut index = WatchIndex::new(root, Syntax::default()).unwrap();
+        let start = Instant::now();
+
+        fs::write(
+            root.join("main.rs"),
+            "fn main() {}\n//TODO bug: parse flags\n//TODO feature: retry\n",
+        )
+        .unwrap();
+        let later = start + Duration::from_secs(60);
+        assert_eq!(
+            descriptions(&index.update("main.rs", later)),
+            ["+ main.rs:3 feature: retry"]
+        );
+        assert_eq!(index.todos().next().unwrap().submission.line_number, 2);
+
+        let stable = index.stable(Duration::from_secs(30), later);
+        assert_eq!(stable.len(), 1);
+        assert_eq!(stable[0].description, "parse flags");
+        assert_eq!(
+            index
+                .stable(Duration::from_secs(30), later + Duration::from_secs(30))
+                .len(),
+            2
+        );
+
+        fs::remove_file(root.join("main.rs")).unwrap();
+        assert_eq!(
+            descriptions(&index.update("main.rs", later)),
+            ["- main.rs:2 bug: parse flags", "- main.rs:3 feature: retry"]
+        );
+        assert_eq!(index.todos().count(), 0);
+    }
+
+    #[test]
+    fn picks_changed_files_from_notifications() {
+        let root = Path::new("/work/repo");
+        let event = Event::new(EventKind::Modify(ModifyKind::Any))
+            .add_path(root.join("src/main.rs"))
+            .add_path(root.join(".git/index"))
+            .add_path(root.join("tissue_box.json"))
+            .add_path(PathBuf::from("/elsewhere/lib.rs"));
+
+        assert_eq!(changed_files(root, &event), ["src/main.rs"]);
+        let opened =
+            Event::new(EventKind::Access(AccessKind::Any)).add_path(root.join("src/main.rs"));
+        assert!(changed_files(root, &opened).is_empty());
+        let created =
+            Event::new(EventKind::Create(CreateKind::File)).add_path(root.join("src/lib.rs"));
+        assert_eq!(changed_files(root, &created), ["src/lib.rs"]);
+    }
+}